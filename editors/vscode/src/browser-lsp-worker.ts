@@ -53,6 +53,22 @@ slint_init(slint_wasm_data).then((_) => {
         },
     );
 
+    connection.onNotification(
+        "slint/activeDocumentChanged",
+        // eslint-disable-next-line @typescript-eslint/no-explicit-any
+        (params: any) => {
+            the_lsp.active_document_changed(params);
+        },
+    );
+
+    connection.onNotification(
+        "slint/cursorMoved",
+        // eslint-disable-next-line @typescript-eslint/no-explicit-any
+        (params: any) => {
+            the_lsp.cursor_moved(params);
+        },
+    );
+
     connection.onDidChangeTextDocument(async (param) => {
         await the_lsp.reload_document(
             param.contentChanges[param.contentChanges.length - 1].text,
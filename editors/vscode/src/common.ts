@@ -105,6 +105,13 @@ export function setServerStatus(
 export function languageClientOptions(): LanguageClientOptions {
     return {
         documentSelector: [{ language: "slint" }, { language: "rust" }],
+        synchronize: {
+            // Report changed images and fonts, so that the preview can reload them,
+            // and deleted Slint files, so that it stops using their contents
+            fileEvents: vscode.workspace.createFileSystemWatcher(
+                "**/*.{png,jpg,jpeg,gif,bmp,webp,svg,svgz,ttf,otf,ttc,slint}",
+            ),
+        },
         middleware: {
             async provideCodeActions(
                 document: vscode.TextDocument,
@@ -206,6 +213,44 @@ export function activate(
     );
     properties_provider.refresh_view();
 
+    // Let the preview show the file that became active
+    vscode.window.onDidChangeActiveTextEditor((editor) => {
+        const doc = editor?.document;
+        if (
+            !doc ||
+            doc.languageId !== "slint" ||
+            !vscode.workspace
+                .getConfiguration("slint")
+                .get<boolean>("preview.autoOpen")
+        ) {
+            return;
+        }
+        client.client?.sendNotification("slint/activeDocumentChanged", {
+            uri: doc.uri.toString(),
+        });
+    });
+
+    // Let the preview highlight the element at the cursor
+    vscode.window.onDidChangeTextEditorSelection((ev) => {
+        const doc = ev.textEditor.document;
+        if (
+            ev.selections.length === 0 ||
+            doc.languageId !== "slint" ||
+            !vscode.workspace
+                .getConfiguration("slint")
+                .get<boolean>("preview.followCursor")
+        ) {
+            return;
+        }
+        client.client?.sendNotification("slint/cursorMoved", {
+            uri: doc.uri.toString(),
+            position: {
+                line: ev.selections[0].active.line,
+                character: ev.selections[0].active.character,
+            },
+        });
+    });
+
     vscode.workspace.onDidChangeTextDocument(async (ev) => {
         if (
             ev.document.languageId !== "slint" &&
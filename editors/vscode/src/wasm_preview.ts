@@ -77,7 +77,8 @@ function open_preview(context: vscode.ExtensionContext): boolean {
     const panel = vscode.window.createWebviewPanel(
         "slint-preview",
         "Slint Preview",
-        vscode.ViewColumn.Beside,
+        // Keep the focus in the editor, the preview can also open on its own
+        { viewColumn: vscode.ViewColumn.Beside, preserveFocus: true },
         { enableScripts: true, retainContextWhenHidden: true },
     );
     previewPanel = initPreviewPanel(context, panel);
@@ -85,9 +85,12 @@ fn create_default_backend() -> Result<Box<dyn Platform + 'static>, PlatformError
         }
 
         pub fn create_backend() -> Result<Box<dyn Platform + 'static>, PlatformError>  {
+            create_backend_by_name(&std::env::var("SLINT_BACKEND").unwrap_or_default())
+        }
 
-            let backend_config = std::env::var("SLINT_BACKEND").unwrap_or_default();
-
+        /// Create the backend named by `backend_config`, in the format of the `SLINT_BACKEND`
+        /// environment variable. Falls back to the default backend if that one is not available.
+        pub fn create_backend_by_name(backend_config: &str) -> Result<Box<dyn Platform + 'static>, PlatformError>  {
             let backend_config = backend_config.to_lowercase();
             let (event_loop, _renderer) = backend_config.split_once('-').unwrap_or(match backend_config.as_str() {
                 "qt" => ("qt", ""),
@@ -121,6 +124,9 @@ pub fn create_backend() -> Result<Box<dyn Platform + 'static>, PlatformError>  {
         pub fn create_backend() -> Result<Box<dyn Platform + 'static>, PlatformError> {
             Err(PlatformError::NoPlatform)
         }
+        pub fn create_backend_by_name(_backend_config: &str) -> Result<Box<dyn Platform + 'static>, PlatformError> {
+            Err(PlatformError::NoPlatform)
+        }
         pub mod native_widgets {}
         pub type NativeWidgets = ();
         pub type NativeGlobals = ();
@@ -950,6 +950,17 @@ pub fn close_popup(&self) {
         }
     }
 
+    /// Returns the component of the active popup and its position in the window, if the popup is
+    /// rendered within the window itself.
+    pub fn active_popup_component(&self) -> Option<(ItemTreeRc, LogicalPoint)> {
+        self.active_popup.borrow().as_ref().and_then(|popup| match popup.location {
+            PopupWindowLocation::TopLevel { .. } => None,
+            PopupWindowLocation::ChildWindow(coordinates) => {
+                Some((popup.component.clone(), coordinates))
+            }
+        })
+    }
+
     /// Returns true if the currently active popup is configured to close on click. None if there is no active popup.
     pub fn close_popup_on_click(&self) -> bool {
         self.active_popup.borrow().as_ref().map_or(false, |popup| popup.close_on_click)
@@ -1206,6 +1206,59 @@ pub fn element_position(
     ) -> Option<i_slint_core::lengths::LogicalRect> {
         crate::highlight::element_position(&self.inner, element)
     }
+
+    /// Whether the `element` is visible, i.e. neither it nor any of its parents is hidden.
+    ///
+    /// WARNING: this is not part of the public API
+    #[cfg(feature = "highlight")]
+    pub fn element_is_visible(&self, element: &i_slint_compiler::object_tree::ElementRc) -> bool {
+        crate::highlight::element_is_visible(&self.inner, element)
+    }
+
+    /// The item of the first occurrence of the `element`, e.g. to query its accessibility properties.
+    ///
+    /// WARNING: this is not part of the public API
+    #[cfg(feature = "highlight")]
+    pub fn element_item(
+        &self,
+        element: &i_slint_compiler::object_tree::ElementRc,
+    ) -> Option<i_slint_core::items::ItemRc> {
+        crate::highlight::element_item(&self.inner, element)
+    }
+
+    /// The value of the property `name` of the first occurrence of the `element`.
+    ///
+    /// Returns `None` if the element has no such property or no item in the instance.
+    ///
+    /// WARNING: this is not part of the public API
+    #[cfg(feature = "highlight")]
+    pub fn element_property(
+        &self,
+        element: &i_slint_compiler::object_tree::ElementRc,
+        name: &str,
+    ) -> Option<Value> {
+        crate::highlight::element_property(&self.inner, element, name)
+    }
+
+    /// The root element of the popup that is open in the window of this instance, if any.
+    /// The functions looking up elements also find the elements of that popup.
+    ///
+    /// WARNING: this is not part of the public API
+    #[cfg(feature = "highlight")]
+    pub fn open_popup_root_element(&self) -> Option<i_slint_compiler::object_tree::ElementRc> {
+        crate::highlight::open_popup_root_element(&self.inner)
+    }
+
+    /// The minimum, preferred and maximum size of the root element along `orientation`.
+    ///
+    /// WARNING: this is not part of the public API
+    #[cfg(feature = "highlight")]
+    pub fn root_layout_info(
+        &self,
+        orientation: i_slint_core::layout::Orientation,
+    ) -> i_slint_core::layout::LayoutInfo {
+        crate::highlight::root_layout_info(&self.inner, orientation)
+    }
 }
 
 impl ComponentHandle for ComponentInstance {
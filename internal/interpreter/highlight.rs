@@ -3,10 +3,13 @@
 
 //! This module contains the code for the highlight of some elements
 
-use crate::dynamic_item_tree::{DynamicComponentVRc, ItemTreeBox};
+use crate::dynamic_item_tree::{DynamicComponentVRc, ErasedItemTreeBox, InstanceRef, ItemTreeBox};
+use crate::Value;
 use i_slint_compiler::object_tree::{Component, Element, ElementRc};
 use i_slint_core::items::ItemRc;
-use i_slint_core::lengths::LogicalRect;
+use i_slint_core::layout::{LayoutInfo, Orientation};
+use i_slint_core::lengths::{LogicalPoint, LogicalRect, LogicalVector};
+use i_slint_core::window::WindowInner;
 use std::cell::RefCell;
 use std::path::PathBuf;
 use std::rc::Rc;
@@ -29,23 +32,80 @@ pub struct ComponentPositions {
     pub geometries: Vec<i_slint_core::lengths::LogicalRect>,
 }
 
+// The popup that is open in the window of `component_instance`, with its position in the window
+fn open_popup(
+    component_instance: &DynamicComponentVRc,
+) -> Option<(DynamicComponentVRc, LogicalPoint)> {
+    let window_adapter = component_instance.window_adapter_ref().ok()?;
+    let (popup, position) =
+        WindowInner::from_pub(window_adapter.window()).active_popup_component()?;
+    // Popups of interpreted components are interpreted components as well
+    let popup_ref = VRc::borrow(&popup);
+    let popup_box = popup_ref.downcast::<ErasedItemTreeBox>()?;
+    generativity::make_guard!(guard);
+    let popup = popup_box.unerase(guard).borrow_instance().self_weak().get()?.upgrade()?;
+    Some((popup, position))
+}
+
+pub(crate) fn open_popup_root_element(
+    component_instance: &DynamicComponentVRc,
+) -> Option<ElementRc> {
+    let (popup, _) = open_popup(component_instance)?;
+    generativity::make_guard!(guard);
+    let root_element = popup.unerase(guard).description().original.root_element.clone();
+    Some(root_element)
+}
+
+// The item tree holding the items of `element`: the component instance itself, or the popup
+// that is open in its window. Returns it with the repeaters leading from it to the element,
+// and with how far its coordinates are from the ones of the component instance.
+fn element_item_tree(
+    component_instance: &DynamicComponentVRc,
+    element: &ElementRc,
+) -> Option<(DynamicComponentVRc, Vec<String>, LogicalVector)> {
+    if let Some(repeater_path) = repeater_path(element, None) {
+        return Some((component_instance.clone(), repeater_path, LogicalVector::default()));
+    }
+    let (popup, position) = open_popup(component_instance)?;
+    let repeater_path = {
+        generativity::make_guard!(guard);
+        let popup_component = popup.unerase(guard).description().original.clone();
+        repeater_path(element, Some(&popup_component))?
+    };
+    let origin = ItemRc::new(VRc::into_dyn(component_instance.clone()), 0)
+        .map_to_window(LogicalPoint::default());
+    Some((popup, repeater_path, position - origin))
+}
+
 fn collect_highlight_data(
     component: &DynamicComponentVRc,
     elements: &[std::rc::Weak<RefCell<Element>>],
 ) -> ComponentPositions {
-    let component_instance = VRc::downgrade(component);
-    let component_instance = component_instance.upgrade().unwrap();
-    generativity::make_guard!(guard);
-    let c = component_instance.unerase(guard);
     let mut values = ComponentPositions::default();
     for element in elements.iter().filter_map(|e| e.upgrade()) {
-        if let Some(repeater_path) = repeater_path(&element) {
-            fill_highlight_data(&repeater_path, &element, &c, &c, &mut values);
-        }
+        fill_element_highlight_data(component, &element, &mut values);
     }
     values
 }
 
+fn fill_element_highlight_data(
+    component_instance: &DynamicComponentVRc,
+    element: &ElementRc,
+    values: &mut ComponentPositions,
+) {
+    let Some((item_tree, repeater_path, offset)) = element_item_tree(component_instance, element)
+    else {
+        return;
+    };
+    generativity::make_guard!(guard);
+    let c = item_tree.unerase(guard);
+    let first_new = values.geometries.len();
+    fill_highlight_data(&repeater_path, element, &c, &c, values);
+    for geometry in &mut values.geometries[first_new..] {
+        geometry.origin += offset;
+    }
+}
+
 pub(crate) fn component_positions(
     component_instance: &DynamicComponentVRc,
     path: PathBuf,
@@ -65,14 +125,82 @@ pub(crate) fn element_position(
     component_instance: &DynamicComponentVRc,
     element: &ElementRc,
 ) -> Option<LogicalRect> {
+    let mut values = ComponentPositions::default();
+    fill_element_highlight_data(component_instance, element, &mut values);
+    values.geometries.get(0).cloned()
+}
+
+pub(crate) fn element_is_visible(
+    component_instance: &DynamicComponentVRc,
+    element: &ElementRc,
+) -> bool {
+    element_item(component_instance, element).map_or(false, |item_rc| item_rc.is_visible())
+}
+
+pub(crate) fn element_item(
+    component_instance: &DynamicComponentVRc,
+    element: &ElementRc,
+) -> Option<ItemRc> {
+    let index = element.borrow().item_index.get().copied()?;
+    let (item_tree, repeater_path, _) = element_item_tree(component_instance, element)?;
     generativity::make_guard!(guard);
-    let c = component_instance.unerase(guard);
+    let c = item_tree.unerase(guard);
 
-    let mut values = ComponentPositions::default();
-    if let Some(repeater_path) = repeater_path(element) {
-        fill_highlight_data(&repeater_path, &element, &c, &c, &mut values);
+    with_first_instance(&repeater_path, &c, &mut |instance| {
+        let vrc = VRc::into_dyn(instance.self_weak().get().unwrap().upgrade().unwrap());
+        Some(ItemRc::new(vrc, index))
+    })
+}
+
+pub(crate) fn root_layout_info(
+    component_instance: &DynamicComponentVRc,
+    orientation: Orientation,
+) -> LayoutInfo {
+    VRc::borrow_pin(component_instance).as_ref().layout_info(orientation)
+}
+
+pub(crate) fn element_property(
+    component_instance: &DynamicComponentVRc,
+    element: &ElementRc,
+    name: &str,
+) -> Option<Value> {
+    // Elements that got optimized away have no item to read the property from
+    element.borrow().item_index.get()?;
+    let (item_tree, repeater_path, _) = element_item_tree(component_instance, element)?;
+    generativity::make_guard!(guard);
+    let c = item_tree.unerase(guard);
+
+    with_first_instance(&repeater_path, &c, &mut |instance| {
+        crate::eval::load_property(instance, element, name).ok()
+    })
+}
+
+// Calls `f` with the instance holding the first occurrence of the element, the one
+// `element_position` returns the geometry of
+fn with_first_instance<R>(
+    repeater_path: &[String],
+    component_instance: &ItemTreeBox,
+    f: &mut dyn FnMut(InstanceRef<'_, '_>) -> Option<R>,
+) -> Option<R> {
+    if let [first, rest @ ..] = repeater_path {
+        generativity::make_guard!(guard);
+        let rep = crate::dynamic_item_tree::get_repeater_by_name(
+            component_instance.borrow_instance(),
+            first.as_str(),
+            guard,
+        );
+        for idx in rep.0.range() {
+            if let Some(c) = rep.0.instance_at(idx) {
+                generativity::make_guard!(guard);
+                if let Some(result) = with_first_instance(rest, &c.unerase(guard), f) {
+                    return Some(result);
+                }
+            }
+        }
+        None
+    } else {
+        f(component_instance.borrow_instance())
     }
-    values.geometries.get(0).cloned()
 }
 
 fn fill_highlight_data(
@@ -146,16 +274,21 @@ fn find_element_at_offset(component: &Rc<Component>, path: PathBuf, offset: u32)
     result
 }
 
-fn repeater_path(elem: &ElementRc) -> Option<Vec<String>> {
+// The names of the repeaters from the root of the item tree down to `elem`. That is the item
+// tree of the `popup` component if given, the one of the main component otherwise.
+fn repeater_path(elem: &ElementRc, popup: Option<&Rc<Component>>) -> Option<Vec<String>> {
     let enclosing = elem.borrow().enclosing_component.upgrade().unwrap();
+    if popup.map_or(false, |popup| Rc::ptr_eq(popup, &enclosing)) {
+        return Some(vec![]);
+    }
     if let Some(parent) = enclosing.parent_element.upgrade() {
-        // This is not a repeater, it might be a popup menu which is not supported ATM
+        // This is not a repeater, but a popup, which has an item tree of its own
         parent.borrow().repeated.as_ref()?;
 
-        let mut r = repeater_path(&parent)?;
+        let mut r = repeater_path(&parent, popup)?;
         r.push(parent.borrow().id.clone());
         Some(r)
     } else {
-        Some(vec![])
+        popup.is_none().then(Vec::new)
     }
 }
@@ -3,6 +3,7 @@
 
 //! Data structures common between LSP and previewer
 
+use lsp_types::WorkspaceEdit;
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
@@ -17,6 +18,209 @@ pub struct PreviewConfig {
     pub style: String,
     pub include_paths: Vec<PathBuf>,
     pub library_paths: HashMap<String, PathBuf>,
+    #[serde(default)]
+    pub selection_colors: SelectionColors,
+    /// Time in milliseconds to wait for further changes before reloading the preview after
+    /// a change to one of its files. The preview picks its default when not set.
+    #[serde(default)]
+    pub reload_debounce_ms: Option<u64>,
+    #[serde(default)]
+    pub background: PreviewBackground,
+    /// Overrides the scale factor of the preview window. The preview clamps it to a sane range.
+    #[serde(default)]
+    pub scale_factor: Option<f32>,
+    /// Forces the previewed component to this logical width instead of its preferred one.
+    #[serde(default)]
+    pub preview_width: Option<f32>,
+    /// Forces the previewed component to this logical height instead of its preferred one.
+    #[serde(default)]
+    pub preview_height: Option<f32>,
+    /// Show a grid with rulers behind the previewed component
+    #[serde(default)]
+    pub show_grid: bool,
+    /// The distance between the lines of the grid in logical pixels. The preview picks its
+    /// default when not set.
+    #[serde(default)]
+    pub grid_spacing: Option<f32>,
+    /// Glob patterns of files that get loaded, but whose changes do not reload the preview
+    #[serde(default)]
+    pub watch_exclude: Vec<String>,
+    /// The sizes to show the component at side by side in the multi-viewport mode
+    #[serde(default)]
+    pub viewport_presets: Vec<ViewportPreset>,
+    #[serde(default)]
+    pub diagnostics_scope: DiagnosticsScope,
+    /// Do not show components that compile with warnings, like with errors
+    #[serde(default)]
+    pub deny_warnings: bool,
+    /// The directory relative include and library paths are resolved against. The directory
+    /// of the previewed file is used when not set.
+    #[serde(default)]
+    pub workspace_root: Option<PathBuf>,
+    /// Keep showing the last component that built successfully when the current one fails
+    /// to, instead of a placeholder with the error
+    #[serde(default)]
+    pub keep_preview_on_error: bool,
+    /// How much the preview reports about what it is doing
+    #[serde(default)]
+    pub log_level: PreviewLogLevel,
+    /// Highlight the element at the cursor of the editor, as reported by `cursor_moved`
+    #[serde(default)]
+    pub follow_cursor: bool,
+    /// Preview the main component of a Slint file when it becomes the active document in the
+    /// editor, as reported by `ActiveDocumentChangedNotification`. A pinned preview stays.
+    #[serde(default)]
+    pub auto_open: bool,
+    /// Show the source code of an element in the editor when it gets selected in the preview.
+    /// Defaults to true.
+    #[serde(default)]
+    pub reveal_on_select: Option<bool>,
+    /// Ask in the editor before applying a change the preview does to the source code, like
+    /// moving, inserting or deleting an element. Defaults to true.
+    #[serde(default)]
+    pub confirm_edits: Option<bool>,
+    /// Types of elements, like layouts, that clicking in the preview selects through to the
+    /// element in them. `DEFAULT_SELECT_THROUGH_TYPES` is used when not set, an empty list
+    /// makes all elements selectable.
+    #[serde(default)]
+    pub select_through_types: Option<Vec<String>>,
+    /// The title of the native preview window. The name of the previewed component is used
+    /// when not set.
+    #[serde(default)]
+    pub window_title: Option<String>,
+    /// The icon of the native preview window, the Slint logo when not set
+    #[serde(default)]
+    pub window_icon: Option<PathBuf>,
+    /// The renderer of the preview window. The window system of the preview picks it when it
+    /// starts, so changes only take effect after restarting the language server.
+    #[serde(default)]
+    pub renderer: PreviewRenderer,
+    /// A design image to compare the previewed component against
+    #[serde(default)]
+    pub reference_image: Option<ReferenceImage>,
+}
+
+/// An image shown on the canvas of the preview together with the component, like a mockup
+#[derive(Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ReferenceImage {
+    /// The image file. Relative paths are relative to the workspace root.
+    pub path: PathBuf,
+    /// From 0 (invisible) to 1 (opaque)
+    pub opacity: f32,
+    /// The logical position of the top left corner of the image, relative to the component
+    pub x: f32,
+    pub y: f32,
+    /// Draw the image on top of the component instead of behind it
+    pub above: bool,
+}
+
+impl Default for ReferenceImage {
+    fn default() -> Self {
+        Self { path: PathBuf::new(), opacity: 0.5, x: 0., y: 0., above: false }
+    }
+}
+
+/// The element types that get selected through when `select_through_types` is not configured
+pub const DEFAULT_SELECT_THROUGH_TYPES: &[&str] = &[
+    "HorizontalLayout",
+    "VerticalLayout",
+    "GridLayout",
+    "HorizontalBox",
+    "VerticalBox",
+    "GridBox",
+];
+
+/// A size to show the previewed component at, like a phone or a desktop screen
+#[derive(Default, Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+pub struct ViewportPreset {
+    pub name: String,
+    /// The logical width
+    pub width: f32,
+    /// The logical height, the preferred height of the component is used when not set
+    #[serde(default)]
+    pub height: Option<f32>,
+}
+
+/// The files the preview publishes diagnostics for
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticsScope {
+    /// All files that got loaded to build the preview
+    #[default]
+    All,
+    /// Only the file containing the previewed component
+    PreviewedFile,
+    /// The previewed file and the files it imports directly
+    DirectImports,
+}
+
+/// How much the preview logs about reloads and highlights
+#[derive(
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Debug,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewLogLevel {
+    #[default]
+    Off,
+    /// Reloads, and what triggered them
+    Info,
+    /// Also the state of the loading machinery and highlight requests
+    Debug,
+}
+
+/// How the canvas behind the previewed component is drawn.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewBackground {
+    #[default]
+    Light,
+    Dark,
+    /// A checkerboard pattern, to make transparent areas visible
+    Checkerboard,
+}
+
+/// Renderers the preview window can use, if they are built in
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewRenderer {
+    /// Whatever the backend picks
+    #[default]
+    Default,
+    Femtovg,
+    Skia,
+    Software,
+}
+
+/// Colors used to highlight selected elements in the preview, encoded as ARGB.
+/// The preview picks its default for colors that are not set.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SelectionColors {
+    pub primary: Option<u32>,
+    pub primary_layout: Option<u32>,
+    pub secondary: Option<u32>,
+    pub secondary_layout: Option<u32>,
+}
+
+/// The configuration the preview passed to the compiler for the last build, after all fallbacks
+#[derive(Default, Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+pub struct PreviewCompilerConfig {
+    pub path: PathBuf,
+    pub component: Option<String>,
+    pub style: String,
+    pub include_paths: Vec<PathBuf>,
+    pub library_paths: HashMap<String, PathBuf>,
+    pub deny_warnings: bool,
 }
 
 /// API used by the LSP to talk to the Preview. The other direction uses the
@@ -24,9 +228,67 @@ pub struct PreviewConfig {
 pub trait PreviewApi {
     fn set_use_external_previewer(&self, use_external: bool);
     fn set_contents(&self, path: &Path, contents: &str);
+    /// Forget the contents of a file that got deleted. Renames are a removal followed by `set_contents`.
+    fn remove_contents(&self, path: &Path);
     fn load_preview(&self, component: PreviewComponent);
+    /// Preview `source`, which is not saved anywhere. `component.path` names the snippet.
+    fn load_preview_from_source(&self, component: PreviewComponent, source: String);
     fn config_changed(&self, config: PreviewConfig);
     fn highlight(&self, path: Option<PathBuf>, offset: u32) -> Result<()>;
+    /// Like `highlight`, with a line and column (both starting at 1) instead of a byte offset
+    fn highlight_at_line_column(&self, path: PathBuf, line: u32, column: u32) -> Result<()>;
+    /// The cursor of the editor moved to `line` and `column` (both starting at 1) of `path`.
+    /// The preview highlights the element there if it is configured to follow the cursor.
+    fn cursor_moved(&self, path: PathBuf, line: u32, column: u32);
+    /// Highlight all elements whose source code overlaps the range between the two offsets
+    fn highlight_range(&self, path: PathBuf, start_offset: u32, end_offset: u32) -> Result<()>;
+    /// Reload the current component, even if no file has changed
+    fn force_reload(&self);
+    /// Render a new instance of the current component into an image file. Only the values of
+    /// its public properties are taken over from the shown one.
+    fn save_screenshot(&self, path: PathBuf) -> Result<()>;
+    /// Write a JSON description of the rendered elements of the current component into a file
+    fn save_render_tree(&self, path: PathBuf) -> Result<()>;
+    /// Write a JSON description of what assistive technology sees of the current component into a file
+    fn save_accessibility_tree(&self, path: PathBuf) -> Result<()>;
+    /// The compiler configuration used for the last build of the preview, if any
+    fn compiler_config(&self) -> Result<Option<PreviewCompilerConfig>>;
+    /// Whether the preview is loading a component or about to, e.g. to show a busy indicator
+    fn is_loading(&self) -> Result<bool>;
+    /// The files the preview has the contents of, and whether the previewed component uses them
+    fn cached_files(&self) -> Result<Vec<(PathBuf, bool)>>;
+    /// Check that the preview can compile, show and render a trivial component
+    fn self_test(&self);
+    /// Compile the previewed component once more and send every diagnostic of that build to the
+    /// editor's log, without changing the preview or its configuration
+    fn recompile_verbose(&self);
+    /// Switch between the last component that built and the error of the current code
+    fn toggle_last_good_preview(&self);
+    /// Change a property of the current component instance, without recompiling it
+    fn set_preview_property(&self, name: String, value: serde_json::Value);
+    /// Invoke a callback or function of the current component instance, e.g. to simulate a click
+    fn invoke_preview_callback(&self, name: String, args: Vec<serde_json::Value>);
+    /// Highlight all elements with the given id or type name. An empty query clears this.
+    fn highlight_matching(&self, query: String);
+    /// Show the next (or previous) diagnostic of the preview in the editor
+    fn show_next_diagnostic(&self, backwards: bool);
+    /// Switch between selecting elements and interacting with the previewed component
+    fn set_design_mode(&self, enabled: bool);
+    /// Show or hide the toolbar and panels of the preview, until the `hide_ui` configuration changes.
+    /// With `show_selections`, the selection stays visible while these are hidden.
+    fn set_preview_ui_visible(&self, visible: bool, show_selections: bool);
+    /// Lock the preview for demos: no selecting, highlighting, design mode or toolbar, only
+    /// interacting with the component. Turning it off restores the previous state.
+    fn set_presentation_mode(&self, enabled: bool);
+    /// Show an image on the canvas to compare the component against, or remove it with None,
+    /// until the `reference_image` configuration changes
+    fn set_reference_image(&self, image: Option<ReferenceImage>);
+    /// Undo the last change the preview did to the source code, e.g. by moving an element
+    fn undo_last_preview_edit(&self);
+    /// Redo the change to the source code undone last
+    fn redo_preview_edit(&self);
+    /// Files that are not Slint sources (images, fonts) changed on disk
+    fn resources_changed(&self, paths: Vec<PathBuf>);
 
     /// What is the current component to preview?
     fn current_component(&self) -> Option<PreviewComponent>;
@@ -34,7 +296,7 @@ pub trait PreviewApi {
 
 /// The Component to preview
 #[allow(unused)]
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, PartialEq, Debug)]
 pub struct PreviewComponent {
     /// The file name to preview
     pub path: PathBuf,
@@ -44,15 +306,97 @@ pub struct PreviewComponent {
 
     /// The style name for the preview
     pub style: String,
+
+    /// Property assignments and other element contents to put into the `_Preview`
+    /// component that wraps the component, e.g. `text: "Hello"; enabled: true;`
+    pub wrapper_body: Option<String>,
 }
 
 #[allow(unused)]
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub enum LspToPreviewMessage {
-    SetContents { path: String, contents: String },
-    SetConfiguration { config: PreviewConfig },
-    ShowPreview { path: String, component: Option<String>, style: String },
-    HighlightFromEditor { path: Option<String>, offset: u32 },
+    SetContents {
+        path: String,
+        contents: String,
+    },
+    RemoveContents {
+        path: String,
+    },
+    CursorMoved {
+        path: String,
+        line: u32,
+        column: u32,
+    },
+    SetConfiguration {
+        config: PreviewConfig,
+    },
+    ShowPreview {
+        path: String,
+        component: Option<String>,
+        style: String,
+        wrapper_body: Option<String>,
+    },
+    ShowPreviewFromSource {
+        path: String,
+        component: Option<String>,
+        style: String,
+        contents: String,
+    },
+    HighlightFromEditor {
+        path: Option<String>,
+        offset: u32,
+    },
+    HighlightAtLineColumn {
+        path: String,
+        line: u32,
+        column: u32,
+    },
+    HighlightRange {
+        path: String,
+        start_offset: u32,
+        end_offset: u32,
+    },
+    ForceReload,
+    SetProperty {
+        name: String,
+        value: serde_json::Value,
+    },
+    InvokeCallback {
+        name: String,
+        args: Vec<serde_json::Value>,
+    },
+    HighlightMatching {
+        query: String,
+    },
+    ShowNextDiagnostic {
+        backwards: bool,
+    },
+    SetDesignMode {
+        enabled: bool,
+    },
+    SetPresentationMode {
+        enabled: bool,
+    },
+    SetReferenceImage {
+        image: Option<ReferenceImage>,
+    },
+    SetPreviewUiVisible {
+        visible: bool,
+        show_selections: bool,
+    },
+    UndoPreviewEdit,
+    RedoPreviewEdit,
+    ResourcesChanged {
+        paths: Vec<String>,
+    },
+    SelfTest,
+    ToggleLastGoodPreview,
+    RecompileVerbose,
+    /// Whether the editor applied the `SendWorkspaceEdit` with the same `id`
+    WorkspaceEditApplied {
+        id: u64,
+        applied: bool,
+    },
 }
 
 #[allow(unused)]
@@ -71,6 +415,14 @@ pub enum PreviewToLspMessage {
     Status { message: String, health: crate::lsp_ext::Health },
     Diagnostics { uri: lsp_types::Url, diagnostics: Vec<lsp_types::Diagnostic> },
     ShowDocument { file: String, selection: lsp_types::Range },
+    ElementSelected { file: String, selection: lsp_types::Range },
+    Dependencies { files: Vec<PathBuf> },
+    Style { style: String, available_styles: Vec<String> },
+    ResourceUrls { resources: Vec<crate::lsp_ext::ResourceUrl> },
+    Loading { loading: bool },
     PreviewTypeChanged { is_external: bool },
     RequestState { unused: bool }, // send all documents!
+    SendWorkspaceEdit { id: u64, label: Option<String>, edit: WorkspaceEdit, confirm: bool },
+    DiagnosticsReport { report: String },
+    ComponentSize { size: crate::lsp_ext::PreviewComponentSizeParams },
 }
@@ -10,8 +10,12 @@
 #[cfg(test)]
 mod test;
 
-use crate::common::{PreviewApi, PreviewConfig, Result};
+use crate::common::{
+    DiagnosticsScope, PreviewApi, PreviewBackground, PreviewConfig, PreviewLogLevel,
+    PreviewRenderer, ReferenceImage, Result, SelectionColors,
+};
 use crate::language::properties::find_element_indent;
+use crate::lsp_ext::{Health, ServerStatusNotification, ServerStatusParams};
 use crate::util::{lookup_current_element_type, map_node, map_range, map_token, to_lsp_diag};
 
 #[cfg(target_arch = "wasm32")]
@@ -23,9 +27,10 @@
 use i_slint_compiler::CompilerConfiguration;
 use i_slint_compiler::{
     diagnostics::{BuildDiagnostics, SourceFileVersion},
-    langtype::Type,
+    langtype::{ElementType, Type},
 };
 use i_slint_compiler::{typeloader::TypeLoader, typeregister::TypeRegister};
+use lsp_types::notification::Notification;
 use lsp_types::request::{
     CodeActionRequest, CodeLensRequest, ColorPresentationRequest, Completion, DocumentColor,
     DocumentHighlightRequest, DocumentSymbolRequest, ExecuteCommand, GotoDefinition, HoverRequest,
@@ -50,6 +55,31 @@
 const REMOVE_BINDING_COMMAND: &str = "slint/removeBinding";
 const SHOW_PREVIEW_COMMAND: &str = "slint/showPreview";
 const SET_BINDING_COMMAND: &str = "slint/setBinding";
+const RELOAD_PREVIEW_COMMAND: &str = "slint/reloadPreview";
+const SAVE_SCREENSHOT_COMMAND: &str = "slint/saveScreenshot";
+const SAVE_RENDER_TREE_COMMAND: &str = "slint/saveRenderTree";
+const SAVE_ACCESSIBILITY_TREE_COMMAND: &str = "slint/saveAccessibilityTree";
+const PREVIEW_COMPILER_CONFIG_COMMAND: &str = "slint/previewCompilerConfig";
+const PREVIEW_IS_LOADING_COMMAND: &str = "slint/previewIsLoading";
+const PREVIEW_CACHED_FILES_COMMAND: &str = "slint/previewCachedFiles";
+const PREVIEW_SELF_TEST_COMMAND: &str = "slint/previewSelfTest";
+const RECOMPILE_VERBOSE_COMMAND: &str = "slint/recompileVerbose";
+const TOGGLE_LAST_GOOD_PREVIEW_COMMAND: &str = "slint/toggleLastGoodPreview";
+const SET_PREVIEW_PROPERTY_COMMAND: &str = "slint/setPreviewProperty";
+const INVOKE_PREVIEW_CALLBACK_COMMAND: &str = "slint/invokePreviewCallback";
+const HIGHLIGHT_MATCHING_COMMAND: &str = "slint/highlightMatching";
+const HIGHLIGHT_AT_LINE_COLUMN_COMMAND: &str = "slint/highlightAtLineColumn";
+const HIGHLIGHT_RANGE_COMMAND: &str = "slint/highlightRange";
+const NEXT_PREVIEW_DIAGNOSTIC_COMMAND: &str = "slint/nextPreviewDiagnostic";
+const PREVIOUS_PREVIEW_DIAGNOSTIC_COMMAND: &str = "slint/previousPreviewDiagnostic";
+const SET_DESIGN_MODE_COMMAND: &str = "slint/setDesignMode";
+const SET_PRESENTATION_MODE_COMMAND: &str = "slint/setPresentationMode";
+const SET_REFERENCE_IMAGE_COMMAND: &str = "slint/setReferenceImage";
+const SET_PREVIEW_UI_VISIBLE_COMMAND: &str = "slint/setPreviewUiVisible";
+const UNDO_PREVIEW_EDIT_COMMAND: &str = "slint/undoPreviewEdit";
+const REDO_PREVIEW_EDIT_COMMAND: &str = "slint/redoPreviewEdit";
+const LIST_PREVIEW_COMPONENTS_COMMAND: &str = "slint/listPreviewComponents";
+const SHOW_PREVIEW_FROM_SOURCE_COMMAND: &str = "slint/showPreviewFromSource";
 
 pub fn uri_to_file(uri: &lsp_types::Url) -> Option<PathBuf> {
     let Ok(path) = uri.to_file_path() else { return None };
@@ -57,12 +87,82 @@ pub fn uri_to_file(uri: &lsp_types::Url) -> Option<PathBuf> {
     Some(cleaned_path)
 }
 
+/// Parse a command argument that is either a URL or a file path.
+///
+/// Absolute paths are tried first: Windows paths like `C:\a.slint` parse as URLs with the
+/// drive letter as scheme otherwise.
+#[cfg(any(feature = "preview-builtin", feature = "preview-external", test))]
+fn url_or_file_path(s: &str) -> Option<Url> {
+    if std::path::Path::new(s).is_absolute() {
+        Url::from_file_path(s).ok()
+    } else {
+        Url::parse(s).or_else(|_| Url::from_file_path(s)).ok()
+    }
+}
+
+/// The file given as command argument, as URL or as file path. Relative paths are kept as is.
+#[cfg(any(feature = "preview-builtin", feature = "preview-external", test))]
+fn path_argument(value: &serde_json::Value) -> Result<PathBuf> {
+    let s = value.as_str().ok_or("InvalidParameter")?;
+    Ok(url_or_file_path(s).and_then(|url| uri_to_file(&url)).unwrap_or_else(|| PathBuf::from(s)))
+}
+
 fn command_list() -> Vec<String> {
     vec![
         QUERY_PROPERTIES_COMMAND.into(),
         REMOVE_BINDING_COMMAND.into(),
         #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
         SHOW_PREVIEW_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        RELOAD_PREVIEW_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        SAVE_SCREENSHOT_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        SAVE_RENDER_TREE_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        SAVE_ACCESSIBILITY_TREE_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        PREVIEW_COMPILER_CONFIG_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        PREVIEW_IS_LOADING_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        PREVIEW_CACHED_FILES_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        PREVIEW_SELF_TEST_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        RECOMPILE_VERBOSE_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        TOGGLE_LAST_GOOD_PREVIEW_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        SET_PREVIEW_PROPERTY_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        INVOKE_PREVIEW_CALLBACK_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        HIGHLIGHT_MATCHING_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        HIGHLIGHT_AT_LINE_COLUMN_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        HIGHLIGHT_RANGE_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        NEXT_PREVIEW_DIAGNOSTIC_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        PREVIOUS_PREVIEW_DIAGNOSTIC_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        SET_DESIGN_MODE_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        SET_PRESENTATION_MODE_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        SET_REFERENCE_IMAGE_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        SET_PREVIEW_UI_VISIBLE_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        UNDO_PREVIEW_EDIT_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        REDO_PREVIEW_EDIT_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        LIST_PREVIEW_COMPONENTS_COMMAND.into(),
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        SHOW_PREVIEW_FROM_SOURCE_COMMAND.into(),
         SET_BINDING_COMMAND.into(),
     ]
 }
@@ -80,6 +180,29 @@ fn create_show_preview_command(
     )
 }
 
+/// Ask the user whether to apply the change to the source code the preview wants to do,
+/// returns whether they agreed
+#[cfg(any(feature = "preview-engine", target_arch = "wasm32"))]
+pub async fn confirm_preview_edit(sender: &crate::ServerNotifier, label: Option<&str>) -> bool {
+    let action = |title: &str| lsp_types::MessageActionItem {
+        title: title.into(),
+        properties: Default::default(),
+    };
+    let Ok(fut) = sender.send_request::<lsp_types::request::ShowMessageRequest>(
+        lsp_types::ShowMessageRequestParams {
+            typ: lsp_types::MessageType::INFO,
+            message: format!(
+                "The preview wants to change the source code: {}",
+                label.unwrap_or("edit")
+            ),
+            actions: Some(vec![action("Apply"), action("Discard")]),
+        },
+    ) else {
+        return false;
+    };
+    matches!(fut.await, Ok(Some(chosen)) if chosen.title == "Apply")
+}
+
 #[cfg(feature = "preview-external")]
 pub fn request_state(ctx: &std::rc::Rc<Context>) {
     let cache = ctx.document_cache.borrow();
@@ -122,6 +245,10 @@ pub struct Context {
     pub server_notifier: crate::ServerNotifier,
     pub init_param: InitializeParams,
     pub preview: Rc<dyn PreviewApi>,
+    /// The directories searched for previewable components together with the `.slint`
+    /// files found in them. Reset when a `.slint` file gets created or deleted.
+    #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+    pub slint_files: RefCell<Option<(Vec<PathBuf>, Vec<PathBuf>)>>,
 }
 
 #[derive(Default)]
@@ -282,6 +409,151 @@ pub fn register_request_handlers(rh: &mut RequestHandler) {
             show_preview_command(&params.arguments, &ctx)?;
             return Ok(None::<serde_json::Value>);
         }
+        if params.command.as_str() == SHOW_PREVIEW_FROM_SOURCE_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            show_preview_from_source_command(&params.arguments, &ctx)?;
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == RELOAD_PREVIEW_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            ctx.preview.force_reload();
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == SAVE_SCREENSHOT_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            save_screenshot_command(&params.arguments, &ctx)?;
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == SAVE_RENDER_TREE_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            save_render_tree_command(&params.arguments, &ctx)?;
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == SAVE_ACCESSIBILITY_TREE_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            save_accessibility_tree_command(&params.arguments, &ctx)?;
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == PREVIEW_COMPILER_CONFIG_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            return Ok(Some(serde_json::to_value(ctx.preview.compiler_config()?)?));
+        }
+        if params.command.as_str() == PREVIEW_IS_LOADING_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            return Ok(Some(serde_json::to_value(ctx.preview.is_loading()?)?));
+        }
+        if params.command.as_str() == PREVIEW_CACHED_FILES_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            return Ok(Some(serde_json::Value::Array(
+                ctx.preview
+                    .cached_files()?
+                    .into_iter()
+                    .map(|(path, dependency)| {
+                        serde_json::json!({ "path": path, "dependency": dependency })
+                    })
+                    .collect(),
+            )));
+        }
+        if params.command.as_str() == PREVIEW_SELF_TEST_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            ctx.preview.self_test();
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == RECOMPILE_VERBOSE_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            ctx.preview.recompile_verbose();
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == TOGGLE_LAST_GOOD_PREVIEW_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            ctx.preview.toggle_last_good_preview();
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == SET_PREVIEW_PROPERTY_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            set_preview_property_command(&params.arguments, &ctx)?;
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == INVOKE_PREVIEW_CALLBACK_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            invoke_preview_callback_command(&params.arguments, &ctx)?;
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == HIGHLIGHT_MATCHING_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            highlight_matching_command(&params.arguments, &ctx)?;
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == HIGHLIGHT_AT_LINE_COLUMN_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            highlight_at_line_column_command(&params.arguments, &ctx)?;
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == HIGHLIGHT_RANGE_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            highlight_range_command(&params.arguments, &ctx)?;
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == NEXT_PREVIEW_DIAGNOSTIC_COMMAND
+            || params.command.as_str() == PREVIOUS_PREVIEW_DIAGNOSTIC_COMMAND
+        {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            ctx.preview.show_next_diagnostic(
+                params.command.as_str() == PREVIOUS_PREVIEW_DIAGNOSTIC_COMMAND,
+            );
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == SET_PRESENTATION_MODE_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            ctx.preview.set_presentation_mode(
+                params.arguments.first().and_then(|v| v.as_bool()).ok_or("InvalidParameter")?,
+            );
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == SET_REFERENCE_IMAGE_COMMAND {
+            // A null argument removes the reference image
+            let image = match params.arguments.first() {
+                None | Some(serde_json::Value::Null) => None,
+                Some(image) => Some(
+                    serde_json::from_value::<ReferenceImage>(image.clone())
+                        .map_err(|_| "InvalidParameter")?,
+                ),
+            };
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            ctx.preview.set_reference_image(image);
+            #[cfg(not(any(feature = "preview-builtin", feature = "preview-external")))]
+            let _ = image;
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == SET_DESIGN_MODE_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            ctx.preview.set_design_mode(
+                params.arguments.first().and_then(|v| v.as_bool()).ok_or("InvalidParameter")?,
+            );
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == SET_PREVIEW_UI_VISIBLE_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            ctx.preview.set_preview_ui_visible(
+                params.arguments.first().and_then(|v| v.as_bool()).ok_or("InvalidParameter")?,
+                params.arguments.get(1).and_then(|v| v.as_bool()).unwrap_or(false),
+            );
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == UNDO_PREVIEW_EDIT_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            ctx.preview.undo_last_preview_edit();
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == REDO_PREVIEW_EDIT_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            ctx.preview.redo_preview_edit();
+            return Ok(None::<serde_json::Value>);
+        }
+        if params.command.as_str() == LIST_PREVIEW_COMPONENTS_COMMAND {
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            return Ok(Some(list_preview_components_command(&ctx)?));
+        }
         if params.command.as_str() == QUERY_PROPERTIES_COMMAND {
             return Ok(Some(query_properties_command(&params.arguments, &ctx)?));
         }
@@ -408,22 +680,307 @@ pub fn show_preview_command(params: &[serde_json::Value], ctx: &Rc<Context>) ->
     let e = || "InvalidParameter";
 
     let url = if let serde_json::Value::String(s) = params.first().ok_or_else(e)? {
-        Url::parse(s)?
+        url_or_file_path(s).ok_or_else(e)?
     } else {
         return Err(e().into());
     };
     let component =
         params.get(1).and_then(|v| v.as_str()).filter(|v| !v.is_empty()).map(|v| v.to_string());
+    // Contents of the `_Preview` component wrapping the previewed one
+    let wrapper_body =
+        params.get(2).and_then(|v| v.as_str()).filter(|v| !v.is_empty()).map(|v| v.to_string());
     let path = uri_to_file(&url).unwrap_or_default();
+    let style = config.style.clone().unwrap_or_default();
+
+    if let Some(component_name) = &component {
+        if !is_previewable_component(document_cache, &path, component_name) {
+            ctx.server_notifier.send_notification(
+                ServerStatusNotification::METHOD.into(),
+                ServerStatusParams {
+                    health: Health::Error,
+                    quiescent: false,
+                    message: Some(format!(
+                        "Cannot preview {component_name}: No such component in {}",
+                        path.to_string_lossy()
+                    )),
+                },
+            )?;
+            return Ok(());
+        }
+    }
 
     ctx.preview.load_preview(crate::common::PreviewComponent {
         path,
         component,
-        style: config.style.clone().unwrap_or_default(),
+        style,
+        wrapper_body,
     });
     Ok(())
 }
 
+/// Preview the main component, the last exported one, of the document at `url` once it became
+/// the active document in the editor, if configured to. Documents without exported components
+/// are left alone.
+pub fn auto_open_preview(ctx: &Rc<Context>, url: &Url) {
+    let document_cache = ctx.document_cache.borrow();
+    if !document_cache.preview_config.auto_open {
+        return;
+    }
+    let Some(path) = uri_to_file(url) else {
+        return;
+    };
+    let Some(source) = document_cache
+        .documents
+        .get_document(&path)
+        .and_then(|doc| Some(doc.node.as_ref()?.text().to_string()))
+    else {
+        return;
+    };
+    let Some(component) = crate::util::exported_component_names(&source, &path).pop() else {
+        return;
+    };
+    let style = document_cache.documents.compiler_config.style.clone().unwrap_or_default();
+    drop(document_cache);
+
+    ctx.preview.load_preview(crate::common::PreviewComponent {
+        path,
+        component: Some(component),
+        style,
+        wrapper_body: None,
+    });
+}
+
+/// Preview source code that is not saved anywhere. The parameters are a name for the
+/// snippet, its source code and optionally the component to show.
+#[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+fn show_preview_from_source_command(params: &[serde_json::Value], ctx: &Rc<Context>) -> Result<()> {
+    let path = params.first().and_then(|v| v.as_str()).ok_or("InvalidParameter")?;
+    let source = params.get(1).and_then(|v| v.as_str()).ok_or("InvalidParameter")?;
+    let component =
+        params.get(2).and_then(|v| v.as_str()).filter(|v| !v.is_empty()).map(|v| v.to_string());
+    let style =
+        ctx.document_cache.borrow().documents.compiler_config.style.clone().unwrap_or_default();
+
+    ctx.preview.load_preview_from_source(
+        crate::common::PreviewComponent {
+            path: PathBuf::from(path),
+            component,
+            style,
+            wrapper_body: None,
+        },
+        source.to_string(),
+    );
+    Ok(())
+}
+
+/// Save an image of the previewed component to the file given as argument.
+///
+/// The image shows a new instance of the component that only gets the values of its public
+/// properties from the shown one, not the window of the preview itself.
+#[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+fn save_screenshot_command(params: &[serde_json::Value], ctx: &Rc<Context>) -> Result<()> {
+    let path = path_argument(params.first().ok_or("InvalidParameter")?)?;
+    ctx.preview.save_screenshot(path)
+}
+
+#[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+fn save_render_tree_command(params: &[serde_json::Value], ctx: &Rc<Context>) -> Result<()> {
+    let path = path_argument(params.first().ok_or("InvalidParameter")?)?;
+    ctx.preview.save_render_tree(path)
+}
+
+#[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+fn save_accessibility_tree_command(params: &[serde_json::Value], ctx: &Rc<Context>) -> Result<()> {
+    let path = path_argument(params.first().ok_or("InvalidParameter")?)?;
+    ctx.preview.save_accessibility_tree(path)
+}
+
+#[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+fn set_preview_property_command(params: &[serde_json::Value], ctx: &Rc<Context>) -> Result<()> {
+    let e = || "InvalidParameter";
+    let name = params.first().and_then(|v| v.as_str()).ok_or_else(e)?;
+    let value = params.get(1).ok_or_else(e)?;
+    ctx.preview.set_preview_property(name.to_string(), value.clone());
+    Ok(())
+}
+
+/// Invoke the callback named by the first argument, with the remaining arguments
+#[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+fn invoke_preview_callback_command(params: &[serde_json::Value], ctx: &Rc<Context>) -> Result<()> {
+    let name = params.first().and_then(|v| v.as_str()).ok_or("InvalidParameter")?;
+    ctx.preview.invoke_preview_callback(name.to_string(), params[1..].to_vec());
+    Ok(())
+}
+
+/// Highlight all elements with the id or type name given as argument, or clear
+/// these highlights when no argument is given.
+#[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+fn highlight_matching_command(params: &[serde_json::Value], ctx: &Rc<Context>) -> Result<()> {
+    let query = match params.first() {
+        None | Some(serde_json::Value::Null) => String::new(),
+        Some(v) => v.as_str().ok_or("InvalidParameter")?.trim().to_string(),
+    };
+    ctx.preview.highlight_matching(query);
+    Ok(())
+}
+
+/// Highlight the element at the line and column (both starting at 1) of the file given
+/// as path or URL.
+#[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+fn highlight_at_line_column_command(params: &[serde_json::Value], ctx: &Rc<Context>) -> Result<()> {
+    let e = || "InvalidParameter";
+    let path = path_argument(params.first().ok_or_else(e)?)?;
+    let line = params.get(1).and_then(|v| v.as_u64()).ok_or_else(e)?;
+    let column = params.get(2).and_then(|v| v.as_u64()).ok_or_else(e)?;
+    ctx.preview.highlight_at_line_column(
+        path,
+        line.try_into().unwrap_or(u32::MAX),
+        column.try_into().unwrap_or(u32::MAX),
+    )
+}
+
+/// Highlight the elements in the range of the document, given as URL and LSP range, e.g.
+/// the selection of the editor.
+#[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+fn highlight_range_command(params: &[serde_json::Value], ctx: &Rc<Context>) -> Result<()> {
+    let e = || "InvalidParameter";
+    let uri: Url = serde_json::from_value(params.first().ok_or_else(e)?.clone())?;
+    let range: lsp_types::Range = serde_json::from_value(params.get(1).ok_or_else(e)?.clone())?;
+    let path = uri_to_file(&uri).ok_or_else(e)?;
+    let (start_offset, end_offset) = {
+        let document_cache = &mut ctx.document_cache.borrow_mut();
+        let (_, start) =
+            get_document_and_offset(document_cache, &uri, &range.start).ok_or_else(e)?;
+        let (_, end) = get_document_and_offset(document_cache, &uri, &range.end).ok_or_else(e)?;
+        (start, end)
+    };
+    ctx.preview.highlight_range(path, start_offset, end_offset)
+}
+
+/// Returns false if `component_name` is known not to be a component that can be
+/// previewed from the document at `path`.
+///
+/// Documents that are not loaded are not checked, the preview will report errors for those.
+#[cfg(any(feature = "preview-builtin", feature = "preview-external", test))]
+fn is_previewable_component(
+    document_cache: &DocumentCache,
+    path: &std::path::Path,
+    component_name: &str,
+) -> bool {
+    let Some(doc) = document_cache.documents.get_document(path) else {
+        return true;
+    };
+    matches!(
+        doc.local_registry.lookup_element(component_name),
+        Ok(ElementType::Component(c)) if !c.is_global()
+    )
+}
+
+#[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+fn list_preview_components_command(ctx: &Rc<Context>) -> Result<serde_json::Value> {
+    let mut directories =
+        ctx.document_cache.borrow().documents.compiler_config.include_paths.clone();
+    if let Some(folders) = &ctx.init_param.workspace_folders {
+        directories.extend(folders.iter().filter_map(|f| uri_to_file(&f.uri)));
+    } else if let Some(root) = ctx.init_param.root_uri.as_ref().and_then(uri_to_file) {
+        directories.push(root);
+    }
+
+    let files = match ctx.slint_files.take() {
+        Some((searched, files)) if searched == directories => files,
+        _ => slint_files(&directories),
+    };
+    let components = previewable_components(&ctx.document_cache.borrow(), &files);
+    ctx.slint_files.replace(Some((directories, files)));
+    Ok(serde_json::to_value(components)?)
+}
+
+/// The exported components of all loaded documents and of the given `.slint` `files`,
+/// sorted by file.
+///
+/// Files that cannot be read are skipped. Only the syntax is looked at, so files with
+/// errors still contribute the components that could be parsed.
+#[cfg(any(feature = "preview-builtin", feature = "preview-external", test))]
+fn previewable_components(
+    document_cache: &DocumentCache,
+    files: &[PathBuf],
+) -> Vec<crate::lsp_ext::PreviewableComponent> {
+    let mut sources = std::collections::BTreeMap::new();
+    for (path, doc) in document_cache.documents.all_file_documents() {
+        if path.starts_with("builtin:/") {
+            continue;
+        }
+        if let Some(node) = &doc.node {
+            sources.insert(path.clone(), node.text().to_string());
+        }
+    }
+
+    for file in files {
+        let file = clean_path(file);
+        if sources.contains_key(&file) {
+            continue;
+        }
+        if let Ok(source) = std::fs::read_to_string(&file) {
+            sources.insert(file, source);
+        }
+    }
+
+    sources
+        .iter()
+        .filter_map(|(path, source)| {
+            Some((lsp_types::Url::from_file_path(path).ok()?, path, source))
+        })
+        .flat_map(|(uri, path, source)| {
+            crate::util::exported_component_names(source, path).into_iter().map(move |component| {
+                crate::lsp_ext::PreviewableComponent { uri: uri.clone(), component }
+            })
+        })
+        .collect()
+}
+
+/// The `.slint` files found in `directories`
+#[cfg(any(feature = "preview-builtin", feature = "preview-external", test))]
+fn slint_files(directories: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for directory in directories {
+        collect_slint_files(directory, 0, &mut files);
+    }
+    files
+}
+
+/// How many directories deep `collect_slint_files` looks for files
+#[cfg(any(feature = "preview-builtin", feature = "preview-external", test))]
+const MAX_DIRECTORY_DEPTH: usize = 16;
+
+// Recursively collect the `.slint` files in `directory`, which is `depth` levels below the
+// directory the search started in. Hidden directories, the usual build output folders and
+// symbolic links to directories are skipped, the latter as they can form loops.
+#[cfg(any(feature = "preview-builtin", feature = "preview-external", test))]
+fn collect_slint_files(directory: &std::path::Path, depth: usize, files: &mut Vec<PathBuf>) {
+    if depth > MAX_DIRECTORY_DEPTH {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        // Unlike `Path::is_dir`, this does not follow symbolic links
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if !name.starts_with('.') && name != "target" && name != "node_modules" {
+                collect_slint_files(&path, depth + 1, files);
+            }
+        } else if path.extension().map_or(false, |e| e == "slint") {
+            files.push(path);
+        }
+    }
+}
+
 pub fn query_properties_command(
     params: &[serde_json::Value],
     ctx: &Rc<Context>,
@@ -1248,6 +1805,29 @@ pub async fn load_configuration(ctx: &Context) -> Result<()> {
 
     let document_cache = &mut ctx.document_cache.borrow_mut();
     let mut hide_ui = None;
+    let mut selection_colors = SelectionColors::default();
+    let mut reload_debounce_ms = None;
+    let mut background = PreviewBackground::default();
+    let mut scale_factor = None;
+    let mut preview_size = (None, None);
+    let mut show_grid = false;
+    let mut grid_spacing = None;
+    let mut viewport_presets = Vec::new();
+    let mut watch_exclude = Vec::new();
+    let mut diagnostics_scope = DiagnosticsScope::default();
+    let mut deny_warnings = false;
+    let mut keep_preview_on_error = false;
+    let mut follow_cursor = false;
+    let mut auto_open = false;
+    let mut reveal_on_select = None;
+    let mut confirm_edits = None;
+    let mut select_through_types = None;
+    let mut window_title = None;
+    let mut window_icon = None;
+    let mut workspace_root = None;
+    let mut log_level = PreviewLogLevel::default();
+    let mut renderer = PreviewRenderer::default();
+    let mut reference_image = None;
     for v in r {
         if let Some(o) = v.as_object() {
             if let Some(ip) = o.get("includePaths").and_then(|v| v.as_array()) {
@@ -1272,6 +1852,103 @@ pub async fn load_configuration(ctx: &Context) -> Result<()> {
                 }
             }
             hide_ui = o.get("preview").and_then(|v| v.as_object()?.get("hide_ui")?.as_bool());
+            if let Some(colors) =
+                o.get("preview").and_then(|v| v.as_object()?.get("selectionColors")?.as_object())
+            {
+                let color = |name: &str| {
+                    i_slint_compiler::literals::parse_color_literal(colors.get(name)?.as_str()?)
+                };
+                selection_colors = SelectionColors {
+                    primary: color("primary"),
+                    primary_layout: color("primaryLayout"),
+                    secondary: color("secondary"),
+                    secondary_layout: color("secondaryLayout"),
+                };
+            }
+            reload_debounce_ms =
+                o.get("preview").and_then(|v| v.as_object()?.get("reloadDebounceMs")?.as_u64());
+            if let Some(bg) = o.get("preview").and_then(|v| v.as_object()?.get("background")) {
+                background = serde_json::from_value(bg.clone()).unwrap_or_default();
+            }
+            scale_factor = o
+                .get("preview")
+                .and_then(|v| v.as_object()?.get("scaleFactor")?.as_f64())
+                .map(|f| f as f32);
+            let size = |name: &str| {
+                o.get("preview").and_then(|v| v.as_object()?.get(name)?.as_f64()).map(|s| s as f32)
+            };
+            preview_size = (size("width"), size("height"));
+            show_grid = o
+                .get("preview")
+                .and_then(|v| v.as_object()?.get("showGrid")?.as_bool())
+                .unwrap_or(false);
+            grid_spacing = size("gridSpacing");
+            if let Some(presets) =
+                o.get("preview").and_then(|v| v.as_object()?.get("viewportPresets"))
+            {
+                viewport_presets = serde_json::from_value(presets.clone()).unwrap_or_default();
+            }
+            if let Some(patterns) =
+                o.get("preview").and_then(|v| v.as_object()?.get("watchExclude")?.as_array())
+            {
+                watch_exclude =
+                    patterns.iter().filter_map(|p| Some(p.as_str()?.to_string())).collect();
+            }
+            if let Some(scope) =
+                o.get("preview").and_then(|v| v.as_object()?.get("diagnosticsScope"))
+            {
+                diagnostics_scope = serde_json::from_value(scope.clone()).unwrap_or_default();
+            }
+            deny_warnings = o
+                .get("preview")
+                .and_then(|v| v.as_object()?.get("denyWarnings")?.as_bool())
+                .unwrap_or(false);
+            workspace_root = o
+                .get("preview")
+                .and_then(|v| v.as_object()?.get("workspaceRoot")?.as_str())
+                .filter(|root| !root.is_empty())
+                .map(PathBuf::from);
+            keep_preview_on_error = o
+                .get("preview")
+                .and_then(|v| v.as_object()?.get("keepPreviewOnError")?.as_bool())
+                .unwrap_or(false);
+            follow_cursor = o
+                .get("preview")
+                .and_then(|v| v.as_object()?.get("followCursor")?.as_bool())
+                .unwrap_or(false);
+            reveal_on_select =
+                o.get("preview").and_then(|v| v.as_object()?.get("revealOnSelect")?.as_bool());
+            confirm_edits =
+                o.get("preview").and_then(|v| v.as_object()?.get("confirmEdits")?.as_bool());
+            auto_open = o
+                .get("preview")
+                .and_then(|v| v.as_object()?.get("autoOpen")?.as_bool())
+                .unwrap_or(false);
+            select_through_types = o
+                .get("preview")
+                .and_then(|v| v.as_object()?.get("selectThroughTypes")?.as_array())
+                .map(|types| types.iter().filter_map(|t| Some(t.as_str()?.to_string())).collect());
+            if let Some(level) = o.get("preview").and_then(|v| v.as_object()?.get("logLevel")) {
+                log_level = serde_json::from_value(level.clone()).unwrap_or_default();
+            }
+            if let Some(name) = o.get("preview").and_then(|v| v.as_object()?.get("renderer")) {
+                renderer = serde_json::from_value(name.clone()).unwrap_or_default();
+            }
+            window_title = o
+                .get("preview")
+                .and_then(|v| v.as_object()?.get("windowTitle")?.as_str())
+                .filter(|title| !title.is_empty())
+                .map(String::from);
+            window_icon = o
+                .get("preview")
+                .and_then(|v| v.as_object()?.get("windowIcon")?.as_str())
+                .filter(|icon| !icon.is_empty())
+                .map(PathBuf::from);
+            reference_image = o
+                .get("preview")
+                .and_then(|v| v.as_object()?.get("referenceImage"))
+                .and_then(|image| serde_json::from_value::<ReferenceImage>(image.clone()).ok())
+                .filter(|image| !image.path.as_os_str().is_empty());
         }
     }
 
@@ -1279,12 +1956,55 @@ pub async fn load_configuration(ctx: &Context) -> Result<()> {
     let mut diag = BuildDiagnostics::default();
     document_cache.documents.import_component("std-widgets.slint", "StyleMetrics", &mut diag).await;
 
+    // Relative roots are relative to the workspace folder
+    let workspace_folder = ctx
+        .init_param
+        .workspace_folders
+        .as_ref()
+        .and_then(|folders| uri_to_file(&folders.first()?.uri))
+        .or_else(|| ctx.init_param.root_uri.as_ref().and_then(uri_to_file));
+    let workspace_root = match (workspace_root, workspace_folder) {
+        (Some(root), Some(folder)) if root.is_relative() => Some(clean_path(&folder.join(root))),
+        (Some(root), _) => Some(root),
+        (None, folder) => folder,
+    };
+
+    if let (Some(image), Some(root)) = (&mut reference_image, &workspace_root) {
+        if image.path.is_relative() {
+            image.path = clean_path(&root.join(&image.path));
+        }
+    }
+
     let cc = &document_cache.documents.compiler_config;
     document_cache.preview_config = PreviewConfig {
         hide_ui,
         style: cc.style.clone().unwrap_or_default(),
         include_paths: cc.include_paths.clone(),
         library_paths: cc.library_paths.clone(),
+        selection_colors,
+        reload_debounce_ms,
+        background,
+        scale_factor,
+        preview_width: preview_size.0,
+        preview_height: preview_size.1,
+        show_grid,
+        grid_spacing,
+        watch_exclude,
+        viewport_presets,
+        diagnostics_scope,
+        deny_warnings,
+        workspace_root,
+        keep_preview_on_error,
+        log_level,
+        follow_cursor,
+        auto_open,
+        reveal_on_select,
+        confirm_edits,
+        renderer,
+        reference_image,
+        select_through_types,
+        window_title,
+        window_icon,
     };
     ctx.preview.config_changed(document_cache.preview_config.clone());
     Ok(())
@@ -1317,6 +2037,94 @@ fn test_reload_document_valid_contents() {
         assert!(diagnostics.is_empty());
     }
 
+    #[test]
+    fn test_is_previewable_component() {
+        let (dc, url, _) = loaded_document_cache(
+            r#"
+            component Helper inherits Rectangle { }
+            global Settings { }
+            export component Main inherits Rectangle { Helper { } }
+            "#
+            .into(),
+        );
+        let path = uri_to_file(&url).unwrap();
+
+        assert!(is_previewable_component(&dc, &path, "Main"));
+        assert!(is_previewable_component(&dc, &path, "Helper"));
+        assert!(!is_previewable_component(&dc, &path, "Settings"));
+        assert!(!is_previewable_component(&dc, &path, "Unknown"));
+        assert!(is_previewable_component(&dc, std::path::Path::new("/not/loaded.slint"), "Main"));
+    }
+
+    #[test]
+    fn test_url_or_file_path() {
+        let url = Url::parse("file:///a/b.slint").unwrap();
+        assert_eq!(url_or_file_path("file:///a/b.slint"), Some(url.clone()));
+        #[cfg(not(windows))]
+        assert_eq!(url_or_file_path("/a/b.slint"), Some(url));
+        #[cfg(windows)]
+        assert_eq!(
+            url_or_file_path("C:\\x\\a.slint"),
+            Some(Url::parse("file:///C:/x/a.slint").unwrap())
+        );
+        assert_eq!(url_or_file_path("b.slint"), None);
+    }
+
+    #[test]
+    fn test_path_argument() {
+        let path = |s: &str| path_argument(&serde_json::Value::from(s)).unwrap();
+        #[cfg(not(windows))]
+        {
+            assert_eq!(path("file:///a/../b.png"), PathBuf::from("/b.png"));
+            assert_eq!(path("/a/b.png"), PathBuf::from("/a/b.png"));
+        }
+        #[cfg(windows)]
+        assert_eq!(path("C:\\x\\b.png"), PathBuf::from("C:\\x\\b.png"));
+        assert_eq!(path("b.png"), PathBuf::from("b.png"));
+        assert!(path_argument(&serde_json::Value::from(1)).is_err());
+    }
+
+    #[test]
+    fn test_previewable_components() {
+        let (dc, url, _) = loaded_document_cache(
+            r#"
+            component Helper inherits Rectangle { }
+            export global Settings { }
+            export component Main inherits Rectangle { Helper { } }
+            "#
+            .into(),
+        );
+
+        let components = previewable_components(&dc, &[]);
+        assert_eq!(
+            components,
+            vec![crate::lsp_ext::PreviewableComponent { uri: url, component: "Main".into() }]
+        );
+        // Files that do not exist are skipped
+        assert_eq!(
+            previewable_components(&dc, &[PathBuf::from("/does/not/exist.slint")]),
+            components
+        );
+        // So are directories
+        assert!(slint_files(&[PathBuf::from("/does/not/exist")]).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_slint_files_symlink_loop() {
+        let directory =
+            std::env::temp_dir().join(format!("slint-lsp-collect-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("sub")).unwrap();
+        std::fs::write(directory.join("sub/a.slint"), "export component A { }").unwrap();
+        std::fs::write(directory.join("sub/b.txt"), "").unwrap();
+        std::os::unix::fs::symlink("..", directory.join("sub/loop")).unwrap();
+
+        let mut files = Vec::new();
+        collect_slint_files(&directory, 0, &mut files);
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(files, vec![directory.join("sub/a.slint")]);
+    }
+
     #[test]
     fn test_text_document_color_no_color_set() {
         let (mut dc, url, _) = loaded_document_cache(
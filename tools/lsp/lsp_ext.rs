@@ -28,3 +28,140 @@ pub enum Health {
     Warning,
     Error,
 }
+
+/// Sent by the preview when the user selected an element in it
+pub enum SelectElementNotification {}
+
+impl Notification for SelectElementNotification {
+    type Params = SelectElementParams;
+    const METHOD: &'static str = "slint/selectElement";
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct SelectElementParams {
+    pub uri: lsp_types::Url,
+    pub range: lsp_types::Range,
+}
+
+/// Sent by the editor when the cursor moved, so that the preview can follow it
+pub enum CursorMovedNotification {}
+
+impl Notification for CursorMovedNotification {
+    type Params = CursorMovedParams;
+    const METHOD: &'static str = "slint/cursorMoved";
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct CursorMovedParams {
+    pub uri: lsp_types::Url,
+    pub position: lsp_types::Position,
+}
+
+/// Sent by the editor when a document became the active one
+pub enum ActiveDocumentChangedNotification {}
+
+impl Notification for ActiveDocumentChangedNotification {
+    type Params = ActiveDocumentChangedParams;
+    const METHOD: &'static str = "slint/activeDocumentChanged";
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct ActiveDocumentChangedParams {
+    pub uri: lsp_types::Url,
+}
+
+/// Sent by the preview after loading a component, listing all files the preview depends on
+pub enum PreviewDependenciesNotification {}
+
+impl Notification for PreviewDependenciesNotification {
+    type Params = PreviewDependenciesParams;
+    const METHOD: &'static str = "slint/previewDependencies";
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct PreviewDependenciesParams {
+    pub uris: Vec<lsp_types::Url>,
+}
+
+/// Sent by the preview after loading a component, reporting the style it used
+pub enum PreviewStyleNotification {}
+
+impl Notification for PreviewStyleNotification {
+    type Params = PreviewStyleParams;
+    const METHOD: &'static str = "slint/previewStyle";
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewStyleParams {
+    pub style: String,
+    /// All styles the preview can use
+    pub available_styles: Vec<String>,
+}
+
+/// Sent by the preview when it starts loading a component, and when it is done loading
+pub enum PreviewLoadingNotification {}
+
+impl Notification for PreviewLoadingNotification {
+    type Params = PreviewLoadingParams;
+    const METHOD: &'static str = "slint/previewLoading";
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct PreviewLoadingParams {
+    pub loading: bool,
+}
+
+/// Sent by the preview after showing a component, with the sizes its root element asks for
+pub enum PreviewComponentSizeNotification {}
+
+impl Notification for PreviewComponentSizeNotification {
+    type Params = PreviewComponentSizeParams;
+    const METHOD: &'static str = "slint/previewComponentSize";
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub struct PreviewComponentSizeParams {
+    pub uri: lsp_types::Url,
+    /// The previewed component, `None` for the last one of the file
+    pub component: Option<String>,
+    pub width: SizeConstraints,
+    pub height: SizeConstraints,
+}
+
+/// Sizes along one direction, in logical pixels
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy, Debug)]
+pub struct SizeConstraints {
+    pub min: f32,
+    pub preferred: f32,
+    pub max: f32,
+}
+
+/// Sent by the preview after compiling a component, listing the resources (images) it
+/// requested and the URL the resource URL mapper turned them into
+pub enum PreviewResourceUrlsNotification {}
+
+impl Notification for PreviewResourceUrlsNotification {
+    type Params = PreviewResourceUrlsParams;
+    const METHOD: &'static str = "slint/previewResourceUrls";
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct PreviewResourceUrlsParams {
+    pub resources: Vec<ResourceUrl>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct ResourceUrl {
+    /// The path as written in the Slint code, made absolute
+    pub path: String,
+    /// The URL the path was mapped to, `None` if it is used as is
+    pub url: Option<String>,
+}
+
+/// A component that can be previewed, as listed by the `slint/listPreviewComponents` command
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct PreviewableComponent {
+    pub uri: lsp_types::Url,
+    pub component: String,
+}
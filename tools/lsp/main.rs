@@ -18,9 +18,13 @@
 
 use i_slint_compiler::CompilerConfiguration;
 use lsp_types::notification::{
-    DidChangeConfiguration, DidChangeTextDocument, DidOpenTextDocument, Notification,
+    DidChangeConfiguration, DidChangeTextDocument, DidChangeWatchedFiles, DidOpenTextDocument,
+    Notification,
+};
+use lsp_types::{
+    DidChangeTextDocumentParams, DidChangeWatchedFilesParams, DidOpenTextDocumentParams,
+    InitializeParams,
 };
-use lsp_types::{DidChangeTextDocumentParams, DidOpenTextDocumentParams, InitializeParams};
 
 use clap::Parser;
 use lsp_server::{Connection, ErrorCode, IoThreads, Message, RequestId, Response};
@@ -69,6 +73,38 @@ fn set_contents(&self, _path: &std::path::Path, _contents: &str) {
         }
     }
 
+    fn remove_contents(&self, _path: &std::path::Path) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::RemoveContents {
+                    path: _path.to_string_lossy().to_string(),
+                },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::remove_contents(_path);
+        }
+    }
+
+    fn cursor_moved(&self, _path: PathBuf, _line: u32, _column: u32) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::CursorMoved {
+                    path: _path.to_string_lossy().to_string(),
+                    line: _line,
+                    column: _column,
+                },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::cursor_moved(_path, _line, _column);
+        }
+    }
+
     fn load_preview(&self, component: common::PreviewComponent) {
         self.to_show.replace(Some(component.clone()));
 
@@ -80,6 +116,7 @@ fn load_preview(&self, component: common::PreviewComponent) {
                     path: component.path.to_string_lossy().to_string(),
                     component: component.component,
                     style: component.style.to_string(),
+                    wrapper_body: component.wrapper_body,
                 },
             );
         } else {
@@ -91,6 +128,27 @@ fn load_preview(&self, component: common::PreviewComponent) {
         }
     }
 
+    fn load_preview_from_source(&self, component: common::PreviewComponent, _source: String) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::ShowPreviewFromSource {
+                    path: component.path.to_string_lossy().to_string(),
+                    component: component.component,
+                    style: component.style.to_string(),
+                    contents: _source,
+                },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            {
+                preview::open_ui(&self.server_notifier);
+                preview::load_preview_from_source(component, _source);
+            }
+        }
+    }
+
     fn config_changed(&self, _config: crate::common::PreviewConfig) {
         if *self.use_external_previewer.borrow() {
             #[cfg(feature = "preview-external")]
@@ -124,6 +182,320 @@ fn highlight(&self, _path: Option<std::path::PathBuf>, _offset: u32) -> Result<(
         }
     }
 
+    fn highlight_at_line_column(
+        &self,
+        _path: std::path::PathBuf,
+        _line: u32,
+        _column: u32,
+    ) -> Result<()> {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::HighlightAtLineColumn {
+                    path: _path.to_string_lossy().to_string(),
+                    line: _line,
+                    column: _column,
+                },
+            )?;
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::highlight_at_line_column(&_path, _line, _column);
+        }
+        Ok(())
+    }
+
+    fn highlight_range(
+        &self,
+        _path: std::path::PathBuf,
+        _start_offset: u32,
+        _end_offset: u32,
+    ) -> Result<()> {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::HighlightRange {
+                    path: _path.to_string_lossy().to_string(),
+                    start_offset: _start_offset,
+                    end_offset: _end_offset,
+                },
+            )?;
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::highlight_range(_path, _start_offset, _end_offset);
+        }
+        Ok(())
+    }
+
+    fn force_reload(&self) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::ForceReload,
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::force_reload();
+        }
+    }
+
+    fn self_test(&self) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::SelfTest,
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::self_test();
+        }
+    }
+
+    fn recompile_verbose(&self) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::RecompileVerbose,
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::recompile_verbose();
+        }
+    }
+
+    fn toggle_last_good_preview(&self) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::ToggleLastGoodPreview,
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::toggle_last_good_preview();
+        }
+    }
+
+    fn save_screenshot(&self, _path: PathBuf) -> Result<()> {
+        if *self.use_external_previewer.borrow() {
+            Err("Screenshots are not supported by the external preview".into())
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::save_screenshot(_path)
+                .map_err(|e| format!("Failed to save screenshot: {e}"))?;
+            Ok(())
+        }
+    }
+
+    fn save_render_tree(&self, _path: PathBuf) -> Result<()> {
+        if *self.use_external_previewer.borrow() {
+            Err("Saving the render tree is not supported by the external preview".into())
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::save_render_tree(_path);
+            Ok(())
+        }
+    }
+
+    fn save_accessibility_tree(&self, _path: PathBuf) -> Result<()> {
+        if *self.use_external_previewer.borrow() {
+            Err("Saving the accessibility tree is not supported by the external preview".into())
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::save_accessibility_tree(_path);
+            Ok(())
+        }
+    }
+
+    fn compiler_config(&self) -> Result<Option<crate::common::PreviewCompilerConfig>> {
+        if *self.use_external_previewer.borrow() {
+            Err("Querying the compiler configuration is not supported by the external preview"
+                .into())
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            let config = preview::compiler_config();
+            #[cfg(not(feature = "preview-builtin"))]
+            let config = None;
+            Ok(config)
+        }
+    }
+
+    fn is_loading(&self) -> Result<bool> {
+        if *self.use_external_previewer.borrow() {
+            Err("Querying the loading state is not supported by the external preview".into())
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            let loading = preview::is_loading();
+            #[cfg(not(feature = "preview-builtin"))]
+            let loading = false;
+            Ok(loading)
+        }
+    }
+
+    fn cached_files(&self) -> Result<Vec<(std::path::PathBuf, bool)>> {
+        if *self.use_external_previewer.borrow() {
+            Err("Listing the cached files is not supported by the external preview".into())
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            let files = preview::cached_files();
+            #[cfg(not(feature = "preview-builtin"))]
+            let files = Vec::new();
+            Ok(files)
+        }
+    }
+
+    fn set_preview_property(&self, _name: String, _value: serde_json::Value) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::SetProperty { name: _name, value: _value },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::set_preview_property(_name, _value);
+        }
+    }
+
+    fn invoke_preview_callback(&self, _name: String, _args: Vec<serde_json::Value>) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::InvokeCallback { name: _name, args: _args },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::invoke_preview_callback(_name, _args);
+        }
+    }
+
+    fn highlight_matching(&self, _query: String) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::HighlightMatching { query: _query },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::highlight_matching(_query);
+        }
+    }
+
+    fn show_next_diagnostic(&self, _backwards: bool) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::ShowNextDiagnostic { backwards: _backwards },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::show_next_diagnostic(_backwards);
+        }
+    }
+
+    fn set_design_mode(&self, _enabled: bool) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::SetDesignMode { enabled: _enabled },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::set_design_mode(_enabled);
+        }
+    }
+
+    fn set_presentation_mode(&self, _enabled: bool) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::SetPresentationMode { enabled: _enabled },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::set_presentation_mode(_enabled);
+        }
+    }
+
+    fn set_reference_image(&self, _image: Option<crate::common::ReferenceImage>) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::SetReferenceImage { image: _image },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::set_reference_image(_image);
+        }
+    }
+
+    fn set_preview_ui_visible(&self, _visible: bool, _show_selections: bool) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::SetPreviewUiVisible {
+                    visible: _visible,
+                    show_selections: _show_selections,
+                },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::set_preview_ui_visible(_visible, _show_selections);
+        }
+    }
+
+    fn undo_last_preview_edit(&self) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::UndoPreviewEdit,
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::undo_last_preview_edit();
+        }
+    }
+
+    fn redo_preview_edit(&self) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::RedoPreviewEdit,
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::redo_preview_edit();
+        }
+    }
+
+    fn resources_changed(&self, _paths: Vec<PathBuf>) {
+        if *self.use_external_previewer.borrow() {
+            #[cfg(feature = "preview-external")]
+            let _ = self.server_notifier.send_notification(
+                "slint/lsp_to_preview".to_string(),
+                crate::common::LspToPreviewMessage::ResourcesChanged {
+                    paths: _paths.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+                },
+            );
+        } else {
+            #[cfg(feature = "preview-builtin")]
+            preview::resources_changed(_paths);
+        }
+    }
+
     fn current_component(&self) -> Option<crate::common::PreviewComponent> {
         self.to_show.borrow().clone()
     }
@@ -336,6 +708,8 @@ fn main_loop(connection: Connection, init_param: InitializeParams, cli_args: Cli
         server_notifier,
         init_param,
         preview,
+        #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+        slint_files: Default::default(),
     });
 
     let mut futures = Vec::<Pin<Box<dyn Future<Output = Result<()>>>>>::new();
@@ -429,6 +803,46 @@ async fn handle_notification(req: lsp_server::Notification, ctx: &Rc<Context>) -
         DidChangeConfiguration::METHOD => {
             load_configuration(ctx).await?;
         }
+        DidChangeWatchedFiles::METHOD => {
+            let params: DidChangeWatchedFilesParams = serde_json::from_value(req.params)?;
+            let mut paths = Vec::new();
+            for change in params.changes {
+                if change.uri.path().ends_with(".slint") {
+                    #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+                    if change.typ != lsp_types::FileChangeType::CHANGED {
+                        ctx.slint_files.borrow_mut().take();
+                    }
+                    // Open documents and their changes are reported by the editor already
+                    if change.typ == lsp_types::FileChangeType::DELETED {
+                        if let Some(path) = uri_to_file(&change.uri) {
+                            ctx.preview.remove_contents(&path);
+                        }
+                    }
+                } else if let Ok(path) = change.uri.to_file_path() {
+                    paths.push(path);
+                }
+            }
+            if !paths.is_empty() {
+                ctx.preview.resources_changed(paths);
+            }
+        }
+
+        lsp_ext::ActiveDocumentChangedNotification::METHOD => {
+            let params: lsp_ext::ActiveDocumentChangedParams = serde_json::from_value(req.params)?;
+            language::auto_open_preview(ctx, &params.uri);
+        }
+
+        lsp_ext::CursorMovedNotification::METHOD => {
+            let params: lsp_ext::CursorMovedParams = serde_json::from_value(req.params)?;
+            if let Some(path) = uri_to_file(&params.uri) {
+                // LSP positions start at 0
+                ctx.preview.cursor_moved(
+                    path,
+                    params.position.line + 1,
+                    params.position.character + 1,
+                );
+            }
+        }
 
         #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
         "slint/showPreview" => {
@@ -457,12 +871,53 @@ async fn handle_notification(req: lsp_server::Notification, ctx: &Rc<Context>) -
                     send_show_document_to_editor(ctx.server_notifier.clone(), file, selection)
                         .await;
                 }
+                M::ElementSelected { file, selection } => {
+                    crate::preview::send_element_selected_notification(
+                        &ctx.server_notifier,
+                        &file,
+                        selection,
+                    );
+                }
+                M::Dependencies { files } => {
+                    crate::preview::send_dependencies_notification(&ctx.server_notifier, &files);
+                }
+                M::Style { style, available_styles } => {
+                    crate::preview::send_style_notification(
+                        &ctx.server_notifier,
+                        style,
+                        available_styles,
+                    );
+                }
+                M::ResourceUrls { resources } => {
+                    crate::preview::send_resource_urls_notification(
+                        &ctx.server_notifier,
+                        resources,
+                    );
+                }
+                M::Loading { loading } => {
+                    crate::preview::send_loading_notification(&ctx.server_notifier, loading);
+                }
                 M::PreviewTypeChanged { is_external } => {
                     ctx.preview.set_use_external_previewer(is_external);
                 }
                 M::RequestState { .. } => {
                     crate::language::request_state(ctx);
                 }
+                M::SendWorkspaceEdit { id, label, edit, confirm } => {
+                    let applied =
+                        send_workspace_edit(ctx.server_notifier.clone(), label, edit, confirm)
+                            .await;
+                    let _ = ctx.server_notifier.send_notification(
+                        "slint/lsp_to_preview".to_string(),
+                        crate::common::LspToPreviewMessage::WorkspaceEditApplied { id, applied },
+                    );
+                }
+                M::DiagnosticsReport { report } => {
+                    crate::preview::send_log_message_notification(&ctx.server_notifier, &report);
+                }
+                M::ComponentSize { size } => {
+                    crate::preview::send_component_size_notification(&ctx.server_notifier, size);
+                }
             }
         }
         _ => (),
@@ -486,3 +941,24 @@ pub async fn send_show_document_to_editor(
 
     let _ = fut.await;
 }
+
+/// Ask the editor to apply `edit`, returns whether it did. With `confirm`, the user
+/// gets asked first.
+#[cfg(feature = "preview-engine")]
+pub async fn send_workspace_edit(
+    sender: ServerNotifier,
+    label: Option<String>,
+    edit: lsp_types::WorkspaceEdit,
+    confirm: bool,
+) -> bool {
+    if confirm && !language::confirm_preview_edit(&sender, label.as_deref()).await {
+        return false;
+    }
+    let Ok(fut) = sender.send_request::<lsp_types::request::ApplyWorkspaceEdit>(
+        lsp_types::ApplyWorkspaceEditParams { label, edit },
+    ) else {
+        return false;
+    };
+
+    fut.await.map_or(false, |response| response.applied)
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: GPL-3.0-only OR LicenseRef-Slint-Royalty-free-1.1 OR LicenseRef-Slint-commercial
 
 use std::{
+    cell::{Cell, RefCell},
     collections::{HashMap, HashSet},
     path::{Path, PathBuf},
     rc::Rc,
@@ -9,18 +10,26 @@
 };
 
 use crate::{
-    common::{PreviewComponent, PreviewConfig},
+    common::{
+        DiagnosticsScope, PreviewBackground, PreviewCompilerConfig, PreviewComponent,
+        PreviewConfig, ReferenceImage,
+    },
     lsp_ext::Health,
 };
-use i_slint_compiler::{diagnostics::SourceFile, object_tree::ElementRc};
+use i_slint_compiler::{
+    diagnostics::SourceFile,
+    object_tree::{ElementRc, ElementWeak},
+    parser::syntax_nodes,
+};
 use i_slint_core::{
+    accessibility::AccessibleStringProperty,
     component_factory::FactoryContext,
-    lengths::{LogicalLength, LogicalPoint, LogicalRect},
+    lengths::{LogicalLength, LogicalPoint, LogicalRect, LogicalVector},
 };
 use rowan::TextRange;
 use slint_interpreter::{
     highlight::{ComponentKind, ComponentPositions},
-    ComponentDefinition, ComponentHandle, ComponentInstance,
+    ComponentDefinition, ComponentHandle, ComponentInstance, DiagnosticLevel,
 };
 
 use lsp_types::notification::Notification;
@@ -29,7 +38,10 @@
 use crate::wasm_prelude::*;
 
 mod debug;
+mod element_edit;
+mod logging;
 mod ui;
+mod undo;
 #[cfg(all(target_arch = "wasm32", feature = "preview-external"))]
 mod wasm;
 #[cfg(all(target_arch = "wasm32", feature = "preview-external"))]
@@ -52,35 +64,207 @@ enum PreviewFutureState {
     NeedsReload,
 }
 
+impl PreviewFutureState {
+    fn is_loading(self) -> bool {
+        self != PreviewFutureState::Pending
+    }
+}
+
 #[derive(Default)]
 struct ContentCache {
     source_code: HashMap<PathBuf, String>,
+    /// The `content_hash` of each entry in `source_code`
+    source_hashes: HashMap<PathBuf, u64>,
     dependency: HashSet<PathBuf>,
+    /// Every Slint file the compiler asked for while building the current component,
+    /// including the ones excluded from being watched
+    loaded_files: HashSet<PathBuf>,
+    /// The files other than Slint sources (images, fonts) the current component uses
+    resource_dependency: HashSet<PathBuf>,
     current: PreviewComponent,
+    /// The last component with a non-empty path that was asked to be previewed
+    last_component: PreviewComponent,
     config: PreviewConfig,
     loading_state: PreviewFutureState,
     highlight: Option<(PathBuf, u32)>,
     ui_is_visible: bool,
+    /// Incremented for every change that schedules a debounced reload, only the
+    /// reload scheduled last is executed.
+    pending_reload: u64,
+    /// Counts the cursor movements, to only follow the last one
+    pending_cursor: u64,
+    /// Keep showing the current component when asked to preview a different file
+    pinned: bool,
+    /// Compile the next component from scratch instead of using a previously compiled one
+    skip_compiled_cache: bool,
+    /// Clicks into the preview select elements instead of interacting with the component
+    design_mode: bool,
+    /// The styles picked in the preview for the files previewed so far
+    style_overrides: HashMap<PathBuf, String>,
+    /// Whether the toolbar and panels are shown, as set by command. Overrides `config.hide_ui`
+    /// until that changes.
+    preview_ui_visible: Option<bool>,
+    /// Keep showing the selection while the toolbar and panels are hidden
+    selections_without_ui: bool,
+    /// What was passed to the compiler for the last build
+    compiler_config: Option<PreviewCompilerConfig>,
+    /// Set while the preview is in presentation mode
+    presentation: Option<PresentationRestore>,
+}
+
+/// What the presentation mode turned off, to turn it on again afterwards. Changes requested
+/// during the presentation end up here too.
+#[derive(Clone, Copy)]
+struct PresentationRestore {
+    design_mode: bool,
+    preview_ui_visible: Option<bool>,
+    selections_without_ui: bool,
+}
+
+impl ContentCache {
+    fn set_loading_state(&mut self, state: PreviewFutureState) {
+        logging::preview_log!(Debug, "loading state: {:?} -> {:?}", self.loading_state, state);
+        let was_loading = self.loading_state.is_loading();
+        self.loading_state = state;
+        if was_loading != state.is_loading() {
+            notify_loading(state.is_loading());
+        }
+    }
+
+    /// The hash of the contents the compiler gets for `path`: the ones in the cache, or else
+    /// the ones on disk. `None` if the file cannot be read.
+    fn file_hash(&self, path: &Path) -> Option<u64> {
+        self.source_hashes
+            .get(path)
+            .copied()
+            .or_else(|| Some(content_hash(&std::fs::read_to_string(path).ok()?)))
+    }
 }
 
+/// Default time to wait for further changes before reloading the preview
+const DEFAULT_RELOAD_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Time to wait for the cursor of the editor to stop moving before highlighting the element there
+const FOLLOW_CURSOR_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(150);
+
 static CONTENT_CACHE: std::sync::OnceLock<Mutex<ContentCache>> = std::sync::OnceLock::new();
 
+/// Returns whether the preview is currently loading a component, or about to
+pub fn is_loading() -> bool {
+    CONTENT_CACHE.get().map_or(false, |cache| cache.lock().unwrap().loading_state.is_loading())
+}
+
 pub fn set_contents(path: &Path, content: String) {
+    let hash = content_hash(&content);
     let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
-    let old = cache.source_code.insert(path.to_owned(), content.clone());
+    // Only compare the strings if the hashes match, as most calls do not change anything
+    if cache.source_hashes.get(path) == Some(&hash)
+        && cache.source_code.get(path).map_or(false, |old| *old == content)
+    {
+        return;
+    }
+    cache.source_code.insert(path.to_owned(), content);
+    cache.source_hashes.insert(path.to_owned(), hash);
     if cache.dependency.contains(path) {
-        if let Some(old) = old {
-            if content == old {
-                return;
-            }
-        }
+        logging::preview_log!(Info, "{} changed, reloading the preview", path.display());
+        schedule_reload(cache);
+    }
+}
+
+/// Forget the contents of `path`, e.g. because the file got deleted. The preview reloads
+/// if it used that file, so that the compiler reports the missing import.
+pub fn remove_contents(path: &Path) {
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    cache.source_hashes.remove(path);
+    if cache.source_code.remove(path).is_some() && cache.dependency.contains(path) {
+        logging::preview_log!(Info, "{} was removed, reloading the preview", path.display());
+        schedule_reload(cache);
+    }
+}
+
+/// Reload the current component after the reload delay, if it is shown
+fn schedule_reload(mut cache: std::sync::MutexGuard<ContentCache>) {
+    if !cache.ui_is_visible || cache.current.path.as_os_str().is_empty() {
+        return;
+    }
+    let debounce = cache
+        .config
+        .reload_debounce_ms
+        .map_or(DEFAULT_RELOAD_DEBOUNCE, std::time::Duration::from_millis);
+    if debounce.is_zero() {
         let current = cache.current.clone();
-        let ui_is_visible = cache.ui_is_visible;
         drop(cache);
+        load_preview(current);
+        return;
+    }
 
-        if ui_is_visible && !current.path.as_os_str().is_empty() {
-            load_preview(current);
-        }
+    cache.pending_reload += 1;
+    let pending_reload = cache.pending_reload;
+    drop(cache);
+
+    run_in_ui_thread(move || async move {
+        slint::Timer::single_shot(debounce, move || reload_if_still_pending(pending_reload));
+    });
+}
+
+/// Returns the files the currently previewed component depends on, as collected
+/// during the last load. This includes the images and fonts it uses.
+pub fn current_dependencies() -> Vec<PathBuf> {
+    let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let mut dependencies = cache
+        .dependency
+        .iter()
+        .chain(cache.resource_dependency.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+    dependencies.sort();
+    dependencies
+}
+
+/// Returns the files the preview has the contents of, and whether the current component
+/// uses them. Sorted by path.
+pub fn cached_files() -> Vec<(PathBuf, bool)> {
+    let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let mut files = cache
+        .source_code
+        .keys()
+        .map(|path| (path.clone(), cache.dependency.contains(path)))
+        .collect::<Vec<_>>();
+    drop(cache);
+    files.sort();
+    files
+}
+
+/// Reload the current component if it uses any of the images or fonts at `paths`
+pub fn resources_changed(paths: Vec<PathBuf>) {
+    let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let changed =
+        paths.into_iter().filter(|p| cache.resource_dependency.contains(p)).collect::<Vec<_>>();
+    if changed.is_empty() || !cache.ui_is_visible || cache.current.path.as_os_str().is_empty() {
+        return;
+    }
+    let current = cache.current.clone();
+    drop(cache);
+
+    run_in_ui_thread(move || async move {
+        // Otherwise the new instance shows the images as they were when loaded first
+        refresh_cached_images(&changed);
+        load_preview(current);
+    });
+}
+
+/// Reloads the current component unless another change arrived since `pending_reload` was scheduled
+fn reload_if_still_pending(pending_reload: u64) {
+    let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    if cache.pending_reload != pending_reload {
+        return;
+    }
+    let current = cache.current.clone();
+    let ui_is_visible = cache.ui_is_visible;
+    drop(cache);
+
+    if ui_is_visible && !current.path.as_os_str().is_empty() {
+        load_preview(current);
     }
 }
 
@@ -130,7 +314,164 @@ pub fn element_covers_point(
         return false;
     };
 
-    position.contains(click_position)
+    is_hit_testable(component_instance, selected_element, &position)
+        && position.contains(click_position)
+}
+
+/// Elements with less area than this can not be clicked on. A degenerate rectangle would
+/// still contain the points on its edges.
+const MIN_HIT_TEST_AREA: f32 = 0.01;
+
+// Can the element at `position` be clicked on at all?
+fn is_hit_testable(
+    component_instance: &ComponentInstance,
+    element: &ElementRc,
+    position: &LogicalRect,
+) -> bool {
+    position.size.area() >= MIN_HIT_TEST_AREA && component_instance.element_is_visible(element)
+}
+
+// Look through the element that the compiler adds to implement the `visible` property. It
+// has no size, the element it hides is its only child.
+fn self_or_visibility_wrapped_element(element: ElementRc) -> ElementRc {
+    let wrapped = {
+        let e = element.borrow();
+        let is_visibility_wrapper = e.node.is_none()
+            && e.id.ends_with("-visibility")
+            && e.native_class().map_or(false, |n| n.class_name == "Clip");
+        if is_visibility_wrapper && e.children.len() == 1 {
+            e.children.first().cloned()
+        } else {
+            None
+        }
+    };
+    wrapped.unwrap_or(element)
+}
+
+// The elements at x/y that clicking repeatedly cycles through, starting at `selected_element`:
+// it and its siblings, also when it got selected inside of a layout or of an open popup
+fn cycle_candidates(
+    x: f32,
+    y: f32,
+    component_instance: &ComponentInstance,
+    root_element: &ElementRc,
+    selected_element: &ElementRc,
+) -> Vec<(ElementRc, LogicalRect)> {
+    let roots =
+        std::iter::once(root_element.clone()).chain(component_instance.open_popup_root_element());
+    for root in roots {
+        let mut parent = find_parent_element(&root, selected_element);
+        // Look through the element the compiler adds to implement the `visible` property
+        while let Some(p) = parent.clone() {
+            if Rc::ptr_eq(&self_or_visibility_wrapped_element(p.clone()), &p) {
+                break;
+            }
+            parent = find_parent_element(&root, &p);
+        }
+        if let Some(parent) = parent {
+            return elements_at_point(x, y, component_instance, &parent);
+        }
+    }
+    Vec::new()
+}
+
+// Find the children of `root_element` that are at the position x/y
+//
+// Children are drawn in order, so the result is sorted from the child on top
+// to the one at the bottom.
+fn elements_at_point(
+    x: f32,
+    y: f32,
+    component_instance: &ComponentInstance,
+    root_element: &ElementRc,
+) -> Vec<(ElementRc, LogicalRect)> {
+    let click_position = LogicalPoint::from_lengths(LogicalLength::new(x), LogicalLength::new(y));
+
+    let mut result = root_element
+        .borrow()
+        .children
+        .iter()
+        .map(self_or_embedded_component_root)
+        .map(self_or_visibility_wrapped_element)
+        .filter_map(|c| {
+            let position = component_instance.element_position(&c)?;
+            (is_hit_testable(component_instance, &c, &position)
+                && position.contains(click_position))
+            .then_some((c, position))
+        })
+        .collect::<Vec<_>>();
+    result.reverse();
+    result
+}
+
+/// Find the child of `root_element` that is on top at the position x/y, with its geometry.
+/// Elements of the types configured in `select_through_types` are looked through. Inside of an
+/// open popup, this finds the elements of the popup instead.
+///
+/// This is the hit-test of selecting with the mouse, without selecting anything.
+pub fn element_at(
+    x: f32,
+    y: f32,
+    component_instance: &ComponentInstance,
+    root_element: &ElementRc,
+) -> Option<(ElementRc, LogicalRect)> {
+    let select_through = CONTENT_CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .config
+        .select_through_types
+        .clone()
+        .unwrap_or_else(|| {
+            crate::common::DEFAULT_SELECT_THROUGH_TYPES.iter().map(|t| t.to_string()).collect()
+        });
+
+    // An open popup covers everything below it
+    if let Some(popup) = component_instance.open_popup_root_element() {
+        let click_position =
+            LogicalPoint::from_lengths(LogicalLength::new(x), LogicalLength::new(y));
+        if let Some(position) = component_instance
+            .element_position(&popup)
+            .filter(|position| position.contains(click_position))
+        {
+            return element_at_selecting_through(x, y, component_instance, &popup, &select_through)
+                .or(Some((popup, position)));
+        }
+    }
+
+    element_at_selecting_through(x, y, component_instance, root_element, &select_through)
+}
+
+// Like `element_at`, going on to the element on top inside of elements of the `select_through`
+// types. Such an element is only the result if there is nothing in it at x/y.
+fn element_at_selecting_through(
+    x: f32,
+    y: f32,
+    component_instance: &ComponentInstance,
+    root_element: &ElementRc,
+    select_through: &[String],
+) -> Option<(ElementRc, LogicalRect)> {
+    let mut result =
+        elements_at_point(x, y, component_instance, root_element).into_iter().next()?;
+    while select_through.contains(&element_source_type_name(&result.0)) {
+        match elements_at_point(x, y, component_instance, &result.0).into_iter().next() {
+            Some(inner) => result = inner,
+            None => break,
+        }
+    }
+    Some(result)
+}
+
+// The type of the element as written in the source code, like `VerticalBox`. The compiler
+// replaces some types, like the layouts, so `base_type` does not always match it.
+fn element_source_type_name(element: &ElementRc) -> String {
+    let element = element.borrow();
+    element
+        .node
+        .as_ref()
+        .and_then(|n| n.QualifiedName())
+        .map(|q| q.text().to_string().trim().to_owned())
+        .unwrap_or_else(|| element.base_type.to_string())
 }
 
 // triggered from the UI, running in UI thread
@@ -140,30 +481,55 @@ pub fn select_element_at_impl(
     component_instance: &ComponentInstance,
     root_element: &ElementRc,
 ) -> Option<ElementRc> {
-    let click_position = LogicalPoint::from_lengths(LogicalLength::new(x), LogicalLength::new(y));
-
-    for c in &root_element.borrow().children {
-        let c = self_or_embedded_component_root(c);
+    let (c, position) = element_at(x, y, component_instance, root_element)?;
+    select_element(component_instance, &c, position);
+    Some(c)
+}
 
-        let Some(position) = component_instance.element_position(&c) else {
-            continue;
-        };
-        if position.contains(click_position) {
-            let secondary_positions = if let Some((path, offset)) = element_offset(&c) {
-                component_instance.component_positions(path, offset)
-            } else {
-                ComponentPositions::default()
-            };
+// Select the `element` and ask the editor to show its source code, unless configured otherwise
+fn select_element(
+    component_instance: &ComponentInstance,
+    element: &ElementRc,
+    position: LogicalRect,
+) {
+    let secondary_positions = if let Some((path, offset)) = element_offset(element) {
+        component_instance.component_positions(path, offset)
+    } else {
+        ComponentPositions::default()
+    };
 
-            set_selected_element(Some((&c, position)), secondary_positions);
-            let document_position = lsp_element_position(&c);
-            if !document_position.0.is_empty() {
-                ask_editor_to_show_document(document_position.0, document_position.1);
-            }
-            return Some(c.clone());
+    set_selected_element(Some((element, position)), secondary_positions);
+    let document_position = lsp_element_position(element);
+    if !document_position.0.is_empty() {
+        notify_element_selected(document_position.0.clone(), document_position.1);
+        if reveal_on_select() {
+            ask_editor_to_show_document(document_position.0, document_position.1);
         }
     }
+}
+
+fn reveal_on_select() -> bool {
+    let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    cache.config.reveal_on_select.unwrap_or(true)
+}
+
+fn confirm_edits() -> bool {
+    let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    cache.config.confirm_edits.unwrap_or(true)
+}
 
+// Find the element that has `element` as one of its children, looking into
+// the element tree below `root_element`.
+fn find_parent_element(root_element: &ElementRc, element: &ElementRc) -> Option<ElementRc> {
+    for c in &root_element.borrow().children {
+        let c = self_or_embedded_component_root(c);
+        if Rc::ptr_eq(&c, element) {
+            return Some(root_element.clone());
+        }
+        if let Some(parent) = find_parent_element(&c, element) {
+            return Some(parent);
+        }
+    }
     None
 }
 
@@ -187,371 +553,4129 @@ fn element_source_range(element: &ElementRc) -> Option<(SourceFile, TextRange)>
 
 // Return the real root element, skipping any WindowElement that got added
 fn root_element(component_instance: &ComponentInstance) -> ElementRc {
-    let root_element = component_instance.definition().root_component().root_element.clone();
+    definition_root_element(&component_instance.definition())
+}
 
-    if root_element.borrow().children.len() != 1 {
-        return root_element;
-    }
+// Return the real root element of a component definition, skipping any WindowElement that got added
+fn definition_root_element(definition: &ComponentDefinition) -> ElementRc {
+    let root_element = definition.root_component().root_element.clone();
 
-    let Some(child) = root_element.borrow().children.first().cloned() else {
+    if !is_injected_window(&root_element) {
         return root_element;
-    };
+    }
     let Some((rsf, rr)) = element_source_range(&root_element) else {
         return root_element;
     };
-    let Some((csf, cr)) = element_source_range(&child) else {
-        return root_element;
-    };
 
-    if Rc::ptr_eq(&rsf, &csf) && rr == cr {
-        child
-    } else {
-        root_element
-    }
+    // The window took over the source node of the real root, which became one of its children
+    let real_root = root_element.borrow().children.iter().find_map(|child| {
+        let (csf, cr) = element_source_range(child)?;
+        (Rc::ptr_eq(&rsf, &csf) && rr == cr).then(|| child.clone())
+    });
+    real_root.unwrap_or(root_element)
 }
 
-// triggered from the UI, running in UI thread
-pub fn select_element_at(x: f32, y: f32) {
-    let Some(component_instance) = component_instance() else {
-        return;
-    };
+// Is this the Window the compiler wraps around components that are no window themselves?
+fn is_injected_window(element: &ElementRc) -> bool {
+    let e = element.borrow();
+    // The id got made unique after the window was added
+    e.id.starts_with("root_window")
+        && e.builtin_type().map_or(false, |b| matches!(b.name.as_str(), "Window" | "WindowItem"))
+}
 
-    if let Some(selected_element) = selected_element() {
-        if element_covers_point(x, y, &component_instance, &selected_element) {
-            // We clicked on the already selected element: Do nothing!
-            return;
-        }
-    }
+/// A node in the element tree of the previewed component, as shown in the outline
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ElementTreeNode {
+    /// The element id (if any) and type name
+    pub label: String,
+    pub file: String,
+    pub range: lsp_types::Range,
+    pub children: Vec<ElementTreeNode>,
+    #[serde(skip)]
+    element: ElementWeak,
+}
 
-    let root_element = root_element(&component_instance);
+/// Build the tree of elements below `root_element`
+///
+/// Elements that do not appear in the source code are skipped, their children
+/// are attached to the closest parent that does.
+pub fn element_tree(root_element: &ElementRc) -> ElementTreeNode {
+    let (file, range) = lsp_element_position(root_element);
+    ElementTreeNode {
+        label: element_label(root_element),
+        file,
+        range,
+        children: element_tree_children(root_element),
+        element: Rc::downgrade(root_element),
+    }
+}
 
-    select_element_at_impl(x, y, &component_instance, &root_element);
+fn element_tree_children(element: &ElementRc) -> Vec<ElementTreeNode> {
+    let mut result = Vec::new();
+    for c in &element.borrow().children {
+        let c = self_or_embedded_component_root(c);
+        if c.borrow().node.is_some() {
+            result.push(element_tree(&c));
+        } else {
+            result.extend(element_tree_children(&c));
+        }
+    }
+    result
 }
 
-// triggered from the UI, running in UI thread
-pub fn select_element_into(x: f32, y: f32) {
-    let Some(component_instance) = component_instance() else {
-        return;
-    };
+fn element_label(element: &ElementRc) -> String {
+    let type_name = element.borrow().base_type.to_string();
+    match element_id(element) {
+        Some(id) => format!("{id} := {type_name}"),
+        None => type_name,
+    }
+}
 
-    // We have an actively selected element (via the earlier click-event :-):
-    let Some(selected_element) = selected_element() else {
-        return;
-    };
+// The id given to the element in the source code, if any
+fn element_id(element: &ElementRc) -> Option<String> {
+    element
+        .borrow()
+        .node
+        .as_ref()
+        .and_then(|n| n.parent())
+        .filter(|p| p.kind() == i_slint_compiler::parser::SyntaxKind::SubElement)
+        .and_then(|p| p.child_text(i_slint_compiler::parser::SyntaxKind::Identifier))
+}
 
-    let _ = select_element_at_impl(x, y, &component_instance, &selected_element);
+// Flatten the tree depth-first, in the order shown in the outline
+fn flatten_element_tree<'a>(
+    node: &'a ElementTreeNode,
+    depth: usize,
+    result: &mut Vec<(usize, &'a ElementTreeNode)>,
+) {
+    result.push((depth, node));
+    for c in &node.children {
+        flatten_element_tree(c, depth + 1, result);
+    }
 }
 
-fn change_style() {
-    let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
-    let ui_is_visible = cache.ui_is_visible;
-    let current = cache.current.clone();
-    drop(cache);
+/// The properties that end up in the render tree dump, if they are set in the source code
+const RENDER_TREE_PROPERTIES: &[&str] = &[
+    "background",
+    "border-color",
+    "border-radius",
+    "border-width",
+    "color",
+    "font-size",
+    "font-weight",
+    "image-fit",
+    "opacity",
+    "source",
+    "text",
+    "visible",
+];
 
-    if ui_is_visible && !current.path.as_os_str().is_empty() {
-        load_preview(current);
-    }
+/// One element in the dump of what the preview renders
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct RenderTreeNode {
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub id: Option<String>,
+    pub file: String,
+    pub range: lsp_types::Range,
+    /// The geometry relative to the previewed component, `None` if it is not rendered
+    pub geometry: Option<RenderTreeGeometry>,
+    /// The bindings of the `RENDER_TREE_PROPERTIES`, as written in the source code
+    pub properties: std::collections::BTreeMap<String, String>,
+    pub children: Vec<RenderTreeNode>,
 }
 
-pub fn start_parsing() {
-    set_status_text("Updating Preview...");
-    set_diagnostics(&[]);
-    send_status("Loading Preview…", Health::Ok);
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct RenderTreeGeometry {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
 }
 
-pub fn finish_parsing(ok: bool) {
-    set_status_text("");
-    if ok {
-        send_status("Preview Loaded", Health::Ok);
-    } else {
-        send_status("Preview not updated", Health::Error);
+/// Describe the elements below `root_element` as rendered by `component_instance`
+///
+/// The children are in the order of the element tree, so that the output is stable and can be
+/// diffed. Elements that do not appear in the source code are skipped like in the outline.
+pub fn render_tree(
+    component_instance: &ComponentInstance,
+    root_element: &ElementRc,
+) -> RenderTreeNode {
+    let (file, range) = element_source_range(root_element)
+        .map(|(sf, range)| {
+            (sf.path().to_string_lossy().to_string(), crate::util::map_range(&sf, range))
+        })
+        .unwrap_or_default();
+    let geometry = component_instance.element_position(root_element).map(|r| RenderTreeGeometry {
+        x: r.origin.x,
+        y: r.origin.y,
+        width: r.size.width,
+        height: r.size.height,
+    });
+
+    RenderTreeNode {
+        type_name: root_element.borrow().base_type.to_string(),
+        id: element_id(root_element),
+        file,
+        range,
+        geometry,
+        properties: render_tree_properties(root_element),
+        children: render_tree_children(component_instance, root_element),
     }
 }
 
-pub fn config_changed(config: PreviewConfig) {
-    if let Some(cache) = CONTENT_CACHE.get() {
-        let mut cache = cache.lock().unwrap();
-        if cache.config != config {
-            cache.config = config;
-            let current = cache.current.clone();
-            let ui_is_visible = cache.ui_is_visible;
-            let hide_ui = cache.config.hide_ui;
+fn render_tree_children(
+    component_instance: &ComponentInstance,
+    element: &ElementRc,
+) -> Vec<RenderTreeNode> {
+    let mut result = Vec::new();
+    for c in &element.borrow().children {
+        let c = self_or_embedded_component_root(c);
+        if c.borrow().node.is_some() {
+            result.push(render_tree(component_instance, &c));
+        } else {
+            result.extend(render_tree_children(component_instance, &c));
+        }
+    }
+    result
+}
 
-            drop(cache);
+fn render_tree_properties(element: &ElementRc) -> std::collections::BTreeMap<String, String> {
+    let Some(node) = element.borrow().node.clone() else {
+        return Default::default();
+    };
+    node.Binding()
+        .filter_map(|b| {
+            let name = b.child_text(i_slint_compiler::parser::SyntaxKind::Identifier)?;
+            RENDER_TREE_PROPERTIES.contains(&name.as_str()).then(|| {
+                let value = b.BindingExpression().text().to_string();
+                (name, value.trim().trim_end_matches(';').trim_end().to_string())
+            })
+        })
+        .collect()
+}
 
-            if ui_is_visible {
-                if let Some(hide_ui) = hide_ui {
-                    set_show_preview_ui(!hide_ui);
-                }
-                if !current.path.as_os_str().is_empty() {
-                    load_preview(current);
-                }
-            }
+/// Write the render tree of the current component to `path` as JSON
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_render_tree(path: PathBuf) {
+    run_in_ui_thread(move || async move {
+        let result = component_instance()
+            .ok_or_else(|| "No component is loaded in the preview".to_string())
+            .and_then(|component_instance| {
+                let tree = render_tree(&component_instance, &root_element(&component_instance));
+                serde_json::to_string_pretty(&tree).map_err(|e| e.to_string())
+            })
+            .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => send_status(&format!("Saved render tree to {}", path.display()), Health::Ok),
+            Err(e) => send_status(&format!("Failed to save render tree: {e}"), Health::Error),
         }
-    };
+    });
 }
 
-/// If the file is in the cache, returns it.
-/// In any way, register it as a dependency
-fn get_file_from_cache(path: PathBuf) -> Option<String> {
-    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
-    let r = cache.source_code.get(&path).cloned();
-    cache.dependency.insert(path);
-    r
+/// The accessible properties that end up in the accessibility tree dump, if they are not empty
+const ACCESSIBILITY_TREE_PROPERTIES: &[AccessibleStringProperty] = &[
+    AccessibleStringProperty::Label,
+    AccessibleStringProperty::Description,
+    AccessibleStringProperty::Value,
+    AccessibleStringProperty::ValueMinimum,
+    AccessibleStringProperty::ValueMaximum,
+    AccessibleStringProperty::ValueStep,
+    AccessibleStringProperty::Checkable,
+    AccessibleStringProperty::Checked,
+];
+
+/// One accessible element in the dump of what screen readers see
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct AccessibilityTreeNode {
+    pub role: String,
+    pub id: Option<String>,
+    pub file: String,
+    /// The offset of the element in `file`, as used to highlight it
+    pub offset: Option<u32>,
+    pub range: lsp_types::Range,
+    /// The `ACCESSIBILITY_TREE_PROPERTIES` as evaluated by the runtime
+    pub properties: std::collections::BTreeMap<String, String>,
+    pub children: Vec<AccessibilityTreeNode>,
 }
 
-pub fn load_preview(preview_component: PreviewComponent) {
-    {
-        let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
-        cache.current = preview_component.clone();
-        if !cache.ui_is_visible {
-            return;
-        }
-        match cache.loading_state {
-            PreviewFutureState::Pending => (),
-            PreviewFutureState::PreLoading => return,
-            PreviewFutureState::Loading => {
-                cache.loading_state = PreviewFutureState::NeedsReload;
-                return;
-            }
-            PreviewFutureState::NeedsReload => return,
-        }
-        cache.loading_state = PreviewFutureState::PreLoading;
+/// Describe the accessible elements below `root_element` as exposed by `component_instance`
+/// to assistive technology
+///
+/// Elements that are not accessible are skipped, their accessible children are attached
+/// to the closest accessible parent. Repeated elements are described by their first occurrence.
+pub fn accessibility_tree(
+    component_instance: &ComponentInstance,
+    root_element: &ElementRc,
+) -> Vec<AccessibilityTreeNode> {
+    let mut children = Vec::new();
+    for c in &root_element.borrow().children {
+        let c = self_or_embedded_component_root(c);
+        children.extend(accessibility_tree(component_instance, &c));
+    }
+
+    let Some(item) = component_instance.element_item(root_element) else {
+        return children;
     };
+    let role = item.accessible_role();
+    if !item.is_accessible() || role == i_slint_core::items::AccessibleRole::None {
+        return children;
+    }
 
-    run_in_ui_thread(move || async move {
-        loop {
-            let (preview_component, config) = {
-                let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
-                assert_eq!(cache.loading_state, PreviewFutureState::PreLoading);
-                if !cache.ui_is_visible {
-                    cache.loading_state = PreviewFutureState::Pending;
-                    return;
-                }
-                cache.loading_state = PreviewFutureState::Loading;
-                cache.dependency.clear();
-                let preview_component = cache.current.clone();
-                cache.current.style.clear();
-                (preview_component, cache.config.clone())
-            };
-            let style = if preview_component.style.is_empty() {
-                get_current_style()
-            } else {
-                set_current_style(preview_component.style.clone());
-                preview_component.style.clone()
-            };
+    let (file, range) = element_source_range(root_element)
+        .map(|(sf, range)| {
+            (sf.path().to_string_lossy().to_string(), crate::util::map_range(&sf, range))
+        })
+        .unwrap_or_default();
+    let properties = ACCESSIBILITY_TREE_PROPERTIES
+        .iter()
+        .filter_map(|p| {
+            let value = item.accessible_string_property(*p);
+            (!value.is_empty()).then(|| (p.to_string(), value.to_string()))
+        })
+        .collect();
 
-            reload_preview_impl(preview_component, style, config).await;
+    vec![AccessibilityTreeNode {
+        role: role.to_string(),
+        id: element_id(root_element),
+        file,
+        offset: element_offset(root_element).map(|(_, offset)| offset),
+        range,
+        properties,
+        children,
+    }]
+}
 
-            let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
-            match cache.loading_state {
-                PreviewFutureState::Loading => {
-                    cache.loading_state = PreviewFutureState::Pending;
-                    return;
-                }
-                PreviewFutureState::Pending => unreachable!(),
-                PreviewFutureState::PreLoading => unreachable!(),
-                PreviewFutureState::NeedsReload => {
-                    cache.loading_state = PreviewFutureState::PreLoading;
-                    continue;
-                }
-            };
+/// The compiler configuration of the last build of the preview
+pub fn compiler_config() -> Option<PreviewCompilerConfig> {
+    CONTENT_CACHE.get_or_init(Default::default).lock().unwrap().compiler_config.clone()
+}
+
+/// The configuration `compile_preview` passes to the compiler
+fn effective_compiler_config(
+    component: &PreviewComponent,
+    style: &str,
+    config: &PreviewConfig,
+) -> PreviewCompilerConfig {
+    // Mirror the fallback of the compiler for an unset style
+    let style = if style.is_empty() {
+        std::env::var("SLINT_STYLE").unwrap_or_else(|_| "native".into())
+    } else {
+        style.to_string()
+    };
+    PreviewCompilerConfig {
+        path: component.path.clone(),
+        component: component.component.clone(),
+        style,
+        include_paths: config.include_paths.clone(),
+        library_paths: config.library_paths.clone(),
+        deny_warnings: config.deny_warnings,
+    }
+}
+
+/// Write the accessibility tree of the current component to `path` as JSON
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_accessibility_tree(path: PathBuf) {
+    run_in_ui_thread(move || async move {
+        let result = component_instance()
+            .ok_or_else(|| "No component is loaded in the preview".to_string())
+            .and_then(|component_instance| {
+                let tree =
+                    accessibility_tree(&component_instance, &root_element(&component_instance));
+                serde_json::to_string_pretty(&tree).map_err(|e| e.to_string())
+            })
+            .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => {
+                send_status(&format!("Saved accessibility tree to {}", path.display()), Health::Ok)
+            }
+            Err(e) => {
+                send_status(&format!("Failed to save accessibility tree: {e}"), Health::Error)
+            }
         }
     });
 }
 
-// Most be inside the thread running the slint event loop
-async fn reload_preview_impl(
-    preview_component: PreviewComponent,
-    style: String,
-    config: PreviewConfig,
-) {
-    let component = PreviewComponent { style: String::new(), ..preview_component };
-
-    start_parsing();
+fn set_outline(ui: &ui::PreviewUi, definition: &ComponentDefinition) {
+    let tree = element_tree(&definition_root_element(definition));
+    let mut flat = Vec::new();
+    flatten_element_tree(&tree, 0, &mut flat);
 
-    let mut builder = slint_interpreter::ComponentCompiler::default();
+    let items = flat
+        .into_iter()
+        .map(|(depth, node)| ui::OutlineItem {
+            label: node.label.clone().into(),
+            indent: depth as i32,
+        })
+        .collect::<Vec<_>>();
+    ui.set_outline(Rc::new(slint::VecModel::from(items)).into());
+}
 
-    #[cfg(target_arch = "wasm32")]
-    {
-        let cc = builder.compiler_configuration(i_slint_core::InternalToken);
-        cc.resource_url_mapper = resource_url_mapper();
-    }
+// triggered from the UI, running in UI thread
+pub fn select_outline_item(index: i32) {
+    let Some(component_instance) = component_instance() else {
+        return;
+    };
+    let tree = element_tree(&root_element(&component_instance));
+    let mut flat = Vec::new();
+    flatten_element_tree(&tree, 0, &mut flat);
 
-    if !style.is_empty() {
-        builder.set_style(style.clone());
-    }
-    builder.set_include_paths(config.include_paths);
-    builder.set_library_paths(config.library_paths);
+    let Some(element) = flat.get(index as usize).and_then(|(_, node)| node.element.upgrade())
+    else {
+        return;
+    };
+    // Elements that are not currently shown (e.g. in a false `if`) have no position
+    let Some(position) = component_instance.element_position(&element) else {
+        return;
+    };
+    select_element(&component_instance, &element, position);
+}
 
-    builder.set_file_loader(|path| {
-        let path = path.to_owned();
-        Box::pin(async move { get_file_from_cache(path).map(Result::Ok) })
-    });
+thread_local! {static HOVERED_ELEMENT: RefCell<Option<ElementWeak>> = Default::default();}
 
-    let compiled = if let Some(mut from_cache) = get_file_from_cache(component.path.clone()) {
-        if let Some(component_name) = &component.component {
-            from_cache = format!(
-                "{from_cache}\nexport component _Preview inherits {component_name} {{ }}\n"
-            );
-        }
-        builder.build_from_source(from_cache, component.path).await
-    } else {
-        builder.build_from_path(component.path).await
+// triggered from the UI, running in UI thread
+pub fn hover_element_at(x: f32, y: f32) {
+    let Some(component_instance) = component_instance() else {
+        return;
     };
+    let root_element = root_element(&component_instance);
+    let hovered = element_at(x, y, &component_instance, &root_element);
 
-    notify_diagnostics(builder.diagnostics());
+    let changed = HOVERED_ELEMENT.with(|h| {
+        let mut h = h.borrow_mut();
+        let previous = h.as_ref().and_then(|e| e.upgrade());
+        let changed = match (&previous, &hovered) {
+            (Some(previous), Some((e, _))) => !Rc::ptr_eq(previous, e),
+            (None, None) => false,
+            _ => true,
+        };
+        if changed {
+            *h = hovered.as_ref().map(|(e, _)| Rc::downgrade(e));
+        }
+        changed
+    });
 
-    if let Some(compiled) = compiled {
-        update_preview_area(compiled);
-        finish_parsing(true);
-    } else {
-        finish_parsing(false);
-    };
+    if changed {
+        set_hovered_element(hovered);
+    }
 }
 
-/// This sets up the preview area to show the ComponentInstance
-///
-/// This must be run in the UI thread.
-pub fn set_preview_factory(
-    ui: &ui::PreviewUi,
-    compiled: ComponentDefinition,
-    callback: Box<dyn Fn(ComponentInstance)>,
-) {
-    // Ensure that the popup is closed as it is related to the old factory
-    i_slint_core::window::WindowInner::from_pub(ui.window()).close_popup();
+/// How far (in logical pixels) clicks may be apart to still cycle through the
+/// elements stacked at one position
+const SELECTION_CYCLE_TOLERANCE: f32 = 4.0;
 
-    let factory = slint::ComponentFactory::new(move |ctx: FactoryContext| {
-        let instance = compiled.create_embedded(ctx).unwrap();
+// Remembers the last click of `select_element_at`, so that repeated clicks at the
+// same position can cycle through all elements stacked there
+struct SelectionCycle {
+    position: LogicalPoint,
+    /// The element that was selected before the last click moved on to the next element
+    cycled_from: Option<ElementWeak>,
+}
 
-        if let Some((path, offset)) =
-            CONTENT_CACHE.get().and_then(|c| c.lock().unwrap().highlight.clone())
-        {
-            highlight(&Some(path), offset);
-        }
+thread_local! {static SELECTION_CYCLE: RefCell<Option<SelectionCycle>> = Default::default();}
 
-        callback(instance.clone_strong());
+// triggered from the UI, running in UI thread
+pub fn select_element_at(x: f32, y: f32) {
+    if in_presentation_mode() {
+        return;
+    }
+    let Some(component_instance) = component_instance() else {
+        return;
+    };
 
-        Some(instance)
+    let click_position = LogicalPoint::from_lengths(LogicalLength::new(x), LogicalLength::new(y));
+    let is_repeated_click = SELECTION_CYCLE.with(|cycle| {
+        cycle
+            .borrow_mut()
+            .replace(SelectionCycle { position: click_position, cycled_from: None })
+            .map_or(false, |c| {
+                (c.position.x - x).abs() <= SELECTION_CYCLE_TOLERANCE
+                    && (c.position.y - y).abs() <= SELECTION_CYCLE_TOLERANCE
+            })
     });
-    ui.set_preview_area(factory);
-}
 
-/// Highlight the element pointed at the offset in the path.
-/// When path is None, remove the highlight.
-pub fn highlight(path: &Option<PathBuf>, offset: u32) {
-    let highlight = path.clone().map(|x| (x, offset));
-    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let root_element = root_element(&component_instance);
 
-    if cache.highlight == highlight {
-        return;
+    if let Some(selected_element) = selected_element() {
+        if element_covers_point(x, y, &component_instance, &selected_element) {
+            // We clicked on the already selected element: Move on to the next element
+            // below it when clicking on the same spot again, do nothing otherwise!
+            if !is_repeated_click {
+                return;
+            }
+            let candidates =
+                cycle_candidates(x, y, &component_instance, &root_element, &selected_element);
+            let Some(index) = candidates.iter().position(|(e, _)| Rc::ptr_eq(e, &selected_element))
+            else {
+                return;
+            };
+            if candidates.len() < 2 {
+                return;
+            }
+
+            let (element, position) = &candidates[(index + 1) % candidates.len()];
+            select_element(&component_instance, element, *position);
+            SELECTION_CYCLE.with(|cycle| {
+                if let Some(cycle) = cycle.borrow_mut().as_mut() {
+                    cycle.cycled_from = Some(Rc::downgrade(&selected_element));
+                }
+            });
+            return;
+        }
     }
-    cache.highlight = highlight;
 
-    if cache.highlight.as_ref().map_or(true, |(path, _)| cache.dependency.contains(path)) {
-        let path = path.clone().unwrap_or_default();
-        update_highlight(path, offset);
+    if select_element_at_impl(x, y, &component_instance, &root_element).is_none() {
+        // We clicked on an empty area: Start over
+        clear_selection();
     }
 }
 
-pub fn show_document_request_from_element_callback(
-    file: &str,
-    range: lsp_types::Range,
-) -> Option<lsp_types::ShowDocumentParams> {
-    use lsp_types::{ShowDocumentParams, Url};
+// triggered from the UI, running in UI thread
+pub fn select_element_into(x: f32, y: f32) {
+    let Some(component_instance) = component_instance() else {
+        return;
+    };
 
-    if file.is_empty() || range.start.character == 0 || range.end.character == 0 {
-        return None;
-    }
+    // The second click of a double click might have cycled the selection away from
+    // the element we want to go into:
+    let cycled_from = SELECTION_CYCLE.with(|cycle| {
+        cycle.borrow_mut().take().and_then(|c| c.cycled_from).and_then(|e| e.upgrade())
+    });
 
-    Url::from_file_path(file).ok().map(|uri| ShowDocumentParams {
-        uri,
-        external: Some(false),
-        take_focus: Some(true),
-        selection: Some(range),
-    })
+    // We have an actively selected element (via the earlier click-event :-):
+    let Some(selected_element) = cycled_from.or_else(selected_element) else {
+        return;
+    };
+
+    let _ = select_element_at_impl(x, y, &component_instance, &selected_element);
 }
 
-pub fn convert_diagnostics(
-    diagnostics: &[slint_interpreter::Diagnostic],
-) -> HashMap<lsp_types::Url, Vec<lsp_types::Diagnostic>> {
-    let mut result: HashMap<lsp_types::Url, Vec<lsp_types::Diagnostic>> = Default::default();
-    for d in diagnostics {
-        if d.source_file().map_or(true, |f| !i_slint_compiler::pathutils::is_absolute(f)) {
-            continue;
-        }
-        let uri = lsp_types::Url::from_file_path(d.source_file().unwrap())
-            .ok()
-            .unwrap_or_else(|| lsp_types::Url::parse("file:/unknown").unwrap());
-        result.entry(uri).or_default().push(crate::util::to_lsp_diag(d));
+// triggered from the UI, running in UI thread
+pub fn add_element_to_selection_at(x: f32, y: f32) {
+    let Some(component_instance) = component_instance() else {
+        return;
+    };
+
+    let root_element = root_element(&component_instance);
+    let Some((element, position)) = element_at(x, y, &component_instance, &root_element) else {
+        return;
+    };
+
+    let mut elements = selected_elements();
+    if let Some(index) = elements.iter().position(|(e, _)| Rc::ptr_eq(e, &element)) {
+        // Clicking on an element that is already part of the selection removes it again
+        elements.remove(index);
+    } else {
+        elements.push((element, position));
     }
-    result
+
+    // The secondary positions belong to the element that is selected last
+    let secondary_positions = elements
+        .last()
+        .and_then(|(e, _)| element_offset(e))
+        .map(|(path, offset)| component_instance.component_positions(path, offset))
+        .unwrap_or_default();
+    show_selected_elements(&elements, secondary_positions);
 }
 
-pub fn notify_lsp_diagnostics(
-    sender: &crate::ServerNotifier,
-    uri: lsp_types::Url,
-    diagnostics: Vec<lsp_types::Diagnostic>,
-) -> Option<()> {
-    sender
-        .send_notification(
-            "textDocument/publishDiagnostics".into(),
-            lsp_types::PublishDiagnosticsParams { uri, diagnostics, version: None },
-        )
-        .ok()
+/// The file and offset of the source code of the element that was selected last, if any
+pub fn selected_element_source_location() -> Option<(PathBuf, u32)> {
+    element_offset(&selected_element()?)
 }
 
-pub fn send_status_notification(sender: &crate::ServerNotifier, message: &str, health: Health) {
-    sender
-        .send_notification(
-            crate::lsp_ext::ServerStatusNotification::METHOD.into(),
-            crate::lsp_ext::ServerStatusParams {
-                health,
-                quiescent: false,
-                message: Some(message.into()),
-            },
-        )
-        .unwrap_or_else(|e| eprintln!("Error sending notification: {:?}", e));
+// triggered from the UI, running in UI thread
+pub fn copy_selected_element_location() {
+    let Some((path, offset)) = selected_element_source_location() else {
+        return;
+    };
+    let line_column = selected_element()
+        .and_then(|e| Some(e.borrow().node.as_ref()?.source_file.line_column(offset as usize)));
+    let location = match line_column {
+        Some((line, column)) => format!("{}:{line}:{column}", path.to_string_lossy()),
+        None => format!("{}:{offset}", path.to_string_lossy()),
+    };
+
+    let _ = i_slint_backend_selector::with_platform(|platform| {
+        platform.set_clipboard_text(&location, i_slint_core::platform::Clipboard::DefaultClipboard);
+        Ok(())
+    });
 }
 
-pub fn reset_selections(ui: &ui::PreviewUi) {
-    let model = Rc::new(slint::VecModel::from(Vec::new()));
-    ui.set_selections(slint::ModelRc::from(model));
+/// The element that was selected last, if any
+pub fn selected_element() -> Option<ElementRc> {
+    selected_elements().pop().map(|(e, _)| e)
 }
 
-pub fn set_selections(
-    ui: Option<&ui::PreviewUi>,
+/// Replace the current selection with `element_position`
+pub fn set_selected_element(
     element_position: Option<(&ElementRc, LogicalRect)>,
     positions: ComponentPositions,
 ) {
-    let Some(ui) = ui else {
-        return;
-    };
+    let elements = element_position.map(|(e, r)| (e.clone(), r)).into_iter().collect::<Vec<_>>();
+    show_selected_elements(&elements, positions);
+}
 
-    let values = {
-        let mut tmp = Vec::with_capacity(
-            positions.geometries.len() + if element_position.is_some() { 1 } else { 0 },
-        );
+thread_local! {
+    /// Where the selected elements start in the source code, to select them again in the
+    /// instance built after the next change
+    static SELECTED_LOCATIONS: RefCell<Vec<(PathBuf, u32)>> = Default::default();
+}
+
+// Set the selection, adding the elements matching the highlight query to the secondary positions
+fn show_selected_elements(
+    elements: &[(ElementRc, LogicalRect)],
+    mut positions: ComponentPositions,
+) {
+    SELECTED_LOCATIONS.with(|locations| {
+        *locations.borrow_mut() = elements.iter().filter_map(|(e, _)| element_offset(e)).collect()
+    });
+    if let Some(component_instance) = component_instance() {
+        positions.geometries.extend(matching_element_geometries(&component_instance));
+    }
+    set_selected_elements(elements, positions);
+}
 
-        if let Some((e, primary_position)) = element_position.as_ref() {
-            let border_color = if e.borrow().layout.is_some() {
-                i_slint_core::Color::from_argb_encoded(0xffff0000)
+// Draw the current selection again, e.g. after the selection colors changed
+fn refresh_selections() {
+    let elements = selected_elements();
+    let positions = match (component_instance(), elements.last()) {
+        (Some(component_instance), Some((element, _))) => {
+            if let Some((path, offset)) = element_offset(element) {
+                component_instance.component_positions(path, offset)
             } else {
-                i_slint_core::Color::from_argb_encoded(0xff0000ff)
-            };
+                ComponentPositions::default()
+            }
+        }
+        _ => ComponentPositions::default(),
+    };
+    show_selected_elements(&elements, positions);
+}
 
-            tmp.push(ui::Selection {
-                width: primary_position.size.width,
-                height: primary_position.size.height,
+pub fn clear_selection() {
+    show_selected_elements(&[], ComponentPositions::default());
+}
+
+thread_local! {static HIGHLIGHT_QUERY: RefCell<String> = Default::default();}
+
+/// Highlight all elements that have `query` as id or type name, in addition to the selection.
+/// An empty query removes these highlights again.
+pub fn highlight_matching(query: String) {
+    if in_presentation_mode() {
+        return;
+    }
+    run_in_ui_thread(move || async move {
+        HIGHLIGHT_QUERY.with(|q| *q.borrow_mut() = query);
+        refresh_selections();
+    });
+}
+
+/// A warning of the last build, to mark the element it is about in the preview
+struct WarningLocation {
+    path: PathBuf,
+    offset: u32,
+    line: usize,
+    column: usize,
+}
+
+thread_local! {static WARNING_LOCATIONS: RefCell<Vec<WarningLocation>> = Default::default();}
+
+// Remember the warnings of the last successful build, for `warning_markers`
+fn set_warning_locations(diagnostics: &[slint_interpreter::Diagnostic]) {
+    let locations = diagnostics
+        .iter()
+        .filter(|d| d.level() == DiagnosticLevel::Warning)
+        .filter_map(|d| {
+            let path = d.source_file().filter(|f| i_slint_compiler::pathutils::is_absolute(f))?;
+            let (line, column) = d.line_column();
+            let offset = offset_at_line_column(current_source(path)?, line as u32, column as u32);
+            Some(WarningLocation { path: path.to_path_buf(), offset, line, column })
+        })
+        .collect();
+    WARNING_LOCATIONS.with(|w| *w.borrow_mut() = locations);
+}
+
+// The geometries of the elements the warnings of the last build are about. Clicking a marker
+// shows its warning in the editor.
+fn warning_markers(component_instance: &ComponentInstance) -> Vec<ui::WarningMarker> {
+    let tree = element_tree(&root_element(component_instance));
+    WARNING_LOCATIONS.with(|locations| {
+        let mut markers = Vec::new();
+        for location in locations.borrow().iter() {
+            let mut elements = Vec::new();
+            let range = TextRange::empty(location.offset.into());
+            elements_in_range(&tree, &location.path, range, &mut elements);
+            let geometries =
+                elements.iter().filter_map(element_offset).flat_map(|(path, offset)| {
+                    component_instance.component_positions(path, offset).geometries
+                });
+            markers.extend(geometries.map(|geometry| ui::WarningMarker {
+                x: geometry.origin.x,
+                y: geometry.origin.y,
+                width: geometry.size.width,
+                height: geometry.size.height,
+                url: location.path.to_string_lossy().to_string().into(),
+                line: location.line as i32,
+                column: location.column as i32,
+            }));
+        }
+        markers
+    })
+}
+
+/// Highlight all elements whose source code in `path` overlaps the range between the two
+/// offsets, replacing the selection. The elements that just contain the whole range are
+/// left out when one of their children overlaps it, so that not all parents light up.
+pub fn highlight_range(path: PathBuf, start_offset: u32, end_offset: u32) {
+    if in_presentation_mode() {
+        return;
+    }
+    run_in_ui_thread(move || async move {
+        let Some(component_instance) = component_instance() else {
+            return;
+        };
+        let range = TextRange::new(
+            start_offset.min(end_offset).into(),
+            start_offset.max(end_offset).into(),
+        );
+        let tree = element_tree(&root_element(&component_instance));
+        let mut elements = Vec::new();
+        elements_in_range(&tree, &path, range, &mut elements);
+
+        let mut positions = ComponentPositions::default();
+        for (path, offset) in elements.iter().filter_map(element_offset) {
+            positions
+                .geometries
+                .extend(component_instance.component_positions(path, offset).geometries);
+        }
+        set_selected_element(None, positions);
+    });
+}
+
+// Collect the elements below `node` overlapping `range`, returns whether any did
+fn elements_in_range(
+    node: &ElementTreeNode,
+    path: &Path,
+    range: TextRange,
+    result: &mut Vec<ElementRc>,
+) -> bool {
+    let mut child_matched = false;
+    for c in &node.children {
+        child_matched |= elements_in_range(c, path, range, result);
+    }
+    let Some(element) = node.element.upgrade() else {
+        return child_matched;
+    };
+    let Some((source_file, element_range)) = element_source_range(&element) else {
+        return child_matched;
+    };
+    if source_file.path() != path {
+        return child_matched;
+    }
+    let overlaps = if range.is_empty() {
+        element_range.contains_inclusive(range.start())
+    } else {
+        element_range.start() < range.end() && range.start() < element_range.end()
+    };
+    if overlaps && !(child_matched && element_range.contains_range(range)) {
+        result.push(element);
+    }
+    overlaps || child_matched
+}
+
+fn has_highlight_query() -> bool {
+    HIGHLIGHT_QUERY.with(|q| !q.borrow().is_empty())
+}
+
+fn matching_elements(root_element: &ElementRc, query: &str) -> Vec<ElementRc> {
+    let tree = element_tree(root_element);
+    let mut flat = Vec::new();
+    flatten_element_tree(&tree, 0, &mut flat);
+    flat.into_iter()
+        .filter_map(|(_, node)| node.element.upgrade())
+        .filter(|e| {
+            element_id(e).as_deref() == Some(query) || e.borrow().base_type.to_string() == query
+        })
+        .collect()
+}
+
+// All occurrences of the elements matching the highlight query, including repeated ones
+fn matching_element_geometries(component_instance: &ComponentInstance) -> Vec<LogicalRect> {
+    let query = HIGHLIGHT_QUERY.with(|q| q.borrow().clone());
+    if query.is_empty() {
+        return Vec::new();
+    }
+    matching_elements(&root_element(component_instance), &query)
+        .iter()
+        .filter_map(element_offset)
+        .flat_map(|(path, offset)| component_instance.component_positions(path, offset).geometries)
+        .collect()
+}
+
+// triggered from the UI, running in UI thread
+pub fn select_parent_element() {
+    let Some(component_instance) = component_instance() else {
+        return;
+    };
+    let Some(selected_element) = selected_element() else {
+        return;
+    };
+
+    let root_element = root_element(&component_instance);
+    if Rc::ptr_eq(&root_element, &selected_element) {
+        // Already at the top: Nothing to do!
+        return;
+    }
+
+    let Some(parent) = selectable_parent(&root_element, &selected_element) else {
+        return;
+    };
+
+    let Some(position) = component_instance.element_position(&parent) else {
+        return;
+    };
+    select_element(&component_instance, &parent, position);
+}
+
+// The compiled element tree has no back-references, so search for the parent
+// from the top. Skip over elements that were generated by the compiler and
+// have no source code attached.
+fn selectable_parent(root_element: &ElementRc, element: &ElementRc) -> Option<ElementRc> {
+    let mut parent = find_parent_element(root_element, element);
+    while let Some(p) = &parent {
+        if Rc::ptr_eq(p, root_element) || p.borrow().node.is_some() {
+            break;
+        }
+        parent = find_parent_element(root_element, p);
+    }
+    parent
+}
+
+// The children of `element` that can get selected: Those that are shown and have
+// source code. The children of elements generated by the compiler take their place.
+fn selectable_children(
+    component_instance: &ComponentInstance,
+    element: &ElementRc,
+) -> Vec<(ElementRc, LogicalRect)> {
+    let mut result = Vec::new();
+    for c in &element.borrow().children {
+        let c = self_or_embedded_component_root(c);
+        if c.borrow().node.is_none() {
+            result.extend(selectable_children(component_instance, &c));
+        } else if let Some(position) = component_instance.element_position(&c) {
+            result.push((c, position));
+        }
+    }
+    result
+}
+
+// triggered from the UI, running in UI thread
+pub fn select_first_child_element() {
+    let (Some(component_instance), Some(selected_element)) =
+        (component_instance(), selected_element())
+    else {
+        return;
+    };
+
+    let Some((child, position)) =
+        selectable_children(&component_instance, &selected_element).into_iter().next()
+    else {
+        return;
+    };
+    select_element(&component_instance, &child, position);
+}
+
+// triggered from the UI, running in UI thread
+/// Select the next (or previous) sibling of the selected element, wrapping around
+pub fn select_sibling_element(forward: bool) {
+    let (Some(component_instance), Some(selected_element)) =
+        (component_instance(), selected_element())
+    else {
+        return;
+    };
+
+    let root_element = root_element(&component_instance);
+    let Some(parent) = selectable_parent(&root_element, &selected_element) else {
+        return;
+    };
+    let siblings = selectable_children(&component_instance, &parent);
+    let Some(index) = siblings.iter().position(|(e, _)| Rc::ptr_eq(e, &selected_element)) else {
+        return;
+    };
+    let index = if forward {
+        (index + 1) % siblings.len()
+    } else {
+        (index + siblings.len() - 1) % siblings.len()
+    };
+    let (sibling, position) = &siblings[index];
+    select_element(&component_instance, sibling, *position);
+}
+
+// triggered from the UI, running in UI thread
+pub fn move_selected_element(dx: f32, dy: f32) {
+    let (Some(component_instance), Some(element)) = (component_instance(), selected_element())
+    else {
+        return;
+    };
+    if let Err(e) = geometry_edit(&component_instance, &element, dx, dy, None)
+        .and_then(|(node, edits)| apply_source_edits("Move element", &node, edits))
+    {
+        send_status(&format!("Cannot move the element: {e}"), Health::Warning);
+    }
+}
+
+/// How long the keyboard has to rest before the nudges of the selected element get applied
+/// to the source code, as one edit
+const NUDGE_IDLE_TIME: std::time::Duration = std::time::Duration::from_millis(400);
+
+thread_local! {
+    static NUDGE_TIMER: slint::Timer = Default::default();
+    /// The element being nudged, and how far it got nudged so far
+    static PENDING_NUDGE: RefCell<Option<(ElementWeak, f32, f32)>> = Default::default();
+}
+
+// triggered from the UI, running in UI thread
+pub fn nudge_selected_element(dx: f32, dy: f32) {
+    let (Some(component_instance), Some(element)) = (component_instance(), selected_element())
+    else {
+        return;
+    };
+    let (dx, dy) = PENDING_NUDGE.with(|pending| match &*pending.borrow() {
+        Some((e, x, y)) if e.upgrade().map_or(false, |e| Rc::ptr_eq(&e, &element)) => {
+            (x + dx, y + dy)
+        }
+        _ => (dx, dy),
+    });
+    // Tell right away when the element can not be moved, not only once the keyboard rests
+    if let Err(e) = geometry_edit(&component_instance, &element, dx, dy, None) {
+        PENDING_NUDGE.with(|pending| pending.take());
+        send_status(&format!("Cannot move the element: {e}"), Health::Warning);
+        return;
+    }
+    PENDING_NUDGE.with(|pending| *pending.borrow_mut() = Some((Rc::downgrade(&element), dx, dy)));
+
+    // Show where the element goes, it only moves once the preview got the changed source
+    if let Some(position) = component_instance.element_position(&element) {
+        let moved = position.translate(LogicalVector::new(dx, dy));
+        show_selected_elements(&[(element, moved)], ComponentPositions::default());
+    }
+    NUDGE_TIMER.with(|timer| {
+        timer.start(slint::TimerMode::SingleShot, NUDGE_IDLE_TIME, apply_pending_nudge)
+    });
+}
+
+fn apply_pending_nudge() {
+    let Some((element, dx, dy)) = PENDING_NUDGE.with(|pending| pending.take()) else {
+        return;
+    };
+    // The selection changed in the meantime
+    let still_selected = element
+        .upgrade()
+        .zip(selected_element())
+        .map_or(false, |(element, selected)| Rc::ptr_eq(&element, &selected));
+    if still_selected {
+        move_selected_element(dx, dy);
+    }
+}
+
+// triggered from the UI, running in UI thread. Moves the top left corner by dx/dy
+pub fn resize_selected_element(dx: f32, dy: f32, width: f32, height: f32) {
+    let (Some(component_instance), Some(element)) = (component_instance(), selected_element())
+    else {
+        return;
+    };
+    if let Err(e) = geometry_edit(&component_instance, &element, dx, dy, Some((width, height)))
+        .and_then(|(node, edits)| apply_source_edits("Resize element", &node, edits))
+    {
+        send_status(&format!("Cannot resize the element: {e}"), Health::Warning);
+    }
+}
+
+// The edits moving `element` by dx/dy, changing its x and y properties, and setting its
+// width and height to `size` if given. All values are snapped to whole logical pixels.
+fn geometry_edit(
+    component_instance: &ComponentInstance,
+    element: &ElementRc,
+    dx: f32,
+    dy: f32,
+    size: Option<(f32, f32)>,
+) -> Result<(syntax_nodes::Element, Vec<lsp_types::TextEdit>), String> {
+    use element_edit::LengthBinding;
+
+    let root_element = root_element(component_instance);
+    let parent = find_parent_element(&root_element, element);
+    if parent.as_ref().map_or(false, |p| p.borrow().layout.is_some()) {
+        return Err("it is positioned by a layout".into());
+    }
+    let Some(node) = element.borrow().node.clone() else {
+        return Err("it has no source code".into());
+    };
+    let position = component_instance.element_position(element).ok_or("it is not shown")?;
+    // Elements without x or y are centered in their parent, so start from where they are shown
+    let parent_origin = parent
+        .and_then(|p| component_instance.element_position(&p))
+        .map_or(LogicalPoint::default(), |p| p.origin);
+
+    let mut values = Vec::new();
+    for (property, shown, delta) in [
+        ("x", position.origin.x - parent_origin.x, dx),
+        ("y", position.origin.y - parent_origin.y, dy),
+    ] {
+        if delta == 0. && size.is_some() {
+            // Resizing from the bottom or right side keeps the position untouched
+            continue;
+        }
+        let current = match element_edit::length_binding(&node, property) {
+            LengthBinding::NotSet => shown,
+            LengthBinding::Literal(value) => value,
+            LengthBinding::Other => {
+                return Err(format!("its {property} is not set to a literal length"))
+            }
+        };
+        values.push((property, (current + delta).round()));
+    }
+    if let Some((width, height)) = size {
+        for (property, value) in [("width", width), ("height", height)] {
+            if element_edit::length_binding(&node, property) == LengthBinding::Other {
+                return Err(format!("its {property} is not set to a literal length"));
+            }
+            values.push((property, value.round().max(0.)));
+        }
+    }
+
+    let edits = element_edit::set_length_bindings(&node, &values)
+        .ok_or("its source code could not be changed")?;
+    Ok((node, edits))
+}
+
+// triggered from the UI, running in UI thread
+pub fn insert_component(component: slint::SharedString) {
+    let Some(container) = selected_element() else {
+        return;
+    };
+    let component = component.trim();
+    if let Err(e) = insert_component_edit(&container, component)
+        .and_then(|(node, edit)| apply_source_edits("Insert component", &node, vec![edit]))
+    {
+        send_status(&format!("Cannot insert {component}: {e}"), Health::Warning);
+    }
+}
+
+// triggered from the UI, running in UI thread
+pub fn delete_selected_element() {
+    let (Some(component_instance), Some(element)) = (component_instance(), selected_element())
+    else {
+        return;
+    };
+    if Rc::ptr_eq(&element, &root_element(&component_instance)) {
+        send_status("Cannot delete the root element", Health::Warning);
+        return;
+    }
+    let Some(node) = element.borrow().node.clone() else {
+        send_status("Cannot delete the element: it has no source code", Health::Warning);
+        return;
+    };
+    let Some(edit) = element_edit::remove_element(&node) else {
+        send_status("Cannot delete the root element of a component", Health::Warning);
+        return;
+    };
+    match apply_source_edits("Delete element", &node, vec![edit]) {
+        Ok(_) => set_selected_element(None, ComponentPositions::default()),
+        Err(e) => send_status(&format!("Cannot delete the element: {e}"), Health::Warning),
+    }
+}
+
+thread_local! {
+    /// The element to select once the preview shows the given content of its file, as the file,
+    /// the hash of the content, and the offset of the element in it
+    static PENDING_SELECTION: RefCell<Option<(PathBuf, u64, u32)>> = Default::default();
+}
+
+// triggered from the UI, running in UI thread
+pub fn duplicate_selected_element() {
+    let (Some(component_instance), Some(element)) = (component_instance(), selected_element())
+    else {
+        return;
+    };
+    if Rc::ptr_eq(&element, &root_element(&component_instance)) {
+        send_status("Cannot duplicate the root element", Health::Warning);
+        return;
+    }
+    let Some(node) = element.borrow().node.clone() else {
+        send_status("Cannot duplicate the element: it has no source code", Health::Warning);
+        return;
+    };
+    let Some((edit, offset)) = element_edit::duplicate_element(&node) else {
+        send_status("Cannot duplicate the root element of a component", Health::Warning);
+        return;
+    };
+    match apply_source_edits("Duplicate element", &node, vec![edit]) {
+        Ok(after) => {
+            let path = node.source_file.path().to_owned();
+            PENDING_SELECTION
+                .with(|p| *p.borrow_mut() = Some((path, content_hash(&after), offset)));
+        }
+        Err(e) => send_status(&format!("Cannot duplicate the element: {e}"), Health::Warning),
+    }
+}
+
+// Select the element waiting for `instance` to be built from the changed source, if any
+fn select_pending_element(instance: &ComponentInstance) {
+    let Some((path, offset)) = PENDING_SELECTION.with(|p| {
+        let mut pending = p.borrow_mut();
+        let (path, hash, _) = pending.as_ref()?;
+        let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+        if cache.source_hashes.get(path) != Some(hash) {
+            return None;
+        }
+        pending.take().map(|(path, _, offset)| (path, offset))
+    }) else {
+        return;
+    };
+
+    let Some(element) = element_starting_at(&element_tree(&root_element(instance)), &path, offset)
+    else {
+        return;
+    };
+    if let Some(position) = instance.element_position(&element) {
+        select_element(instance, &element, position);
+    }
+}
+
+// The element below `tree` whose source code starts at `offset` in `path`
+fn element_starting_at(tree: &ElementTreeNode, path: &Path, offset: u32) -> Option<ElementRc> {
+    let mut elements = Vec::new();
+    elements_in_range(tree, path, TextRange::empty(offset.into()), &mut elements);
+    elements.into_iter().find(|e| element_offset(e).map_or(false, |(_, o)| o == offset))
+}
+
+// Select the elements that start at `locations` in the source code of `instance`, the ones
+// that were selected in the previous instance. Elements whose source code is gone or moved
+// are not selected anymore.
+fn restore_selection(instance: &ComponentInstance, locations: &[(PathBuf, u32)]) {
+    if locations.is_empty() {
+        return;
+    }
+    let tree = element_tree(&root_element(instance));
+    let elements = locations
+        .iter()
+        .filter_map(|(path, offset)| {
+            let element = element_starting_at(&tree, path, *offset)?;
+            let position = instance.element_position(&element)?;
+            Some((element, position))
+        })
+        .collect::<Vec<_>>();
+    let positions = elements
+        .last()
+        .and_then(|(e, _)| element_offset(e))
+        .map(|(path, offset)| instance.component_positions(path, offset))
+        .unwrap_or_default();
+    show_selected_elements(&elements, positions);
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().map_or(false, |c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+// The edit adding an instance of `component` as the last child of `container`
+fn insert_component_edit(
+    container: &ElementRc,
+    component: &str,
+) -> Result<(syntax_nodes::Element, lsp_types::TextEdit), String> {
+    use i_slint_compiler::langtype::ElementType;
+
+    if !is_identifier(component) {
+        return Err("it is not a component name".into());
+    }
+    let Some(node) = container.borrow().node.clone() else {
+        return Err("the selected element has no source code".into());
+    };
+    match &container.borrow().base_type {
+        ElementType::Builtin(b) if b.disallow_global_types_as_child_elements => {
+            return Err(format!("a {} only accepts specific children", b.name));
+        }
+        ElementType::Component(c) if c.child_insertion_point.borrow().is_none() => {
+            return Err(format!("{} has no @children", c.id));
+        }
+        _ => (),
+    }
+    // A new child of a grid with rows could go into a row or after them, so let the user pick
+    let is_row = |e: syntax_nodes::SubElement| {
+        e.Element().QualifiedName().map_or(false, |q| q.text().to_string().trim() == "Row")
+    };
+    if node.SubElement().any(is_row) {
+        return Err("the selected grid has rows, select one of them instead".into());
+    }
+
+    let edit = element_edit::insert_child(&node, component)
+        .ok_or("its source code could not be changed")?;
+    Ok((node, edit))
+}
+
+thread_local! {static EDIT_HISTORY: RefCell<undo::EditHistory> = Default::default();}
+
+// Send `edits` of the file containing `element` to the editor, remembering them to undo them later
+// once the editor applied them. Returns the changed source.
+fn apply_source_edits(
+    label: &str,
+    element: &syntax_nodes::Element,
+    edits: Vec<lsp_types::TextEdit>,
+) -> Result<String, String> {
+    let path = element.source_file.path().to_owned();
+    let compiled = element.source_file.source().ok_or("its source code is not known")?;
+    let cached =
+        CONTENT_CACHE.get_or_init(Default::default).lock().unwrap().source_code.get(&path).cloned();
+    let before = match cached {
+        Some(cached) if is_compiled_from(compiled, &cached) => cached,
+        // The edits are relative to the compiled source code
+        Some(_) => return Err("its file changed since the preview got updated".into()),
+        None => compiled.to_owned(),
+    };
+    let after = element_edit::apply_text_edits(&before, &edits)
+        .ok_or("its source code could not be changed")?;
+    let edit = element_edit::workspace_edit(element, edits)
+        .ok_or("its source code could not be changed")?;
+
+    let change = undo::SourceChange {
+        label: label.into(),
+        path,
+        before,
+        after: after.clone(),
+        time: i_slint_core::animations::Instant::now().as_millis(),
+    };
+    send_workspace_edit(label.into(), edit, confirm_edits(), move |applied| {
+        if applied {
+            EDIT_HISTORY.with(|history| history.borrow_mut().push(change));
+        }
+    });
+    Ok(after)
+}
+
+/// Undo the last change done to the source code in the preview
+pub fn undo_last_preview_edit() {
+    run_in_ui_thread(move || async move {
+        apply_edit_history_step(false);
+    });
+}
+
+/// Redo the last change to the source code undone with `undo_last_preview_edit`
+pub fn redo_preview_edit() {
+    run_in_ui_thread(move || async move {
+        apply_edit_history_step(true);
+    });
+}
+
+fn apply_edit_history_step(redo: bool) {
+    let action = if redo { "redo" } else { "undo" };
+    let Some(change) = EDIT_HISTORY.with(|history| {
+        let history = history.borrow();
+        if redo {
+            history.next_redo().cloned()
+        } else {
+            history.next_undo().cloned()
+        }
+    }) else {
+        send_status(&format!("Nothing to {action} in the preview"), Health::Ok);
+        return;
+    };
+
+    let (from, to) =
+        if redo { (&change.before, &change.after) } else { (&change.after, &change.before) };
+    let current = CONTENT_CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .source_code
+        .get(&change.path)
+        .cloned();
+    if current.map_or(false, |current| &current != from) {
+        // The edit would not apply to what is in the editor anymore
+        EDIT_HISTORY.with(|history| history.borrow_mut().clear());
+        send_status(
+            &format!("Cannot {action} the change: the file was changed in the meantime"),
+            Health::Warning,
+        );
+        return;
+    }
+    let Ok(url) = lsp_types::Url::from_file_path(&change.path) else {
+        return;
+    };
+
+    let edit = lsp_types::WorkspaceEdit {
+        changes: Some(HashMap::from([(url, vec![element_edit::text_edit_between(from, to)])])),
+        ..Default::default()
+    };
+    let label =
+        if redo { format!("Redo {}", change.label) } else { format!("Undo {}", change.label) };
+    send_workspace_edit(label, edit, confirm_edits(), move |applied| {
+        if !applied {
+            return;
+        }
+        EDIT_HISTORY.with(|history| {
+            let mut history = history.borrow_mut();
+            // Only move the change over if nothing else changed the history in the meantime
+            if redo && history.next_redo() == Some(&change) {
+                history.redo();
+            } else if !redo && history.next_undo() == Some(&change) {
+                history.undo();
+            }
+        })
+    });
+}
+
+/// How close an edge has to get to a guide, in logical pixels, to snap to it
+const SNAP_DISTANCE: f32 = 4.;
+
+// The bits of the edges mask passed to `snap_geometry`, like the Edges global in ui/resizer.slint
+const EDGE_LEFT: i32 = 1;
+const EDGE_TOP: i32 = 2;
+const EDGE_RIGHT: i32 = 4;
+const EDGE_BOTTOM: i32 = 8;
+
+// triggered from the UI, running in UI thread
+pub fn snap_geometry(x: f32, y: f32, width: f32, height: f32, edges: i32) -> ui::SnappedGeometry {
+    let mut result = ui::SnappedGeometry { x, y, width, height, ..Default::default() };
+    if edges == 0 {
+        return result;
+    }
+    let (Some(component_instance), Some(element)) = (component_instance(), selected_element())
+    else {
+        return result;
+    };
+    let (vertical_lines, horizontal_lines) = snap_lines(&component_instance, &element);
+
+    let (left, right, vertical_guide) =
+        snap_span(x, x + width, edges & EDGE_LEFT != 0, edges & EDGE_RIGHT != 0, &vertical_lines);
+    result.x = left;
+    result.width = right - left;
+    result.has_vertical_guide = vertical_guide.is_some();
+    result.vertical_guide = vertical_guide.unwrap_or_default();
+
+    let (top, bottom, horizontal_guide) = snap_span(
+        y,
+        y + height,
+        edges & EDGE_TOP != 0,
+        edges & EDGE_BOTTOM != 0,
+        &horizontal_lines,
+    );
+    result.y = top;
+    result.height = bottom - top;
+    result.has_horizontal_guide = horizontal_guide.is_some();
+    result.horizontal_guide = horizontal_guide.unwrap_or_default();
+
+    result
+}
+
+// The x and y positions `element` can snap to: the edges of its siblings and the center lines
+// of its parent
+fn snap_lines(component_instance: &ComponentInstance, element: &ElementRc) -> (Vec<f32>, Vec<f32>) {
+    let (mut vertical, mut horizontal) = (Vec::new(), Vec::new());
+    let Some(parent) = find_parent_element(&root_element(component_instance), element) else {
+        return (vertical, horizontal);
+    };
+
+    if let Some(position) = component_instance.element_position(&parent) {
+        vertical.push(position.center().x);
+        horizontal.push(position.center().y);
+    }
+    for sibling in parent.borrow().children.iter().filter(|c| !Rc::ptr_eq(c, element)) {
+        if let Some(position) = component_instance.element_position(sibling) {
+            vertical.extend([position.min_x(), position.max_x()]);
+            horizontal.extend([position.min_y(), position.max_y()]);
+        }
+    }
+    (vertical, horizontal)
+}
+
+// Snap the moving edges of the span `start..end` to the closest of `lines`, if one is close
+// enough. Moving both edges keeps the size, and snaps the center of the span as well.
+// Returns the new edges and the line that got snapped to.
+fn snap_span(
+    start: f32,
+    end: f32,
+    move_start: bool,
+    move_end: bool,
+    lines: &[f32],
+) -> (f32, f32, Option<f32>) {
+    let mut edges = Vec::new();
+    if move_start {
+        edges.push(start);
+    }
+    if move_end {
+        edges.push(end);
+    }
+    if move_start && move_end {
+        edges.push((start + end) / 2.);
+    }
+
+    let closest = edges
+        .iter()
+        .flat_map(|edge| lines.iter().map(move |line| (line - edge, *line)))
+        .filter(|(delta, _)| delta.abs() <= SNAP_DISTANCE)
+        .min_by(|(a, _), (b, _)| a.abs().total_cmp(&b.abs()));
+    let Some((delta, line)) = closest else {
+        return (start, end, None);
+    };
+    (
+        if move_start { start + delta } else { start },
+        if move_end { end + delta } else { end },
+        Some(line),
+    )
+}
+
+// triggered from the UI, running in UI thread
+/// Measure from the element (or the point, if there is none) at `x`/`y`. The first click
+/// starts a new measurement, the second one completes `previous`.
+pub fn measure_at(x: f32, y: f32, previous: ui::Measurement) -> ui::Measurement {
+    let rect = component_instance()
+        .and_then(|component_instance| {
+            let root_element = root_element(&component_instance);
+            element_at(x, y, &component_instance, &root_element)
+        })
+        .map(|(_, rect)| rect)
+        .unwrap_or_else(|| LogicalRect::new(LogicalPoint::new(x, y), Default::default()));
+
+    if !previous.has_from || previous.has_to {
+        return ui::Measurement {
+            has_from: true,
+            from: measured_rect(&rect),
+            ..Default::default()
+        };
+    }
+    let from = LogicalRect::new(
+        LogicalPoint::new(previous.from.x, previous.from.y),
+        i_slint_core::lengths::LogicalSize::new(previous.from.width, previous.from.height),
+    );
+    measure(&from, &rect)
+}
+
+fn measured_rect(rect: &LogicalRect) -> ui::MeasuredRect {
+    ui::MeasuredRect {
+        x: rect.origin.x,
+        y: rect.origin.y,
+        width: rect.size.width,
+        height: rect.size.height,
+    }
+}
+
+/// The gaps between `from` and `to`, with the dimension lines to show them. The gaps are
+/// zero where the rectangles overlap.
+fn measure(from: &LogicalRect, to: &LogicalRect) -> ui::Measurement {
+    // The start and the end of the gap along one axis, and where the dimension line goes
+    // on the other axis: through the middle of the overlap, if there is one.
+    let gap = |from_start: f32,
+               from_end: f32,
+               to_start: f32,
+               to_end: f32,
+               cross: (f32, f32, f32, f32)| {
+        let (start, end) = if to_start >= from_end {
+            (from_end, to_start)
+        } else if from_start >= to_end {
+            (to_end, from_start)
+        } else {
+            (0., 0.)
+        };
+        let (from_cross_start, from_cross_end, to_cross_start, to_cross_end) = cross;
+        let overlap_start = from_cross_start.max(to_cross_start);
+        let overlap_end = from_cross_end.min(to_cross_end);
+        let line = if overlap_start <= overlap_end {
+            (overlap_start + overlap_end) / 2.
+        } else {
+            (from_cross_start + from_cross_end + to_cross_start + to_cross_end) / 4.
+        };
+        (start, end, line)
+    };
+
+    let (x1, x2, horizontal_y) = gap(
+        from.min_x(),
+        from.max_x(),
+        to.min_x(),
+        to.max_x(),
+        (from.min_y(), from.max_y(), to.min_y(), to.max_y()),
+    );
+    let (y1, y2, vertical_x) = gap(
+        from.min_y(),
+        from.max_y(),
+        to.min_y(),
+        to.max_y(),
+        (from.min_x(), from.max_x(), to.min_x(), to.max_x()),
+    );
+
+    ui::Measurement {
+        has_from: true,
+        from: measured_rect(from),
+        has_to: true,
+        to: measured_rect(to),
+        horizontal_x1: x1,
+        horizontal_x2: x2,
+        horizontal_y,
+        vertical_y1: y1,
+        vertical_y2: y2,
+        vertical_x,
+        distance: (x2 - x1).hypot(y2 - y1),
+    }
+}
+
+/// What is shown while an element is isolated
+enum Isolation {
+    /// The component the isolated element is an instance of is previewed on its own.
+    /// `previous` is the component to go back to.
+    Component { previous: PreviewComponent },
+    /// Everything but the isolated element is masked
+    Mask,
+}
+
+thread_local! {
+    static ISOLATION: RefCell<Option<Isolation>> = Default::default();
+}
+
+// triggered from the UI, running in UI thread
+pub fn isolate_changed(enabled: bool) {
+    if enabled {
+        isolate_selected_element();
+    } else {
+        end_isolation();
+    }
+}
+
+fn isolate_selected_element() {
+    let Some((element, position)) = selected_elements().into_iter().next() else {
+        update_isolation(false, None);
+        return;
+    };
+
+    let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let previous = cache.current.clone();
+    drop(cache);
+
+    let component = isolated_component(&element, &previous.style)
+        .filter(|c| get_file_from_cache(c.path.clone()).is_some());
+    match component {
+        Some(component) => {
+            ISOLATION.with(|i| *i.borrow_mut() = Some(Isolation::Component { previous }));
+            update_isolation(true, None);
+            load_preview(component);
+        }
+        None => {
+            ISOLATION.with(|i| *i.borrow_mut() = Some(Isolation::Mask));
+            update_isolation(true, Some(position));
+        }
+    }
+}
+
+fn end_isolation() {
+    let isolation = ISOLATION.with(|i| i.borrow_mut().take());
+    update_isolation(false, None);
+    if let Some(Isolation::Component { previous }) = isolation {
+        if !previous.path.as_os_str().is_empty() {
+            load_preview(previous);
+        }
+    }
+}
+
+/// The component to preview to show the `element` on its own: a `_Preview` inheriting
+/// from its type in the file it is used in. Returns None for builtin elements, which
+/// get masked instead.
+fn isolated_component(element: &ElementRc, style: &str) -> Option<PreviewComponent> {
+    let node = element.borrow().node.clone()?;
+    let type_name =
+        i_slint_compiler::object_tree::QualifiedTypeName::from_node(node.QualifiedName()?)
+            .to_string();
+    let is_builtin = i_slint_compiler::typeregister::TypeRegister::builtin()
+        .borrow()
+        .lookup_element(&type_name)
+        .is_ok();
+    if type_name.is_empty() || is_builtin {
+        return None;
+    }
+
+    Some(PreviewComponent {
+        path: node.source_file.path().to_path_buf(),
+        component: Some(type_name),
+        style: style.to_string(),
+        wrapper_body: None,
+    })
+}
+
+fn set_ui_isolation(ui: &ui::PreviewUi, isolated: bool, mask: Option<LogicalRect>) {
+    ui.set_isolated(isolated);
+    ui.set_has_isolation_mask(mask.is_some());
+    ui.set_isolation_mask(mask.as_ref().map(measured_rect).unwrap_or_default());
+}
+
+fn change_style() {
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let ui_is_visible = cache.ui_is_visible;
+    let current = cache.current.clone();
+    if !current.path.as_os_str().is_empty() {
+        cache.style_overrides.insert(current.path.clone(), get_current_style());
+    }
+    drop(cache);
+
+    if ui_is_visible && !current.path.as_os_str().is_empty() {
+        load_preview(current);
+    }
+}
+
+fn change_component(component: slint::SharedString) {
+    let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let ui_is_visible = cache.ui_is_visible;
+    let current = cache.current.clone();
+    drop(cache);
+
+    if ui_is_visible && !current.path.as_os_str().is_empty() {
+        load_preview(PreviewComponent { component: Some(component.to_string()), ..current });
+    }
+}
+
+/// How long a build has to take before it is reported as being in progress. Most reloads
+/// are faster, and the status would only flicker then.
+const UPDATING_STATUS_DELAY: std::time::Duration = std::time::Duration::from_millis(150);
+
+thread_local! {
+    static UPDATING_STATUS_TIMER: slint::Timer = Default::default();
+    /// The number of files loaded by the current build
+    static LOADED_FILES: Cell<usize> = Default::default();
+}
+
+pub fn start_parsing() {
+    set_diagnostics(&[]);
+    LOADED_FILES.with(|count| count.set(0));
+    UPDATING_STATUS_TIMER.with(|timer| {
+        timer.start(slint::TimerMode::SingleShot, UPDATING_STATUS_DELAY, || {
+            set_status_text("Updating Preview...");
+            send_status("Loading Preview…", Health::Ok);
+        })
+    });
+}
+
+/// Show which file the current build loads, once it is reported as being in progress.
+/// The compiler does not tell how many files are left to load.
+fn report_file_loading(path: &Path) {
+    let count = LOADED_FILES.with(|count| {
+        count.set(count.get() + 1);
+        count.get()
+    });
+    if UPDATING_STATUS_TIMER.with(|timer| timer.running()) {
+        // Too early, the load might still be a quick one
+        return;
+    }
+    let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+    let files = if count == 1 { "file" } else { "files" };
+    let message = format!("Loading {name} ({count} {files} loaded)…");
+    set_status_text(&message);
+    send_status(&message, Health::Ok);
+}
+
+/// Report the end of a build. `note` gets appended to the status message, for a
+/// successful build it is a warning.
+pub fn finish_parsing(
+    ok: bool,
+    elapsed: std::time::Duration,
+    diagnostics: &[slint_interpreter::Diagnostic],
+    note: Option<String>,
+) {
+    UPDATING_STATUS_TIMER.with(|timer| timer.stop());
+    set_status_text("");
+    if ok {
+        let message = format!("Preview loaded in {}ms", elapsed.as_millis());
+        let message =
+            append_diagnostics_count(message, diagnostics, DiagnosticLevel::Warning, "warning");
+        // Let the editor show that the preview is up to date, but not clean
+        let has_warnings = diagnostics.iter().any(|d| d.level() == DiagnosticLevel::Warning);
+        match note {
+            Some(note) => send_status(&format!("{message}. {note}"), Health::Warning),
+            None if has_warnings => send_status(&message, Health::Warning),
+            None => send_status(&message, Health::Ok),
+        }
+    } else {
+        let message = "Preview not updated".to_string();
+        let message =
+            append_diagnostics_count(message, diagnostics, DiagnosticLevel::Error, "error");
+        match note {
+            Some(note) => send_status(&format!("{message}. {note}"), Health::Error),
+            None => send_status(&message, Health::Error),
+        }
+    }
+}
+
+/// Returns the names of the configured library paths that none of the dependencies
+/// got loaded from, sorted by name
+fn unused_library_paths(
+    library_paths: &HashMap<String, PathBuf>,
+    dependencies: &[PathBuf],
+) -> Vec<String> {
+    use i_slint_compiler::pathutils::clean_path;
+
+    let dependencies = dependencies.iter().map(|d| clean_path(d)).collect::<Vec<_>>();
+    let mut result = library_paths
+        .iter()
+        .filter(|(_, path)| {
+            let path = clean_path(path);
+            !dependencies.iter().any(|d| d.starts_with(&path))
+        })
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    result.sort();
+    result
+}
+
+/// Appends " (N <what>s)" to `message` if there are diagnostics of the given level
+fn append_diagnostics_count(
+    message: String,
+    diagnostics: &[slint_interpreter::Diagnostic],
+    level: DiagnosticLevel,
+    what: &str,
+) -> String {
+    match diagnostics.iter().filter(|d| d.level() == level).count() {
+        0 => message,
+        1 => format!("{message} (1 {what})"),
+        count => format!("{message} ({count} {what}s)"),
+    }
+}
+
+pub fn config_changed(config: PreviewConfig) {
+    logging::set_level(config.log_level);
+    if let Some(cache) = CONTENT_CACHE.get() {
+        let mut cache = cache.lock().unwrap();
+        // These only change what gets logged and highlighted
+        cache.config.log_level = config.log_level;
+        cache.config.follow_cursor = config.follow_cursor;
+        cache.config.auto_open = config.auto_open;
+        cache.config.reveal_on_select = config.reveal_on_select;
+        cache.config.confirm_edits = config.confirm_edits;
+        cache.config.select_through_types = config.select_through_types.clone();
+        if cache.config.renderer != config.renderer {
+            // The window system keeps its renderer, reloading does not change it
+            cache.config.renderer = config.renderer;
+            drop(cache);
+
+            renderer_changed(config.renderer);
+            cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+        }
+        if cache.config.selection_colors != config.selection_colors {
+            // No need to reload the preview for this, redrawing the selection is enough
+            cache.config.selection_colors = config.selection_colors;
+            let ui_is_visible = cache.ui_is_visible;
+
+            drop(cache);
+
+            if ui_is_visible {
+                run_in_ui_thread(move || async move { refresh_selections() });
+            }
+            cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+        }
+        if cache.config.background != config.background
+            || cache.config.scale_factor != config.scale_factor
+            || cache.config.preview_width != config.preview_width
+            || cache.config.preview_height != config.preview_height
+            || cache.config.show_grid != config.show_grid
+            || cache.config.grid_spacing != config.grid_spacing
+            || cache.config.reference_image != config.reference_image
+        {
+            // These only affect how the preview is shown, so no reload is needed either
+            cache.config.background = config.background;
+            cache.config.scale_factor = config.scale_factor;
+            cache.config.preview_width = config.preview_width;
+            cache.config.preview_height = config.preview_height;
+            cache.config.show_grid = config.show_grid;
+            cache.config.grid_spacing = config.grid_spacing;
+            cache.config.reference_image = config.reference_image.clone();
+            let view_config = cache.config.clone();
+            let ui_is_visible = cache.ui_is_visible;
+
+            drop(cache);
+
+            if ui_is_visible {
+                set_view_config(view_config);
+            }
+            cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+        }
+        if cache.config != config {
+            // A changed configuration wins over what was set by command
+            let mut hide_ui = (cache.config.hide_ui != config.hide_ui).then_some(config.hide_ui);
+            if hide_ui.is_some() {
+                if let Some(restore) = &mut cache.presentation {
+                    // Applied once the presentation is over
+                    restore.preview_ui_visible = None;
+                    restore.selections_without_ui = false;
+                    hide_ui = None;
+                } else {
+                    cache.preview_ui_visible = None;
+                    cache.selections_without_ui = false;
+                }
+            }
+            cache.config = config;
+            let current = cache.current.clone();
+            let ui_is_visible = cache.ui_is_visible;
+
+            drop(cache);
+
+            if ui_is_visible {
+                if let Some(Some(hide_ui)) = hide_ui {
+                    set_show_preview_ui(!hide_ui, false);
+                }
+                if !current.path.as_os_str().is_empty() {
+                    load_preview(current);
+                }
+            }
+        }
+    };
+}
+
+const SCALE_FACTOR_RANGE: (f32, f32) = (0.25, 8.0);
+
+/// Distance between the lines of the grid in logical pixels, when not configured
+const DEFAULT_GRID_SPACING: f32 = 8.;
+
+thread_local! {
+    /// The scale factor of the preview window before it got overridden by the configuration
+    static SYSTEM_SCALE_FACTOR: Cell<Option<f32>> = Default::default();
+    /// The zoom picked in the preview, applied on top of the configured scale factor
+    static ZOOM: Cell<f32> = Cell::new(1.);
+}
+
+/// How much zooming in or out changes the zoom
+const ZOOM_STEP: f32 = 1.25;
+
+/// Space (in logical pixels) left around the component when zooming it to fit
+const ZOOM_TO_FIT_MARGIN: f32 = 20.;
+
+/// Apply the parts of the configuration that do not need the preview to be reloaded
+fn set_ui_view_config(ui: &ui::PreviewUi, config: &PreviewConfig) {
+    ui.set_canvas_background(match config.background {
+        PreviewBackground::Light => ui::CanvasBackground::Light,
+        PreviewBackground::Dark => ui::CanvasBackground::Dark,
+        PreviewBackground::Checkerboard => ui::CanvasBackground::Checkerboard,
+    });
+    let fixed_size = |size: Option<f32>| size.filter(|s| s.is_finite() && *s > 0.).unwrap_or(0.);
+    ui.set_fixed_width(fixed_size(config.preview_width));
+    ui.set_fixed_height(fixed_size(config.preview_height));
+    ui.set_show_grid(config.show_grid);
+    ui.set_grid_spacing(
+        config.grid_spacing.filter(|s| s.is_finite() && *s > 0.).unwrap_or(DEFAULT_GRID_SPACING),
+    );
+    set_ui_reference_image(ui, config.reference_image.as_ref());
+
+    let zoom = ZOOM.with(Cell::get);
+    ui.set_zoom_level(zoom);
+    let scale_factor = SYSTEM_SCALE_FACTOR.with(|system| {
+        let configured = clamped_scale_factor(config.scale_factor);
+        if configured.is_none() && zoom == 1. {
+            return system.take();
+        }
+        if system.get().is_none() {
+            system.set(Some(ui.window().scale_factor()));
+        }
+        let base = configured.or(system.get()).unwrap_or(1.);
+        Some((base * zoom).clamp(SCALE_FACTOR_RANGE.0, SCALE_FACTOR_RANGE.1))
+    });
+    let Some(scale_factor) = scale_factor else {
+        return;
+    };
+    if ui.window().scale_factor() != scale_factor {
+        ui.window().dispatch_event(i_slint_core::platform::WindowEvent::ScaleFactorChanged {
+            scale_factor,
+        });
+    }
+}
+
+// triggered from the UI, running in UI thread
+pub fn zoom_in() {
+    set_zoom(ZOOM.with(Cell::get) * ZOOM_STEP);
+}
+
+// triggered from the UI, running in UI thread
+pub fn zoom_out() {
+    set_zoom(ZOOM.with(Cell::get) / ZOOM_STEP);
+}
+
+// triggered from the UI, running in UI thread
+pub fn zoom_reset() {
+    set_zoom(1.);
+}
+
+// triggered from the UI, running in UI thread
+/// Zoom so that the component fits into the visible part of the canvas
+pub fn zoom_to_fit(visible_width: f32, visible_height: f32) {
+    let Some(component_instance) = component_instance() else {
+        return;
+    };
+    let Some(position) = component_instance.element_position(&root_element(&component_instance))
+    else {
+        return;
+    };
+    if let Some(factor) =
+        fit_factor(position.size.width, position.size.height, visible_width, visible_height)
+    {
+        set_zoom(ZOOM.with(Cell::get) * factor);
+    }
+}
+
+/// How much to scale something of `width`×`height` so that it fits into the visible area,
+/// with a margin. Both sizes are in logical pixels of the current scale factor.
+fn fit_factor(width: f32, height: f32, visible_width: f32, visible_height: f32) -> Option<f32> {
+    if width <= 0. || height <= 0. {
+        return None;
+    }
+    let factor = ((visible_width - 2. * ZOOM_TO_FIT_MARGIN) / width)
+        .min((visible_height - 2. * ZOOM_TO_FIT_MARGIN) / height);
+    (factor.is_finite() && factor > 0.).then_some(factor)
+}
+
+fn set_zoom(zoom: f32) {
+    ZOOM.with(|z| z.set(zoom.clamp(SCALE_FACTOR_RANGE.0, SCALE_FACTOR_RANGE.1)));
+    let config = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap().config.clone();
+    set_view_config(config);
+}
+
+fn clamped_scale_factor(scale_factor: Option<f32>) -> Option<f32> {
+    scale_factor
+        .filter(|f| f.is_finite())
+        .map(|f| f.clamp(SCALE_FACTOR_RANGE.0, SCALE_FACTOR_RANGE.1))
+}
+
+/// If the file is in the cache, returns it.
+/// In any way, register it as a dependency, unless it is excluded from being watched
+fn get_file_from_cache(path: PathBuf) -> Option<String> {
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let r = cache.source_code.get(&path).cloned();
+    cache.loaded_files.insert(path.clone());
+    if !is_watch_excluded(&cache.config.watch_exclude, &path) {
+        cache.dependency.insert(path);
+    }
+    r
+}
+
+// Does one of the `patterns` of the configuration match the path?
+fn is_watch_excluded(patterns: &[String], path: &Path) -> bool {
+    if patterns.is_empty() {
+        return false;
+    }
+    let path = path.to_string_lossy().replace('\\', "/");
+    patterns.iter().any(|pattern| {
+        // Relative patterns can match anywhere in the path
+        if pattern.starts_with('/') || pattern.starts_with("**") {
+            glob_matches(pattern, &path)
+        } else {
+            glob_matches(&format!("**/{pattern}"), &path)
+        }
+    })
+}
+
+/// Does `path` match the glob `pattern`? `*` and `?` match within a directory name,
+/// `**` matches any number of directories.
+fn glob_matches(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[char], path: &[char]) -> bool {
+        match pattern {
+            [] => path.is_empty(),
+            ['*', '*', rest @ ..] => {
+                // `**/` also stands for no directory at all
+                (0..=path.len()).any(|i| matches(rest, &path[i..]))
+                    || (rest.first() == Some(&'/') && matches(&rest[1..], path))
+            }
+            ['*', rest @ ..] => (0..=path.len())
+                .take_while(|i| *i == 0 || path[i - 1] != '/')
+                .any(|i| matches(rest, &path[i..])),
+            ['?', rest @ ..] => {
+                path.first().map_or(false, |c| *c != '/') && matches(rest, &path[1..])
+            }
+            [c, rest @ ..] => path.first() == Some(c) && matches(rest, &path[1..]),
+        }
+    }
+
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let path = path.chars().collect::<Vec<_>>();
+    matches(&pattern, &path)
+}
+
+fn register_resource_dependency(path: PathBuf) {
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    cache.resource_dependency.insert(path);
+}
+
+/// Fonts do not pass the resource URL mapper, so look for them in the imports of the
+/// Slint files the component depends on. Only files in the content cache are checked.
+fn register_font_dependencies() {
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let fonts = cache
+        .dependency
+        .iter()
+        .filter_map(|path| Some(font_imports(cache.source_code.get(path)?, path)))
+        .flatten()
+        .collect::<Vec<_>>();
+    cache.resource_dependency.extend(fonts);
+}
+
+/// The font files the document at `path` imports
+fn font_imports(source_code: &str, path: &Path) -> Vec<PathBuf> {
+    use i_slint_compiler::parser::SyntaxKind;
+
+    let mut diag = i_slint_compiler::diagnostics::BuildDiagnostics::default();
+    let doc: syntax_nodes::Document =
+        i_slint_compiler::parser::parse(source_code.to_string(), Some(path), None, &mut diag)
+            .into();
+
+    doc.ImportSpecifier()
+        .filter(|i| i.ImportIdentifierList().is_none())
+        .filter_map(|i| i.child_token(SyntaxKind::StringLiteral))
+        .map(|t| t.text().trim_matches('"').to_string())
+        .filter(|f| f.ends_with(".ttf") || f.ends_with(".otf") || f.ends_with(".ttc"))
+        .filter_map(|f| {
+            path.parent().and_then(|dir| i_slint_compiler::pathutils::join(dir, Path::new(&f)))
+        })
+        .collect()
+}
+
+/// Load the component that was previewed last again, so that the editor does not
+/// need to send it again when the preview UI gets shown after it was hidden.
+pub fn reload_last_component() {
+    let last_component =
+        CONTENT_CACHE.get_or_init(Default::default).lock().unwrap().last_component.clone();
+
+    if !last_component.path.as_os_str().is_empty() {
+        load_preview(last_component);
+    }
+}
+
+/// Render the current component with the software renderer and save it to `path`,
+/// the image format is picked from its extension.
+///
+/// This renders a new instance of the component that only gets the values of the public
+/// properties of the shown one. Its internal state, like scroll positions, toggled elements
+/// and values set from callbacks, starts out with the initial values.
+///
+/// The selection overlays are drawn by the preview UI and not by the component,
+/// so they never end up in the image.
+///
+/// Blocks until the UI thread rendered the image, to report failures to the caller.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_screenshot(path: PathBuf) -> Result<(), String> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    run_in_ui_thread(move || async move {
+        let result = render_screenshot(&path);
+        if result.is_ok() {
+            send_status(&format!("Saved screenshot to {}", path.display()), Health::Ok);
+        }
+        let _ = sender.send(result);
+    });
+    receiver
+        .recv_timeout(SCREENSHOT_TIMEOUT)
+        .map_err(|_| "The preview did not render the screenshot in time".to_string())?
+}
+
+/// How long to wait for the UI thread to render a screenshot
+#[cfg(not(target_arch = "wasm32"))]
+const SCREENSHOT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[cfg(not(target_arch = "wasm32"))]
+fn render_screenshot(path: &Path) -> Result<(), String> {
+    let component_instance = component_instance().ok_or("No component is loaded in the preview")?;
+    let size = component_instance
+        .element_position(&root_element(&component_instance))
+        .map(|r| r.size)
+        .ok_or("The previewed component has no geometry")?;
+    let (width, height) = (size.width.ceil() as u32, size.height.ceil() as u32);
+    if width == 0 || height == 0 {
+        return Err("The previewed component is empty".into());
+    }
+
+    let definition = component_instance.definition();
+    let buffer = render_definition(&definition, width, height, |snapshot| {
+        // Internal state can not be copied over
+        for (name, _) in definition.properties() {
+            if let Ok(value) = component_instance.get_property(&name) {
+                let _ = snapshot.set_property(&name, value);
+            }
+        }
+    })?;
+
+    let bytes = buffer.iter().flat_map(|p| [p.r, p.g, p.b]).collect::<Vec<_>>();
+    image::save_buffer(path, &bytes, width, height, image::ColorType::Rgb8)
+        .map_err(|e| e.to_string())
+}
+
+/// Render a new instance of `definition` with the software renderer, `init` can set it up
+/// before it gets rendered
+#[cfg(not(target_arch = "wasm32"))]
+fn render_definition(
+    definition: &ComponentDefinition,
+    width: u32,
+    height: u32,
+    init: impl FnOnce(&ComponentInstance),
+) -> Result<Vec<i_slint_core::graphics::Rgb8Pixel>, String> {
+    use i_slint_core::software_renderer::{MinimalSoftwareWindow, RepaintBufferType};
+    use i_slint_core::window::WindowAdapter;
+
+    let window = MinimalSoftwareWindow::new(RepaintBufferType::NewBuffer);
+    window.set_size(i_slint_core::api::PhysicalSize::new(width, height));
+
+    let instance =
+        definition.create_with_existing_window(window.window()).map_err(|e| e.to_string())?;
+    init(&instance);
+    instance.show().map_err(|e| e.to_string())?;
+
+    let mut buffer =
+        vec![i_slint_core::graphics::Rgb8Pixel::default(); width as usize * height as usize];
+    window.request_redraw();
+    window.draw_if_needed(|renderer| {
+        renderer.render(&mut buffer, width as usize);
+    });
+    instance.hide().map_err(|e| e.to_string())?;
+    Ok(buffer)
+}
+
+/// A component that only shows a color, to check that the preview works at all
+const SELF_TEST_SOURCE: &str =
+    "export component SelfTest inherits Rectangle { width: 8px; height: 8px; background: #ff0000; }";
+const SELF_TEST_PATH: &str = "<self test>";
+/// How long to wait for the preview area to create the test component
+const SELF_TEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Check that components can be compiled, instantiated in the preview area and rendered,
+/// and report the outcome of each stage through the status
+///
+/// The previewed component is not touched, it gets shown again afterwards.
+pub fn self_test() {
+    run_in_ui_thread(move || async move {
+        let stages = self_test_impl().await;
+        let ok = stages.iter().all(|(_, r)| r.is_ok());
+        let message = stages
+            .into_iter()
+            .map(|(stage, r)| match r {
+                Ok(()) => format!("{stage}: ok"),
+                Err(e) => format!("{stage}: {e}"),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        send_status(
+            &format!("Preview self-test: {message}"),
+            if ok { Health::Ok } else { Health::Error },
+        );
+    });
+}
+
+async fn self_test_impl() -> Vec<(&'static str, Result<(), String>)> {
+    let mut stages = Vec::new();
+
+    let mut compiler = slint_interpreter::ComponentCompiler::default();
+    let definition =
+        compiler.build_from_source(SELF_TEST_SOURCE.into(), PathBuf::from(SELF_TEST_PATH)).await;
+    let Some(definition) = definition else {
+        let errors = compiler
+            .diagnostics()
+            .iter()
+            .filter(|d| d.level() == DiagnosticLevel::Error)
+            .map(|d| d.message().to_string())
+            .collect::<Vec<_>>();
+        stages.push(("compile", Err(errors.join("; "))));
+        return stages;
+    };
+    stages.push(("compile", Ok(())));
+
+    stages.push(("instantiate", self_test_instantiate(definition.clone()).await));
+
+    #[cfg(not(target_arch = "wasm32"))]
+    stages.push((
+        "render",
+        render_definition(&definition, 8, 8, |_| ()).and_then(|buffer| {
+            buffer
+                .iter()
+                .all(|p| (p.r, p.g, p.b) == (0xff, 0, 0))
+                .then_some(())
+                .ok_or_else(|| "the rendered image is wrong".to_string())
+        }),
+    ));
+
+    stages
+}
+
+/// Show `definition` in the preview area instead of the previewed component, until the
+/// preview area created it
+async fn self_test_instantiate(definition: ComponentDefinition) -> Result<(), String> {
+    use std::task::{Poll, Waker};
+
+    let result = Rc::new(RefCell::new(None::<Result<(), String>>));
+    let waker = Rc::new(RefCell::new(None::<Waker>));
+    let factory = {
+        let (result, waker) = (result.clone(), waker.clone());
+        slint::ComponentFactory::new(move |ctx: FactoryContext| {
+            let instance = definition.create_embedded(ctx);
+            *result.borrow_mut() = Some(instance.as_ref().map(|_| ()).map_err(|e| e.to_string()));
+            if let Some(waker) = waker.borrow_mut().take() {
+                waker.wake();
+            }
+            instance.ok()
+        })
+    };
+
+    let previous = replace_preview_area(factory).ok_or("the preview is not open")?;
+    let created = with_timeout(
+        SELF_TEST_TIMEOUT,
+        std::future::poll_fn(|cx| match result.borrow_mut().take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                *waker.borrow_mut() = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }),
+    )
+    .await;
+    replace_preview_area(previous);
+
+    created.unwrap_or_else(|| Err("the preview area did not create the component".into()))
+}
+
+/// Set a property of the previewed component instance, without recompiling
+///
+/// Errors are reported through the status.
+pub fn set_preview_property(name: String, value: serde_json::Value) {
+    run_in_ui_thread(move || async move {
+        if let Err(e) = set_preview_property_impl(&name, &value) {
+            send_status(&format!("Cannot set property {name}: {e}"), Health::Error);
+        }
+    });
+}
+
+/// Returns the public properties of the previewed component with their type and current value
+fn root_properties(
+    component_instance: &ComponentInstance,
+) -> Vec<(String, slint_interpreter::ValueType, String)> {
+    let mut properties = component_instance
+        .definition()
+        .properties()
+        .map(|(name, value_type)| {
+            let value = component_instance
+                .get_property(&name)
+                .map(|v| value_to_string(&v))
+                .unwrap_or_default();
+            (name, value_type, value)
+        })
+        .collect::<Vec<_>>();
+    properties.sort_by(|a, b| a.0.cmp(&b.0));
+    properties
+}
+
+fn property_info_model(
+    properties: Vec<(String, slint_interpreter::ValueType, String)>,
+) -> slint::ModelRc<ui::PropertyInfo> {
+    let properties = properties
+        .into_iter()
+        .map(|(name, value_type, value)| ui::PropertyInfo {
+            name: name.into(),
+            type_name: format!("{value_type:?}").to_lowercase().into(),
+            value: value.into(),
+        })
+        .collect::<Vec<_>>();
+    Rc::new(slint::VecModel::from(properties)).into()
+}
+
+fn set_root_properties(ui: &ui::PreviewUi, component_instance: &ComponentInstance) {
+    ui.set_root_properties(property_info_model(root_properties(component_instance)));
+}
+
+/// The properties shown for the selected element, if the element has them
+const SELECTED_ELEMENT_PROPERTIES: &[&str] = &[
+    "x",
+    "y",
+    "width",
+    "height",
+    "visible",
+    "opacity",
+    "background",
+    "color",
+    "border-color",
+    "border-width",
+    "border-radius",
+    "text",
+    "font-size",
+    "font-weight",
+];
+
+/// The current values of the relevant properties of `element`, in the first place it occurs
+fn element_properties(
+    component_instance: &ComponentInstance,
+    element: &ElementRc,
+) -> Vec<(String, slint_interpreter::ValueType, String)> {
+    SELECTED_ELEMENT_PROPERTIES
+        .iter()
+        .filter_map(|name| {
+            let value = component_instance.element_property(element, name)?;
+            Some((name.to_string(), value.value_type(), value_to_string(&value)))
+        })
+        .collect()
+}
+
+/// How many callback invocations are kept in the log
+const CALLBACK_LOG_SIZE: usize = 200;
+
+/// Log the invocations of the public callbacks of the component instance in the UI.
+/// This starts a new, empty log.
+///
+/// Callbacks that return a value or that already have a handler in the source code are left
+/// alone, as replacing their handler would change the behavior of the component.
+fn install_callback_log(ui: &ui::PreviewUi, component_instance: &ComponentInstance) {
+    use slint::Model;
+
+    let log = Rc::new(slint::VecModel::<slint::SharedString>::default());
+    ui.set_callback_log(log.clone().into());
+
+    let definition = component_instance.definition();
+    let root_element = definition.root_component().root_element.clone();
+    for (name, ty) in definition.properties_and_callbacks() {
+        let i_slint_compiler::langtype::Type::Callback { return_type, .. } = ty else {
+            continue;
+        };
+        if return_type.is_some() || has_callback_handler(&root_element, &name) {
+            continue;
+        }
+
+        let log = log.clone();
+        let callback_name = name.clone();
+        let _ = component_instance.set_callback(&name, move |args| {
+            let time = i_slint_core::animations::Instant::now().as_millis() as f64 / 1000.;
+            let args = args.iter().map(value_to_string).collect::<Vec<_>>().join(", ");
+            if log.row_count() >= CALLBACK_LOG_SIZE {
+                log.remove(0);
+            }
+            log.push(format!("{time:.3}s: {callback_name}({args})").into());
+            slint_interpreter::Value::Void
+        });
+    }
+}
+
+// Does the element (or the component it is based on) set a handler for the callback?
+fn has_callback_handler(element: &ElementRc, name: &str) -> bool {
+    let e = element.borrow();
+    e.bindings.contains_key(name)
+        || matches!(
+            &e.base_type,
+            i_slint_compiler::langtype::ElementType::Component(c)
+                if has_callback_handler(&c.root_element, name)
+        )
+}
+
+/// A short human readable representation of a value, as shown in the property list
+fn value_to_string(value: &slint_interpreter::Value) -> String {
+    use slint::Model;
+    use slint_interpreter::Value as V;
+
+    match value {
+        V::Void => String::new(),
+        V::Number(n) => n.to_string(),
+        V::String(s) => format!("{:?}", s.as_str()),
+        V::Bool(b) => b.to_string(),
+        V::Model(m) => format!("[{} items]", m.row_count()),
+        V::Struct(s) => format!(
+            "{{ {} }}",
+            s.iter()
+                .map(|(name, v)| format!("{name}: {}", value_to_string(v)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        V::Brush(i_slint_core::Brush::SolidColor(c)) => {
+            format!("#{:02x}{:02x}{:02x}{:02x}", c.red(), c.green(), c.blue(), c.alpha())
+        }
+        V::Brush(_) => "gradient".into(),
+        V::Image(_) => "image".into(),
+        _ => "…".into(),
+    }
+}
+
+fn set_preview_property_impl(name: &str, value: &serde_json::Value) -> Result<(), String> {
+    let component_instance = component_instance().ok_or("No component is loaded in the preview")?;
+    let normalized_name = name.replace('_', "-");
+    let value_type = component_instance
+        .definition()
+        .properties()
+        .find(|(n, _)| n.replace('_', "-") == normalized_name)
+        .map(|(_, t)| t)
+        .ok_or("The previewed component has no such property")?;
+    let value = json_to_value(value, Some(value_type))?;
+    component_instance.set_property(name, value).map_err(|e| e.to_string())?;
+    refresh_root_properties(&component_instance);
+    Ok(())
+}
+
+/// Invoke the public callback or function `name` of the current component with `args`, e.g.
+/// to simulate a click, and report the outcome through the status
+pub fn invoke_preview_callback(name: String, args: Vec<serde_json::Value>) {
+    run_in_ui_thread(move || async move {
+        match invoke_preview_callback_impl(&name, &args) {
+            Ok(slint_interpreter::Value::Void) => {
+                send_status(&format!("Invoked {name}"), Health::Ok)
+            }
+            Ok(result) => send_status(
+                &format!("Invoked {name}, it returned {}", value_to_string(&result)),
+                Health::Ok,
+            ),
+            Err(e) => send_status(&format!("Cannot invoke {name}: {e}"), Health::Error),
+        }
+    });
+}
+
+fn invoke_preview_callback_impl(
+    name: &str,
+    args: &[serde_json::Value],
+) -> Result<slint_interpreter::Value, String> {
+    let component_instance = component_instance().ok_or("No component is loaded in the preview")?;
+    let args = callback_arguments(&component_instance.definition(), name, args)?;
+    component_instance.invoke(name, &args).map_err(|e| e.to_string())
+}
+
+/// Convert the JSON `args` to the argument types of the callback or function `name`
+fn callback_arguments(
+    definition: &ComponentDefinition,
+    name: &str,
+    args: &[serde_json::Value],
+) -> Result<Vec<slint_interpreter::Value>, String> {
+    use i_slint_compiler::langtype::Type;
+
+    let normalized_name = name.replace('_', "-");
+    let arg_types = definition
+        .properties_and_callbacks()
+        .find(|(n, _)| n.replace('_', "-") == normalized_name)
+        .and_then(|(_, ty)| match ty {
+            Type::Callback { args, .. } | Type::Function { args, .. } => Some(args),
+            _ => None,
+        })
+        .ok_or("The previewed component has no such callback")?;
+    if arg_types.len() != args.len() {
+        return Err(format!("Expected {} arguments, got {}", arg_types.len(), args.len()));
+    }
+    args.iter()
+        .zip(arg_types)
+        .enumerate()
+        .map(|(index, (value, ty))| {
+            json_to_value(value, Some(ty.into()))
+                .map_err(|e| format!("Argument {}: {e}", index + 1))
+        })
+        .collect()
+}
+
+/// Convert JSON to an interpreter value. Without an expected type, the JSON type decides.
+fn json_to_value(
+    value: &serde_json::Value,
+    value_type: Option<slint_interpreter::ValueType>,
+) -> Result<slint_interpreter::Value, String> {
+    use serde_json::Value as J;
+    use slint_interpreter::{Value as V, ValueType as T};
+
+    match (value_type, value) {
+        (Some(T::Brush), J::String(s)) => i_slint_compiler::literals::parse_color_literal(s)
+            .map(|c| {
+                V::Brush(i_slint_core::Brush::SolidColor(i_slint_core::Color::from_argb_encoded(c)))
+            })
+            .ok_or_else(|| format!("'{s}' is not a color")),
+        (Some(T::Number) | None, J::Number(n)) => {
+            n.as_f64().map(V::Number).ok_or_else(|| format!("{n} is not a valid number"))
+        }
+        (Some(T::String) | None, J::String(s)) => Ok(V::String(s.as_str().into())),
+        (Some(T::Bool) | None, J::Bool(b)) => Ok(V::Bool(*b)),
+        (Some(T::Model) | None, J::Array(a)) => {
+            let values = a.iter().map(|v| json_to_value(v, None)).collect::<Result<Vec<_>, _>>()?;
+            Ok(V::Model(slint::ModelRc::new(slint::VecModel::from(values))))
+        }
+        (Some(T::Struct) | None, J::Object(o)) => Ok(V::Struct(
+            o.iter()
+                .map(|(k, v)| Ok((k.clone(), json_to_value(v, None)?)))
+                .collect::<Result<slint_interpreter::Struct, String>>()?,
+        )),
+        (Some(t), v) => Err(format!("{v} does not match the property type ({t:?})")),
+        (None, v) => Err(format!("{v} is not supported")),
+    }
+}
+
+/// Reload the current component from scratch, even if none of its files changed
+///
+/// If a load is in progress, another one is scheduled once it is done.
+pub fn force_reload() {
+    logging::preview_log!(Info, "reload requested");
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    cache.dependency.clear();
+    cache.loaded_files.clear();
+    cache.resource_dependency.clear();
+    cache.skip_compiled_cache = true;
+    // Drop any debounced reload, this one replaces it
+    cache.pending_reload += 1;
+    if !cache.ui_is_visible || cache.current.path.as_os_str().is_empty() {
+        return;
+    }
+    match cache.loading_state {
+        PreviewFutureState::Pending => (),
+        // The load about to start will pick up the current component
+        PreviewFutureState::PreLoading | PreviewFutureState::NeedsReload => return,
+        PreviewFutureState::Loading => {
+            cache.set_loading_state(PreviewFutureState::NeedsReload);
+            return;
+        }
+    }
+    let current = cache.current.clone();
+    drop(cache);
+
+    load_preview(current);
+}
+
+#[derive(Default)]
+struct DiagnosticsCursor {
+    /// File and position of each diagnostic
+    locations: Vec<(String, lsp_types::Position)>,
+    current: Option<usize>,
+}
+
+thread_local! {static DIAGNOSTICS_CURSOR: RefCell<DiagnosticsCursor> = Default::default();}
+
+// Remember the diagnostics of the last build for `show_next_diagnostic`
+fn set_diagnostics_to_step_through(diagnostics: &[slint_interpreter::Diagnostic]) {
+    // Same filter as `convert_diagnostics`: the editor can't show the others
+    let locations = diagnostics
+        .iter()
+        .filter_map(|d| {
+            let file = d.source_file().filter(|f| i_slint_compiler::pathutils::is_absolute(f))?;
+            let (line, column) = d.line_column();
+            let position = lsp_types::Position::new(
+                (line as u32).saturating_sub(1),
+                (column as u32).saturating_sub(1),
+            );
+            Some((file.to_string_lossy().to_string(), position))
+        })
+        .collect();
+    DIAGNOSTICS_CURSOR.with(|c| *c.borrow_mut() = DiagnosticsCursor { locations, current: None });
+}
+
+fn next_diagnostic_index(current: Option<usize>, count: usize, backwards: bool) -> Option<usize> {
+    if count == 0 {
+        return None;
+    }
+    Some(match (current, backwards) {
+        (None, false) => 0,
+        (None, true) => count - 1,
+        (Some(i), false) => (i + 1) % count,
+        (Some(i), true) => (i + count - 1) % count,
+    })
+}
+
+/// Ask the editor to show the next (or previous) diagnostic of the last build, wrapping
+/// around at the end of the list.
+pub fn show_next_diagnostic(backwards: bool) {
+    run_in_ui_thread(move || async move {
+        let location = DIAGNOSTICS_CURSOR.with(|cursor| {
+            let mut cursor = cursor.borrow_mut();
+            cursor.current =
+                next_diagnostic_index(cursor.current, cursor.locations.len(), backwards);
+            cursor.current.map(|i| cursor.locations[i].clone())
+        });
+        match location {
+            Some((file, position)) => {
+                ask_editor_to_show_document(file, lsp_types::Range::new(position, position))
+            }
+            None => send_status("The preview has no diagnostics", Health::Ok),
+        }
+    });
+}
+
+/// Pin the preview to the current component: Requests to preview other files are ignored
+/// until it gets unpinned again.
+pub fn set_preview_pinned(pinned: bool) {
+    CONTENT_CACHE.get_or_init(Default::default).lock().unwrap().pinned = pinned;
+}
+
+/// Switch between selecting elements with the mouse (design mode) and interacting with
+/// the previewed component
+pub fn set_design_mode(enabled: bool) {
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    if let Some(restore) = &mut cache.presentation {
+        restore.design_mode = enabled;
+        return;
+    }
+    cache.design_mode = enabled;
+    let ui_is_visible = cache.ui_is_visible;
+    drop(cache);
+
+    if ui_is_visible {
+        update_design_mode(enabled);
+    }
+}
+
+/// Show or hide the toolbar and panels of the preview without changing the configuration,
+/// e.g. to take clean screenshots. With `show_selections`, the selected elements stay
+/// outlined while the toolbar is hidden.
+pub fn set_preview_ui_visible(visible: bool, show_selections: bool) {
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    if let Some(restore) = &mut cache.presentation {
+        restore.preview_ui_visible = Some(visible);
+        restore.selections_without_ui = show_selections;
+        return;
+    }
+    cache.preview_ui_visible = Some(visible);
+    cache.selections_without_ui = show_selections;
+    let ui_is_visible = cache.ui_is_visible;
+    drop(cache);
+
+    if ui_is_visible {
+        set_show_preview_ui(visible, show_selections);
+    }
+}
+
+/// Lock the preview for demos: selecting elements, the design mode, highlights and the
+/// toolbar and panels are all off, only interacting with the component remains. Turning
+/// this off again brings back what was there before.
+pub fn set_presentation_mode(enabled: bool) {
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    if cache.presentation.is_some() == enabled {
+        return;
+    }
+    if enabled {
+        cache.presentation = Some(PresentationRestore {
+            design_mode: cache.design_mode,
+            preview_ui_visible: cache.preview_ui_visible,
+            selections_without_ui: cache.selections_without_ui,
+        });
+        cache.design_mode = false;
+        cache.preview_ui_visible = Some(false);
+        cache.selections_without_ui = false;
+    } else if let Some(restore) = cache.presentation.take() {
+        cache.design_mode = restore.design_mode;
+        cache.preview_ui_visible = restore.preview_ui_visible;
+        cache.selections_without_ui = restore.selections_without_ui;
+    }
+    let design_mode = cache.design_mode;
+    let show_preview_ui =
+        cache.preview_ui_visible.unwrap_or(!cache.config.hide_ui.unwrap_or(false));
+    let show_selections = cache.selections_without_ui;
+    let ui_is_visible = cache.ui_is_visible;
+    drop(cache);
+
+    logging::preview_log!(Info, "presentation mode: {enabled}");
+    if ui_is_visible {
+        update_design_mode(design_mode);
+        set_show_preview_ui(show_preview_ui, show_selections);
+        if enabled {
+            run_in_ui_thread(|| async {
+                clear_selection();
+                set_hovered_element(None);
+            });
+        }
+    }
+}
+
+/// Show `image` on the canvas to compare the component against, or remove the reference
+/// image with None. Relative paths are relative to the workspace root.
+pub fn set_reference_image(image: Option<ReferenceImage>) {
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let root = cache.config.workspace_root.clone();
+    cache.config.reference_image = image.map(|mut image| {
+        if let Some(root) = root.filter(|_| image.path.is_relative()) {
+            image.path = clean_path(&root.join(&image.path));
+        }
+        image
+    });
+    let view_config = cache.config.clone();
+    let ui_is_visible = cache.ui_is_visible;
+    drop(cache);
+
+    if ui_is_visible {
+        set_view_config(view_config);
+    }
+}
+
+thread_local! {
+    /// The reference image that got loaded last, so that it is not loaded again for every
+    /// change of the view
+    static REFERENCE_IMAGE: RefCell<Option<(PathBuf, slint::Image)>> = Default::default();
+}
+
+fn set_ui_reference_image(ui: &ui::PreviewUi, reference: Option<&ReferenceImage>) {
+    let Some(reference) = reference else {
+        REFERENCE_IMAGE.with(|r| r.take());
+        ui.set_has_reference_image(false);
+        return;
+    };
+    let image = REFERENCE_IMAGE.with(|r| {
+        let mut r = r.borrow_mut();
+        if let Some((_, image)) = r.as_ref().filter(|(path, _)| path == &reference.path) {
+            return Some(image.clone());
+        }
+        let image = slint::Image::load_from_path(&reference.path).ok()?;
+        *r = Some((reference.path.clone(), image.clone()));
+        Some(image)
+    });
+    let Some(image) = image else {
+        ui.set_has_reference_image(false);
+        send_status(
+            &format!("Cannot load the reference image {}", reference.path.display()),
+            Health::Warning,
+        );
+        return;
+    };
+    ui.set_reference_image(image);
+    ui.set_reference_opacity(reference.opacity.clamp(0., 1.));
+    ui.set_reference_x(reference.x);
+    ui.set_reference_y(reference.y);
+    ui.set_reference_above(reference.above);
+    ui.set_has_reference_image(true);
+}
+
+fn in_presentation_mode() -> bool {
+    CONTENT_CACHE.get_or_init(Default::default).lock().unwrap().presentation.is_some()
+}
+
+// triggered from the UI, running in UI thread
+fn design_mode_changed(enabled: bool) {
+    CONTENT_CACHE.get_or_init(Default::default).lock().unwrap().design_mode = enabled;
+    if !enabled {
+        set_hovered_element(None);
+    }
+}
+
+/// Where the sources passed to `load_preview_from_source` are stored in the cache
+const SNIPPET_DIRECTORY: &str = "<snippet>";
+
+/// Preview `source` as if it was the content of the file at `component.path`, for sources
+/// that are not saved anywhere, like scratch buffers of the editor.
+///
+/// The source is stored under a path that is not absolute, so the diagnostics of the snippet
+/// are not published to the editor: `convert_diagnostics` skips them. The preview shows them.
+pub fn load_preview_from_source(component: PreviewComponent, source: String) {
+    let path = snippet_path(&component.path);
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    cache.source_hashes.insert(path.clone(), content_hash(&source));
+    cache.source_code.insert(path.clone(), source);
+    drop(cache);
+    load_preview(PreviewComponent { path, ..component });
+}
+
+// The relative path in the snippet directory that the snippet at `virtual_path` gets stored at
+fn snippet_path(virtual_path: &Path) -> PathBuf {
+    let relative = virtual_path
+        .components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(c) => Some(c),
+            _ => None,
+        })
+        .collect::<PathBuf>();
+    let relative =
+        if relative.as_os_str().is_empty() { PathBuf::from("snippet.slint") } else { relative };
+    Path::new(SNIPPET_DIRECTORY).join(relative)
+}
+
+pub fn load_preview(preview_component: PreviewComponent) {
+    {
+        let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+        if cache.pinned
+            && !cache.current.path.as_os_str().is_empty()
+            && cache.current.path != preview_component.path
+        {
+            logging::preview_log!(
+                Debug,
+                "preview is pinned, not showing {}",
+                preview_component.path.display()
+            );
+            return;
+        }
+        cache.current = preview_component.clone();
+        if !preview_component.path.as_os_str().is_empty() {
+            cache.last_component = preview_component.clone();
+        }
+        if !cache.ui_is_visible {
+            return;
+        }
+        match cache.loading_state {
+            PreviewFutureState::Pending => (),
+            PreviewFutureState::PreLoading => return,
+            PreviewFutureState::Loading => {
+                cache.set_loading_state(PreviewFutureState::NeedsReload);
+                return;
+            }
+            PreviewFutureState::NeedsReload => return,
+        }
+        cache.set_loading_state(PreviewFutureState::PreLoading);
+    };
+
+    run_in_ui_thread(move || async move {
+        loop {
+            let (preview_component, style_override, config) = {
+                let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+                assert_eq!(cache.loading_state, PreviewFutureState::PreLoading);
+                if !cache.ui_is_visible {
+                    cache.set_loading_state(PreviewFutureState::Pending);
+                    return;
+                }
+                cache.set_loading_state(PreviewFutureState::Loading);
+                cache.dependency.clear();
+                cache.loaded_files.clear();
+                cache.resource_dependency.clear();
+                let preview_component = cache.current.clone();
+                cache.current.style.clear();
+                let style_override = cache.style_overrides.get(&preview_component.path).cloned();
+                (preview_component, style_override, cache.config.clone())
+            };
+            // A style requested by the editor wins over the one picked in the preview for this file
+            let style = if !preview_component.style.is_empty() {
+                set_current_style(preview_component.style.clone());
+                preview_component.style.clone()
+            } else if let Some(style) = style_override {
+                set_current_style(style.clone());
+                style
+            } else {
+                get_current_style()
+            };
+
+            reload_preview_impl(preview_component, style, config).await;
+
+            let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+            match cache.loading_state {
+                PreviewFutureState::Loading => {
+                    cache.set_loading_state(PreviewFutureState::Pending);
+                    return;
+                }
+                PreviewFutureState::Pending => unreachable!(),
+                PreviewFutureState::PreLoading => unreachable!(),
+                PreviewFutureState::NeedsReload => {
+                    cache.set_loading_state(PreviewFutureState::PreLoading);
+                    continue;
+                }
+            };
+        }
+    });
+}
+
+/// Everything besides the file contents that influences how a component is compiled
+#[derive(PartialEq)]
+struct CompiledCacheKey {
+    component: PreviewComponent,
+    style: String,
+    include_paths: Vec<PathBuf>,
+    library_paths: HashMap<String, PathBuf>,
+}
+
+/// A successfully compiled component, reused as long as none of its inputs changed
+struct CompiledCacheEntry {
+    key: CompiledCacheKey,
+    /// All the files the compiler loaded, with the `ContentCache::file_hash` of their contents
+    files: Vec<(PathBuf, Option<u64>)>,
+    /// The images and fonts the component uses
+    resources: Vec<PathBuf>,
+    compiled: ComponentDefinition,
+    diagnostics: Vec<slint_interpreter::Diagnostic>,
+}
+
+/// How many compiled components are kept around
+const COMPILED_CACHE_SIZE: usize = 8;
+
+thread_local! {static COMPILED_CACHE: RefCell<Vec<CompiledCacheEntry>> = Default::default();}
+
+fn content_hash(content: &str) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the cached compilation result for `key`, if none of its dependencies changed
+/// since it was compiled. Entries of changed dependencies get dropped.
+fn cached_compilation(
+    key: &CompiledCacheKey,
+) -> Option<(ComponentDefinition, Vec<slint_interpreter::Diagnostic>)> {
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    if std::mem::take(&mut cache.skip_compiled_cache) {
+        COMPILED_CACHE.with(|c| c.borrow_mut().clear());
+        return None;
+    }
+
+    COMPILED_CACHE.with(|compiled_cache| {
+        let mut compiled_cache = compiled_cache.borrow_mut();
+        compiled_cache
+            .retain(|entry| entry.files.iter().all(|(path, hash)| cache.file_hash(path) == *hash));
+
+        let index = compiled_cache.iter().position(|entry| &entry.key == key)?;
+        // Move the entry to the back, so that it is evicted last
+        let entry = compiled_cache.remove(index);
+        // The dependencies got cleared when starting to load, register them again
+        let cache = &mut *cache;
+        for (path, _) in &entry.files {
+            if !is_watch_excluded(&cache.config.watch_exclude, path) {
+                cache.dependency.insert(path.clone());
+            }
+            cache.loaded_files.insert(path.clone());
+        }
+        cache.resource_dependency.extend(entry.resources.iter().cloned());
+        let result = (entry.compiled.clone(), entry.diagnostics.clone());
+        compiled_cache.push(entry);
+        Some(result)
+    })
+}
+
+fn store_compilation(
+    key: CompiledCacheKey,
+    compiled: ComponentDefinition,
+    diagnostics: Vec<slint_interpreter::Diagnostic>,
+) {
+    let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let files =
+        cache.loaded_files.iter().map(|path| (path.clone(), cache.file_hash(path))).collect();
+    let resources = cache.resource_dependency.iter().cloned().collect();
+    drop(cache);
+
+    COMPILED_CACHE.with(|compiled_cache| {
+        let mut compiled_cache = compiled_cache.borrow_mut();
+        compiled_cache.retain(|entry| entry.key != key);
+        if compiled_cache.len() >= COMPILED_CACHE_SIZE {
+            compiled_cache.remove(0);
+        }
+        compiled_cache.push(CompiledCacheEntry { key, files, resources, compiled, diagnostics });
+    });
+}
+
+async fn compile_preview(
+    component: &PreviewComponent,
+    style: String,
+    config: PreviewConfig,
+) -> (Option<ComponentDefinition>, Vec<slint_interpreter::Diagnostic>) {
+    let mut builder = slint_interpreter::ComponentCompiler::default();
+
+    // The compiler passes every image through the mapper: use that to see which ones
+    // the component uses, and what they got mapped to.
+    #[cfg(target_arch = "wasm32")]
+    let platform_mapper = resource_url_mapper();
+    let resource_urls = Rc::new(RefCell::new(Vec::new()));
+    let cc = builder.compiler_configuration(i_slint_core::InternalToken);
+    cc.resource_url_mapper = Some(Rc::new({
+        let resource_urls = resource_urls.clone();
+        move |path: &str| {
+            #[cfg(target_arch = "wasm32")]
+            let mapped = platform_mapper.as_ref().map(|mapper| mapper(path));
+            #[cfg(not(target_arch = "wasm32"))]
+            let mapped = None::<std::future::Ready<Option<String>>>;
+
+            let path = path.to_string();
+            let resource_urls = resource_urls.clone();
+            Box::pin(async move {
+                let url = match mapped {
+                    Some(mapped) => mapped.await,
+                    None => None,
+                };
+                if !path.starts_with("builtin:") {
+                    register_resource_dependency(PathBuf::from(&path));
+                    resource_urls
+                        .borrow_mut()
+                        .push(crate::lsp_ext::ResourceUrl { path, url: url.clone() });
+                }
+                url
+            })
+        }
+    }));
+
+    if !style.is_empty() {
+        builder.set_style(style);
+    }
+    builder.set_include_paths(config.include_paths);
+    builder.set_library_paths(config.library_paths);
+
+    builder.set_file_loader(|path| {
+        report_file_loading(path);
+        let path = path.to_owned();
+        Box::pin(async move { get_file_from_cache(path).map(Result::Ok) })
+    });
+
+    // The first line and the source of the `_Preview` wrapper, if it has a body
+    let mut wrapper = None;
+    let compiled = if let Some(mut from_cache) = get_file_from_cache(component.path.clone()) {
+        if let Some(component_name) = &component.component {
+            let wrapper_source =
+                preview_wrapper_source(component_name, component.wrapper_body.as_deref());
+            if component.wrapper_body.is_some() {
+                let first_line = from_cache.matches('\n').count() + 2;
+                wrapper = Some((first_line, wrapper_source.clone()));
+            }
+            from_cache = format!("{from_cache}\n{wrapper_source}");
+        }
+        builder.build_from_source(from_cache, component.path.clone()).await
+    } else {
+        builder.build_from_path(component.path.clone()).await
+    };
+    register_font_dependencies();
+    notify_resource_urls(std::mem::take(&mut *resource_urls.borrow_mut()));
+
+    let mut diagnostics = builder.diagnostics().clone();
+    if let Some((first_line, wrapper_source)) = wrapper {
+        diagnostics =
+            move_wrapper_diagnostics(diagnostics, &component.path, first_line, wrapper_source);
+    }
+    (compiled, diagnostics)
+}
+
+/// The path the diagnostics of a custom `_Preview` wrapper are reported for
+const WRAPPER_PATH: &str = "<preview wrapper>";
+
+/// The `_Preview` component that gets appended to the previewed file to show `component_name`
+fn preview_wrapper_source(component_name: &str, body: Option<&str>) -> String {
+    match body {
+        Some(body) => {
+            format!("{PREVIEW_WRAPPER_HEAD} {component_name} {{\n{body}\n}}\n")
+        }
+        None => format!("{PREVIEW_WRAPPER_HEAD} {component_name} {{ }}\n"),
+    }
+}
+
+/// How the `_Preview` wrapper starts
+const PREVIEW_WRAPPER_HEAD: &str = "export component _Preview inherits";
+
+// Whether `compiled`, the source code the preview compiled for a file, is `source`. The previewed
+// file gets compiled with the `_Preview` wrapper appended.
+fn is_compiled_from(compiled: &str, source: &str) -> bool {
+    compiled.strip_prefix(source).map_or(false, |rest| {
+        rest.is_empty() || rest.starts_with(&format!("\n{PREVIEW_WRAPPER_HEAD} "))
+    })
+}
+
+/// The `_Preview` wrapper is appended to the previewed file, starting at `first_line`. Move
+/// the diagnostics it caused from that file to WRAPPER_PATH, so that they do not show up
+/// past the end of the file in the editor.
+fn move_wrapper_diagnostics(
+    diagnostics: Vec<slint_interpreter::Diagnostic>,
+    path: &Path,
+    first_line: usize,
+    wrapper_source: String,
+) -> Vec<slint_interpreter::Diagnostic> {
+    use i_slint_compiler::diagnostics::{BuildDiagnostics, SourceFileInner, SourceLocation, Span};
+
+    let wrapper_file: SourceFile =
+        Rc::new(SourceFileInner::new(PathBuf::from(WRAPPER_PATH), wrapper_source, None));
+    let mut result = BuildDiagnostics::default();
+    for d in diagnostics {
+        let (line, column) = d.line_column();
+        if d.source_file() != Some(path) || line < first_line {
+            result.push_compiler_error(d);
+            continue;
+        }
+        let location = SourceLocation {
+            source_file: Some(wrapper_file.clone()),
+            span: Span::new(wrapper_file.offset(line - first_line + 1, column)),
+        };
+        result.push_diagnostic_with_span(d.message().to_string(), location, d.level());
+    }
+    result.into_iter().collect()
+}
+
+/// Runs `future`, unless it does not finish within `timeout`: then it gets dropped and
+/// this returns None.
+///
+/// The timeout is a `slint::Timer`, which runs on the event loop of the platform, so this
+/// works in the browser as well. The timeout can only hit while `future` is waiting.
+async fn with_timeout<T>(
+    timeout: std::time::Duration,
+    future: impl std::future::Future<Output = T>,
+) -> Option<T> {
+    use std::future::Future;
+    use std::task::{Poll, Waker};
+
+    let timed_out = Rc::new(Cell::new(false));
+    let waker = Rc::new(RefCell::new(None::<Waker>));
+    let timer = slint::Timer::default();
+    {
+        let (timed_out, waker) = (timed_out.clone(), waker.clone());
+        timer.start(slint::TimerMode::SingleShot, timeout, move || {
+            timed_out.set(true);
+            if let Some(waker) = waker.borrow_mut().take() {
+                waker.wake();
+            }
+        });
+    }
+
+    let mut future = std::pin::pin!(future);
+    std::future::poll_fn(|cx| {
+        if let Poll::Ready(result) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(result));
+        }
+        if timed_out.get() {
+            return Poll::Ready(None);
+        }
+        *waker.borrow_mut() = Some(cx.waker().clone());
+        Poll::Pending
+    })
+    .await
+}
+
+/// Make the relative include and library paths of `config` absolute, relative to the configured
+/// workspace root or to the directory of the previewed file
+fn resolve_relative_paths(mut config: PreviewConfig, previewed_file: &Path) -> PreviewConfig {
+    use i_slint_compiler::pathutils::clean_path;
+
+    let Some(base) =
+        config.workspace_root.clone().or_else(|| previewed_file.parent().map(Path::to_path_buf))
+    else {
+        return config;
+    };
+    let resolve = |path: &mut PathBuf| {
+        if path.is_relative() {
+            let resolved = clean_path(&base.join(&*path));
+            logging::preview_log!(Info, "resolved {} to {}", path.display(), resolved.display());
+            *path = resolved;
+        }
+    };
+    config.include_paths.iter_mut().for_each(resolve);
+    config.library_paths.values_mut().for_each(resolve);
+    config.window_icon.iter_mut().for_each(resolve);
+    config
+}
+
+// The title of the native preview window when showing `component`
+fn window_title(config: &PreviewConfig, component: &str) -> String {
+    config
+        .window_title
+        .clone()
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| format!("{component} - Slint Live-Preview"))
+}
+
+/// `preview_component` the way it gets compiled: the style is passed on separately
+fn component_to_compile(preview_component: PreviewComponent) -> PreviewComponent {
+    let mut component = PreviewComponent { style: String::new(), ..preview_component };
+    if component.wrapper_body.is_some() && component.component.is_none() {
+        // The wrapper needs to know what to wrap: the component shown by default
+        component.component = get_file_from_cache(component.path.clone()).and_then(|source| {
+            crate::util::exported_component_names(&source, &component.path).pop()
+        });
+    }
+    component
+}
+
+/// Compile the previewed component once more, past the cache of compiled components, and send
+/// a report of all diagnostics of that build to the editor
+///
+/// The compiler has no more detailed mode to switch to, but unlike the published diagnostics
+/// the report covers all loaded files whatever the `diagnostics_scope`, and quotes the source
+/// at each location. The preview and its configuration stay as they are.
+pub fn recompile_verbose() {
+    run_in_ui_thread(move || async move {
+        let (preview_component, config) = {
+            let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+            (cache.current.clone(), cache.config.clone())
+        };
+        if preview_component.path.as_os_str().is_empty() {
+            send_status("Nothing is previewed that could be recompiled", Health::Warning);
+            return;
+        }
+        let style = get_current_style();
+        let config = resolve_relative_paths(config, &preview_component.path);
+        let component = component_to_compile(preview_component);
+
+        let (_, diagnostics) = compile_preview(&component, style.clone(), config).await;
+
+        let header = format!(
+            "Recompiled {} from {} with style {style:?}",
+            component.component.as_deref().unwrap_or("the last component"),
+            component.path.display()
+        );
+        send_diagnostics_report(diagnostics_report(&header, &diagnostics, get_file_from_cache));
+    });
+}
+
+/// A description of each of `diagnostics` below `header`, with the source line it points at
+/// when `source_of` knows the file
+fn diagnostics_report(
+    header: &str,
+    diagnostics: &[slint_interpreter::Diagnostic],
+    source_of: impl Fn(PathBuf) -> Option<String>,
+) -> String {
+    let count = |level| diagnostics.iter().filter(|d| d.level() == level).count();
+    let mut report = format!(
+        "{header}: {} errors, {} warnings",
+        count(DiagnosticLevel::Error),
+        count(DiagnosticLevel::Warning)
+    );
+    let mut sources = HashMap::new();
+    for d in diagnostics {
+        let path = d.source_file().map(Path::to_path_buf).unwrap_or_default();
+        let (line, column) = d.line_column();
+        let level = match d.level() {
+            DiagnosticLevel::Error => "error",
+            DiagnosticLevel::Warning => "warning",
+            _ => "note",
+        };
+        report += &format!("\n{}:{line}:{column}: {level}: {}", path.display(), d.message());
+
+        let source = sources.entry(path.clone()).or_insert_with(|| source_of(path)).as_deref();
+        if let Some(text) = source.and_then(|s| s.lines().nth(line.checked_sub(1)?)) {
+            let indent = " ".repeat(column.saturating_sub(1).min(text.chars().count()));
+            report += &format!("\n    {text}\n    {indent}^");
+        }
+    }
+    report
+}
+
+// Most be inside the thread running the slint event loop
+async fn reload_preview_impl(
+    preview_component: PreviewComponent,
+    style: String,
+    config: PreviewConfig,
+) {
+    let config = resolve_relative_paths(config, &preview_component.path);
+    let component = component_to_compile(preview_component);
+    let effective_style = style.clone();
+
+    CONTENT_CACHE.get_or_init(Default::default).lock().unwrap().compiler_config =
+        Some(effective_compiler_config(&component, &style, &config));
+    logging::preview_log!(
+        Info,
+        "loading {} from {} with style {style:?}",
+        component.component.as_deref().unwrap_or("the last component"),
+        component.path.display()
+    );
+    start_parsing();
+    // Use the platform clock, std::time::Instant is not available on all platforms (WASM)
+    let start_time = i_slint_core::animations::Instant::now();
+
+    let library_paths = config.library_paths.clone();
+    let deny_warnings = config.deny_warnings;
+    let keep_preview_on_error = config.keep_preview_on_error;
+    let key = CompiledCacheKey {
+        component: component.clone(),
+        style: style.clone(),
+        include_paths: config.include_paths.clone(),
+        library_paths: config.library_paths.clone(),
+    };
+    let (compiled, diagnostics) = if let Some(cached) = cached_compilation(&key) {
+        logging::preview_log!(Debug, "reusing the previously compiled component");
+        (Some(cached.0), cached.1)
+    } else {
+        let (compiled, diagnostics) = compile_preview(&component, style, config).await;
+        if let Some(compiled) = &compiled {
+            store_compilation(key, compiled.clone(), diagnostics.clone());
+        }
+        (compiled, diagnostics)
+    };
+
+    let known_components = get_file_from_cache(component.path.clone())
+        .map(|source| crate::util::exported_component_names(&source, &component.path))
+        .unwrap_or_default();
+
+    let elapsed = i_slint_core::animations::Instant::now().duration_since(start_time);
+    logging::preview_log!(
+        Info,
+        "compiled in {}ms with {} diagnostics",
+        elapsed.as_millis(),
+        diagnostics.len()
+    );
+    set_diagnostics_to_step_through(&diagnostics);
+    notify_diagnostics(&diagnostics);
+
+    let warnings_denied =
+        deny_warnings && diagnostics.iter().any(|d| d.level() == DiagnosticLevel::Warning);
+    let compiled = compiled.filter(|_| !warnings_denied);
+
+    if let Some(compiled) = compiled {
+        let current_component =
+            component.component.clone().unwrap_or_else(|| compiled.name().to_string());
+        set_known_components(known_components, current_component.clone());
+        let keep_viewport =
+            SHOWN_PATH.with(|shown| shown.replace(component.path.clone()) == component.path);
+        remember_good_preview(component.path.clone(), compiled.clone());
+        set_warning_locations(&diagnostics);
+        CHECK_VISIBLE_CONTENT.with(|check| check.set(true));
+        update_preview_area(compiled, keep_viewport);
+        set_window_title_and_icon(
+            window_title(&config, &current_component),
+            config.window_icon.clone(),
+        );
+        // The dependencies are collected while compiling, so they are complete only now
+        let dependencies = current_dependencies();
+        let unused_library_paths = unused_library_paths(&library_paths, &dependencies);
+        let warning = (!unused_library_paths.is_empty()).then(|| {
+            format!(
+                "Library paths not used by the preview: {}",
+                unused_library_paths
+                    .iter()
+                    .map(|name| format!("@{name}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        });
+        notify_dependencies(dependencies);
+        finish_parsing(true, elapsed, &diagnostics, warning);
+    } else {
+        set_known_components(Vec::new(), String::new());
+        set_warning_locations(&[]);
+        let reason = warnings_denied.then(|| {
+            append_diagnostics_count(
+                "Warnings are treated as errors".into(),
+                &diagnostics,
+                DiagnosticLevel::Warning,
+                "warning",
+            )
+        });
+        let message = diagnostics
+            .iter()
+            .find(|d| d.level() == DiagnosticLevel::Error)
+            .map(|d| d.message().to_string())
+            .or_else(|| reason.clone());
+        finish_parsing(false, elapsed, &diagnostics, reason);
+        let message = message.unwrap_or_else(|| "The component could not be built".into());
+        remember_preview_failure(&component.path, message.clone(), keep_preview_on_error);
+        if !keep_preview_on_error {
+            show_error_placeholder(message).await;
+        }
+    };
+    let resources = CONTENT_CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .resource_dependency
+        .iter()
+        .cloned()
+        .collect();
+    watch_resources(resources);
+    notify_style(effective_style);
+}
+
+/// This sets up the preview area to show the ComponentInstance
+///
+/// This must be run in the UI thread.
+///
+/// With `keep_viewport`, the canvas is scrolled back to where it was once the new instance
+/// is shown, instead of where the new instance ends up.
+///
+/// In the multi-viewport mode, one instance is created for each of the configured sizes,
+/// and `callback` is called for the one in the active viewport.
+pub fn set_preview_factory(
+    ui: &ui::PreviewUi,
+    compiled: ComponentDefinition,
+    keep_viewport: bool,
+    callback: Box<dyn Fn(ComponentInstance)>,
+) {
+    // Ensure that the popup is closed as it is related to the old factory
+    i_slint_core::window::WindowInner::from_pub(ui.window()).close_popup();
+
+    set_outline(ui, &compiled);
+
+    // The masked element might be gone or somewhere else now
+    let masked = ISOLATION.with(|i| matches!(*i.borrow(), Some(Isolation::Mask)));
+    if masked {
+        ISOLATION.with(|i| i.take());
+        set_ui_isolation(ui, false, None);
+    }
+
+    let callback: Rc<dyn Fn(ComponentInstance)> = Rc::from(callback);
+    let presets = CONTENT_CACHE
+        .get()
+        .map(|c| c.lock().unwrap().config.viewport_presets.clone())
+        .unwrap_or_default();
+    ui.set_has_viewport_presets(!presets.is_empty());
+    if ui.get_multi_viewport() && !presets.is_empty() {
+        ui.set_preview_area(Default::default());
+        set_viewports(ui, compiled, &presets, callback);
+        return;
+    }
+    ui.set_viewports(Default::default());
+    VIEWPORTS.with(|v| v.take());
+
+    let viewport = Cell::new(keep_viewport.then(|| (ui.get_viewport_x(), ui.get_viewport_y())));
+    let ui_weak = ui.as_weak();
+    let factory = slint::ComponentFactory::new(move |ctx: FactoryContext| {
+        let instance = compiled.create_embedded(ctx).unwrap();
+
+        if let Some(ui) = ui_weak.upgrade() {
+            make_instance_current(&ui, &instance, &*callback);
+        }
+
+        if let Some((x, y)) = viewport.take() {
+            // Only once the new instance got its size, the viewport stays where it is put
+            let ui_weak = ui_weak.clone();
+            run_in_ui_thread(move || async move {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_viewport_x(x);
+                    ui.set_viewport_y(y);
+                }
+            });
+        }
+
+        Some(instance)
+    });
+    ui.set_preview_area(factory);
+}
+
+/// The component that built last, to compare with once the code got broken
+struct LastGoodPreview {
+    path: PathBuf,
+    definition: ComponentDefinition,
+    /// Why the current code does not build, `None` while it does
+    failure: Option<String>,
+    /// The component is shown instead of the failure
+    shown: bool,
+}
+
+thread_local! {
+    // Component definitions are not `Send`, so this can't be part of the content cache
+    static LAST_GOOD_PREVIEW: RefCell<Option<LastGoodPreview>> = Default::default();
+}
+
+fn remember_good_preview(path: PathBuf, definition: ComponentDefinition) {
+    LAST_GOOD_PREVIEW.with(|last| {
+        *last.borrow_mut() = Some(LastGoodPreview { path, definition, failure: None, shown: false })
+    });
+    update_last_good_preview(false, false);
+}
+
+// The code at `path` failed to build. `shown` tells whether the last good component is still on screen
+fn remember_preview_failure(path: &Path, failure: String, shown: bool) {
+    let available = LAST_GOOD_PREVIEW.with(|last| {
+        let mut last = last.borrow_mut();
+        match last.as_mut() {
+            Some(l) if l.path == path => {
+                l.failure = Some(failure);
+                l.shown = shown;
+                true
+            }
+            _ => {
+                // Only compare with earlier versions of the same file
+                *last = None;
+                false
+            }
+        }
+    });
+    update_last_good_preview(available, available && shown);
+}
+
+/// Switch between showing the last component that built and the error of the current
+/// code, does nothing while the code builds
+pub fn toggle_last_good_preview() {
+    run_in_ui_thread(move || async move {
+        let shown = LAST_GOOD_PREVIEW.with(|last| last.borrow().as_ref().map(|l| l.shown));
+        if let Some(shown) = shown {
+            show_last_good_preview(!shown);
+        }
+    });
+}
+
+// triggered from the UI, running in UI thread
+fn show_last_good_preview(show: bool) {
+    let Some((definition, failure)) = LAST_GOOD_PREVIEW.with(|last| {
+        let mut last = last.borrow_mut();
+        let last = last.as_mut()?;
+        let failure = last.failure.clone()?;
+        last.shown = show;
+        Some((last.definition.clone(), failure))
+    }) else {
+        return;
+    };
+    update_last_good_preview(true, show);
+    if show {
+        update_preview_area(definition, true);
+    } else {
+        run_in_ui_thread(move || show_error_placeholder(failure));
+    }
+}
+
+/// Shown in place of the previewed component when it fails to build
+const ERROR_PLACEHOLDER_SOURCE: &str = r#"export component ErrorPlaceholder inherits Rectangle {
+    in property <string> message;
+    preferred-width: 360px;
+    background: #fdecea;
+    border-color: #d93025;
+    border-width: 2px;
+    VerticalLayout {
+        padding: 12px;
+        spacing: 6px;
+        Text { text: "The preview could not be updated"; font-weight: 700; color: #d93025; }
+        Text { text: root.message; wrap: word-wrap; color: #202020; }
+    }
+}"#;
+const ERROR_PLACEHOLDER_PATH: &str = "<error placeholder>";
+
+/// Replace the previewed component with a placeholder showing `message`, so that it is obvious
+/// that the preview does not reflect the current code
+async fn show_error_placeholder(message: String) {
+    let mut compiler = slint_interpreter::ComponentCompiler::default();
+    let Some(definition) = compiler
+        .build_from_source(ERROR_PLACEHOLDER_SOURCE.into(), PathBuf::from(ERROR_PLACEHOLDER_PATH))
+        .await
+    else {
+        return;
+    };
+    let message = slint::SharedString::from(message);
+    let factory = slint::ComponentFactory::new(move |ctx: FactoryContext| {
+        let instance = definition.create_embedded(ctx).ok()?;
+        instance.set_property("message", slint_interpreter::Value::String(message.clone())).ok()?;
+        Some(instance)
+    });
+    show_placeholder(factory);
+}
+
+/// Set up the preview area to show the placeholder of `factory` instead of a previewed component
+///
+/// This must be run in the UI thread.
+pub fn set_placeholder_factory(ui: &ui::PreviewUi, factory: slint::ComponentFactory) {
+    i_slint_core::window::WindowInner::from_pub(ui.window()).close_popup();
+
+    ui.set_outline(Default::default());
+    ui.set_viewports(Default::default());
+    VIEWPORTS.with(|v| v.take());
+    ui.set_preview_area(factory);
+    reset_selections(ui);
+}
+
+/// Make `instance` the one that gets inspected and selected in
+fn make_instance_current(
+    ui: &ui::PreviewUi,
+    instance: &ComponentInstance,
+    callback: &dyn Fn(ComponentInstance),
+) {
+    set_root_properties(ui, instance);
+    install_callback_log(ui, instance);
+
+    // Taken before highlighting replaces the selection
+    let selected_locations = SELECTED_LOCATIONS.with(|locations| locations.borrow().clone());
+    if let Some((path, offset)) =
+        CONTENT_CACHE.get().and_then(|c| c.lock().unwrap().highlight.clone())
+    {
+        highlight(&Some(path), offset);
+    }
+
+    callback(instance.clone_strong());
+
+    // The geometries of the elements are only known once the instance got laid out
+    let ui_weak = ui.as_weak();
+    let instance = instance.clone_strong();
+    run_in_ui_thread(move || async move {
+        if let Some(ui) = ui_weak.upgrade() {
+            let markers = warning_markers(&instance);
+            ui.set_warning_markers(Rc::new(slint::VecModel::from(markers)).into());
+        }
+        if CHECK_VISIBLE_CONTENT.with(Cell::take) && !has_visible_content(&instance) {
+            send_status("Component rendered but has no visible content", Health::Warning);
+        }
+        notify_size_of(&instance);
+        restore_selection(&instance, &selected_locations);
+        select_pending_element(&instance);
+    });
+
+    if has_highlight_query() {
+        // The selection got reset with the new instance, show the matches again
+        run_in_ui_thread(|| async { refresh_selections() });
+    }
+}
+
+/// The minimum, preferred and maximum width and height of the root element of `component_instance`
+fn component_size(
+    component_instance: &ComponentInstance,
+) -> (crate::lsp_ext::SizeConstraints, crate::lsp_ext::SizeConstraints) {
+    let constraints = |orientation| {
+        let info = component_instance.root_layout_info(orientation);
+        crate::lsp_ext::SizeConstraints { min: info.min, preferred: info.preferred, max: info.max }
+    };
+    (
+        constraints(i_slint_core::layout::Orientation::Horizontal),
+        constraints(i_slint_core::layout::Orientation::Vertical),
+    )
+}
+
+/// Tell the editor which size the previewed component asks for
+fn notify_size_of(component_instance: &ComponentInstance) {
+    let current = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap().current.clone();
+    let Ok(uri) = lsp_types::Url::from_file_path(&current.path) else {
+        return;
+    };
+    let (width, height) = component_size(component_instance);
+    logging::preview_log!(
+        Debug,
+        "the component asks for {}x{} logical pixels",
+        width.preferred,
+        height.preferred
+    );
+    notify_component_size(crate::lsp_ext::PreviewComponentSizeParams {
+        uri,
+        component: current.component,
+        width,
+        height,
+    });
+}
+
+thread_local! {
+    /// Set when a component got loaded, so that the first instance of it is checked for
+    /// visible content and not every instance made current afterwards
+    static CHECK_VISIBLE_CONTENT: Cell<bool> = Cell::new(false);
+}
+
+// Whether the component shows anything: its root has an area, and if it has children that are
+// items, one of them is visible inside of it. Only looks at the direct children to stay cheap.
+fn has_visible_content(component_instance: &ComponentInstance) -> bool {
+    let root_element = root_element(component_instance);
+    let Some(root_position) = component_instance.element_position(&root_element) else {
+        // Nothing to tell then
+        return true;
+    };
+    if root_position.size.area() < MIN_HIT_TEST_AREA {
+        return false;
+    }
+    // Elements without an item, like empty repeaters, show nothing but do not hide the root
+    let children = root_element
+        .borrow()
+        .children
+        .iter()
+        .map(self_or_embedded_component_root)
+        .map(self_or_visibility_wrapped_element)
+        .filter(|c| component_instance.element_item(c).is_some())
+        .collect::<Vec<_>>();
+    children.is_empty()
+        || children.iter().any(|c| {
+            component_instance.element_position(c).map_or(false, |position| {
+                is_hit_testable(component_instance, c, &position)
+                    && position.intersects(&root_position)
+            })
+        })
+}
+
+/// The instances shown in the multi-viewport mode
+#[derive(Default)]
+struct Viewports {
+    /// The instance of each viewport, once it got created
+    instances: Vec<Option<ComponentInstance>>,
+    ui: Option<slint::Weak<ui::PreviewUi>>,
+    /// Makes an instance the current one, see `set_preview_factory`
+    make_current: Option<Rc<dyn Fn(ComponentInstance)>>,
+}
+
+thread_local! {
+    static VIEWPORTS: RefCell<Viewports> = Default::default();
+}
+
+fn set_viewports(
+    ui: &ui::PreviewUi,
+    compiled: ComponentDefinition,
+    presets: &[crate::common::ViewportPreset],
+    callback: Rc<dyn Fn(ComponentInstance)>,
+) {
+    let active = ui.get_active_viewport().clamp(0, presets.len() as i32 - 1);
+    ui.set_active_viewport(active);
+
+    VIEWPORTS.with(|v| {
+        *v.borrow_mut() = Viewports {
+            instances: presets.iter().map(|_| None).collect(),
+            ui: Some(ui.as_weak()),
+            make_current: Some(callback.clone()),
+        }
+    });
+
+    let viewports = presets
+        .iter()
+        .enumerate()
+        .map(|(index, preset)| {
+            let compiled = compiled.clone();
+            let callback = callback.clone();
+            let ui_weak = ui.as_weak();
+            let factory = slint::ComponentFactory::new(move |ctx: FactoryContext| {
+                let instance = compiled.create_embedded(ctx).unwrap();
+                VIEWPORTS.with(|v| {
+                    if let Some(slot) = v.borrow_mut().instances.get_mut(index) {
+                        *slot = Some(instance.clone_strong());
+                    }
+                });
+                if let Some(ui) =
+                    ui_weak.upgrade().filter(|ui| ui.get_active_viewport() == index as i32)
+                {
+                    make_instance_current(&ui, &instance, &*callback);
+                }
+                Some(instance)
+            });
+            let size = |s: Option<f32>| s.filter(|s| s.is_finite() && *s > 0.).unwrap_or(0.);
+            ui::Viewport {
+                name: preset.name.clone().into(),
+                width: size(Some(preset.width)),
+                height: size(preset.height),
+                factory,
+            }
+        })
+        .collect::<Vec<_>>();
+    ui.set_viewports(Rc::new(slint::VecModel::from(viewports)).into());
+}
+
+// triggered from the UI, running in UI thread
+pub fn activate_viewport(index: i32) {
+    let (ui, instance, make_current) = VIEWPORTS.with(|v| {
+        let v = v.borrow();
+        let instance = usize::try_from(index)
+            .ok()
+            .and_then(|i| v.instances.get(i)?.as_ref().map(|i| i.clone_strong()));
+        (v.ui.as_ref().and_then(|ui| ui.upgrade()), instance, v.make_current.clone())
+    });
+    let (Some(ui), Some(instance), Some(make_current)) = (ui, instance, make_current) else {
+        return;
+    };
+    if ui.get_active_viewport() == index {
+        return;
+    }
+
+    ui.set_active_viewport(index);
+    reset_selections(&ui);
+    make_instance_current(&ui, &instance, &*make_current);
+}
+
+// triggered from the UI, running in UI thread
+pub fn multi_viewport_changed(_enabled: bool) {
+    let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    let ui_is_visible = cache.ui_is_visible;
+    let current = cache.current.clone();
+    drop(cache);
+
+    if ui_is_visible && !current.path.as_os_str().is_empty() {
+        load_preview(current);
+    }
+}
+
+thread_local! {
+    /// The file of the component shown last, to tell whether the viewport should be kept
+    static SHOWN_PATH: RefCell<PathBuf> = Default::default();
+}
+
+/// Highlight the element pointed at the offset in the path.
+/// When path is None, remove the highlight.
+pub fn highlight(path: &Option<PathBuf>, offset: u32) {
+    logging::preview_log!(Debug, "highlight {path:?} at offset {offset}");
+    let highlight = path.clone().map(|x| (x, offset));
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    if cache.presentation.is_some() {
+        return;
+    }
+
+    if cache.highlight == highlight {
+        return;
+    }
+    cache.highlight = highlight;
+
+    if cache.highlight.as_ref().map_or(true, |(path, _)| cache.dependency.contains(path)) {
+        let path = path.clone().unwrap_or_default();
+        update_highlight(path, offset);
+    }
+}
+
+/// Like `highlight`, with a line and column (both starting at 1) in the current content of
+/// the file instead of a byte offset. Positions past the end of the file are clamped to its end.
+pub fn highlight_at_line_column(path: &Path, line: u32, column: u32) {
+    let Some(source) = current_source(path) else {
+        return;
+    };
+    highlight(&Some(path.to_owned()), offset_at_line_column(source, line, column));
+}
+
+// The source of `path` as the editor has it, or as saved if the editor did not send it
+fn current_source(path: &Path) -> Option<String> {
+    let source =
+        CONTENT_CACHE.get_or_init(Default::default).lock().unwrap().source_code.get(path).cloned();
+    source.or_else(|| std::fs::read_to_string(path).ok())
+}
+
+/// Highlight the element at the cursor of the editor, once it stopped moving for a moment
+pub fn cursor_moved(path: PathBuf, line: u32, column: u32) {
+    let mut cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+    if !cache.config.follow_cursor || !cache.ui_is_visible {
+        return;
+    }
+    cache.pending_cursor += 1;
+    let pending_cursor = cache.pending_cursor;
+    drop(cache);
+
+    run_in_ui_thread(move || async move {
+        slint::Timer::single_shot(FOLLOW_CURSOR_DEBOUNCE, move || {
+            let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+            if cache.pending_cursor != pending_cursor {
+                return;
+            }
+            drop(cache);
+            highlight_at_line_column(&path, line, column);
+        });
+    });
+}
+
+fn offset_at_line_column(source: String, line: u32, column: u32) -> u32 {
+    let len = source.len();
+    let source_file =
+        i_slint_compiler::diagnostics::SourceFileInner::new(PathBuf::new(), source, None);
+    source_file.offset(line as usize, column as usize).min(len) as u32
+}
+
+pub fn show_document_request_from_element_callback(
+    file: &str,
+    range: lsp_types::Range,
+) -> Option<lsp_types::ShowDocumentParams> {
+    use lsp_types::{ShowDocumentParams, Url};
+
+    if file.is_empty() || range.start.character == 0 || range.end.character == 0 {
+        return None;
+    }
+
+    Url::from_file_path(file).ok().map(|uri| ShowDocumentParams {
+        uri,
+        external: Some(false),
+        take_focus: Some(true),
+        selection: Some(range),
+    })
+}
+
+pub fn convert_diagnostics(
+    diagnostics: &[slint_interpreter::Diagnostic],
+) -> HashMap<lsp_types::Url, Vec<lsp_types::Diagnostic>> {
+    let mut result: HashMap<lsp_types::Url, Vec<lsp_types::Diagnostic>> = Default::default();
+    for d in diagnostics {
+        if d.source_file().map_or(true, |f| !i_slint_compiler::pathutils::is_absolute(f)) {
+            continue;
+        }
+        let uri = lsp_types::Url::from_file_path(d.source_file().unwrap())
+            .ok()
+            .unwrap_or_else(|| lsp_types::Url::parse("file:/unknown").unwrap());
+        result.entry(uri).or_default().push(crate::util::to_lsp_diag(d));
+    }
+    result
+}
+
+thread_local! {
+    /// The diagnostics that were last published for each URL
+    static PUBLISHED_DIAGNOSTICS: RefCell<HashMap<lsp_types::Url, Vec<lsp_types::Diagnostic>>> =
+        Default::default();
+}
+
+/// Like `convert_diagnostics`, but drops the diagnostics of files that are out of the
+/// configured diagnostics scope, as well as those that did not change since they were
+/// published last. Sending them again would make the editor flicker.
+///
+/// Files that had diagnostics published before but have none now get an empty list,
+/// so that the editor does not keep showing stale diagnostics for them.
+pub fn diagnostics_to_publish(
+    diagnostics: &[slint_interpreter::Diagnostic],
+) -> HashMap<lsp_types::Url, Vec<lsp_types::Diagnostic>> {
+    let mut result = convert_diagnostics(diagnostics);
+    if let Some(files) = files_in_diagnostics_scope() {
+        let urls = files
+            .iter()
+            .filter_map(|f| lsp_types::Url::from_file_path(f).ok())
+            .collect::<HashSet<_>>();
+        result.retain(|url, _| urls.contains(url));
+    }
+    PUBLISHED_DIAGNOSTICS.with(|published| {
+        let mut published = published.borrow_mut();
+        let stale =
+            published.keys().filter(|url| !result.contains_key(*url)).cloned().collect::<Vec<_>>();
+        result.retain(|url, diagnostics| {
+            !published.get(url).map_or(false, |old| same_diagnostics(old, diagnostics))
+        });
+        published.extend(result.iter().map(|(url, d)| (url.clone(), d.clone())));
+        for url in stale {
+            published.remove(&url);
+            result.insert(url, Vec::new());
+        }
+    });
+    result
+}
+
+fn same_diagnostics(a: &[lsp_types::Diagnostic], b: &[lsp_types::Diagnostic]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| a.range == b.range && a.severity == b.severity && a.message == b.message)
+}
+
+// The files to publish diagnostics for, or None if that is all of them
+fn files_in_diagnostics_scope() -> Option<HashSet<PathBuf>> {
+    let cache = CONTENT_CACHE.get()?.lock().unwrap();
+    let current = cache.current.path.clone();
+    let mut files = match cache.config.diagnostics_scope {
+        DiagnosticsScope::All => return None,
+        DiagnosticsScope::PreviewedFile => HashSet::new(),
+        DiagnosticsScope::DirectImports => cache
+            .source_code
+            .get(&current)
+            .map(|source| direct_imports(source, &current, &cache.dependency))
+            .unwrap_or_default(),
+    };
+    files.insert(current);
+    Some(files)
+}
+
+/// The files among `dependencies` that the document at `path` imports directly
+fn direct_imports(
+    source_code: &str,
+    path: &Path,
+    dependencies: &HashSet<PathBuf>,
+) -> HashSet<PathBuf> {
+    use i_slint_compiler::parser::{syntax_nodes, SyntaxKind};
+
+    let mut diag = i_slint_compiler::diagnostics::BuildDiagnostics::default();
+    let doc: syntax_nodes::Document =
+        i_slint_compiler::parser::parse(source_code.to_string(), Some(path), None, &mut diag)
+            .into();
+
+    let imports = doc
+        .ImportSpecifier()
+        .filter_map(|i| i.child_token(SyntaxKind::StringLiteral))
+        .chain(
+            doc.ExportsList()
+                .flat_map(|e| e.ExportModule())
+                .filter_map(|e| e.child_token(SyntaxKind::StringLiteral)),
+        )
+        .map(|t| t.text().trim_matches('"').to_string())
+        .filter(|i| !i.is_empty());
+
+    let mut result = HashSet::new();
+    for import in imports {
+        let relative = path
+            .parent()
+            .and_then(|dir| i_slint_compiler::pathutils::join(dir, Path::new(&import)))
+            .filter(|p| dependencies.contains(p));
+        match relative {
+            Some(p) => {
+                result.insert(p);
+            }
+            // Resolved through the include or library paths
+            None => result.extend(
+                dependencies
+                    .iter()
+                    .filter(|d| d.ends_with(import.trim_start_matches('@')))
+                    .cloned(),
+            ),
+        }
+    }
+    result
+}
+
+pub fn notify_lsp_diagnostics(
+    sender: &crate::ServerNotifier,
+    uri: lsp_types::Url,
+    diagnostics: Vec<lsp_types::Diagnostic>,
+) -> Option<()> {
+    sender
+        .send_notification(
+            "textDocument/publishDiagnostics".into(),
+            lsp_types::PublishDiagnosticsParams { uri, diagnostics, version: None },
+        )
+        .ok()
+}
+
+pub fn send_status_notification(sender: &crate::ServerNotifier, message: &str, health: Health) {
+    sender
+        .send_notification(
+            crate::lsp_ext::ServerStatusNotification::METHOD.into(),
+            crate::lsp_ext::ServerStatusParams {
+                health,
+                quiescent: false,
+                message: Some(message.into()),
+            },
+        )
+        .unwrap_or_else(|e| eprintln!("Error sending notification: {:?}", e));
+}
+
+pub fn send_log_message_notification(sender: &crate::ServerNotifier, message: &str) {
+    sender
+        .send_notification(
+            lsp_types::notification::LogMessage::METHOD.into(),
+            lsp_types::LogMessageParams {
+                typ: lsp_types::MessageType::INFO,
+                message: message.into(),
+            },
+        )
+        .unwrap_or_else(|e| eprintln!("Error sending notification: {:?}", e));
+}
+
+pub fn send_element_selected_notification(
+    sender: &crate::ServerNotifier,
+    file: &str,
+    range: lsp_types::Range,
+) {
+    let Ok(uri) = lsp_types::Url::from_file_path(file) else {
+        return;
+    };
+    sender
+        .send_notification(
+            crate::lsp_ext::SelectElementNotification::METHOD.into(),
+            crate::lsp_ext::SelectElementParams { uri, range },
+        )
+        .unwrap_or_else(|e| eprintln!("Error sending notification: {:?}", e));
+}
+
+pub fn send_dependencies_notification(sender: &crate::ServerNotifier, files: &[PathBuf]) {
+    let uris = files.iter().filter_map(|f| lsp_types::Url::from_file_path(f).ok()).collect();
+    sender
+        .send_notification(
+            crate::lsp_ext::PreviewDependenciesNotification::METHOD.into(),
+            crate::lsp_ext::PreviewDependenciesParams { uris },
+        )
+        .unwrap_or_else(|e| eprintln!("Error sending notification: {:?}", e));
+}
+
+pub fn send_loading_notification(sender: &crate::ServerNotifier, loading: bool) {
+    sender
+        .send_notification(
+            crate::lsp_ext::PreviewLoadingNotification::METHOD.into(),
+            crate::lsp_ext::PreviewLoadingParams { loading },
+        )
+        .unwrap_or_else(|e| eprintln!("Error sending notification: {:?}", e));
+}
+
+pub fn send_component_size_notification(
+    sender: &crate::ServerNotifier,
+    size: crate::lsp_ext::PreviewComponentSizeParams,
+) {
+    sender
+        .send_notification(crate::lsp_ext::PreviewComponentSizeNotification::METHOD.into(), size)
+        .unwrap_or_else(|e| eprintln!("Error sending notification: {:?}", e));
+}
+
+pub fn send_resource_urls_notification(
+    sender: &crate::ServerNotifier,
+    resources: Vec<crate::lsp_ext::ResourceUrl>,
+) {
+    sender
+        .send_notification(
+            crate::lsp_ext::PreviewResourceUrlsNotification::METHOD.into(),
+            crate::lsp_ext::PreviewResourceUrlsParams { resources },
+        )
+        .unwrap_or_else(|e| eprintln!("Error sending notification: {:?}", e));
+}
+
+pub fn send_style_notification(
+    sender: &crate::ServerNotifier,
+    style: String,
+    available_styles: Vec<String>,
+) {
+    sender
+        .send_notification(
+            crate::lsp_ext::PreviewStyleNotification::METHOD.into(),
+            crate::lsp_ext::PreviewStyleParams { style, available_styles },
+        )
+        .unwrap_or_else(|e| eprintln!("Error sending notification: {:?}", e));
+}
+
+pub fn reset_selections(ui: &ui::PreviewUi) {
+    let model = Rc::new(slint::VecModel::from(Vec::new()));
+    ui.set_selections(slint::ModelRc::from(model));
+    ui.set_warning_markers(Default::default());
+    ui.set_selected_element_properties(Default::default());
+    ui.set_has_selected_element(false);
+    ui.set_has_hovered_element(false);
+    HOVERED_ELEMENT.with(|h| h.take());
+}
+
+fn set_selected_element_info(
+    ui: &ui::PreviewUi,
+    component_instance: Option<&ComponentInstance>,
+    element_position: Option<&(ElementRc, LogicalRect)>,
+) {
+    let Some((e, position)) = element_position else {
+        ui.set_selected_element_properties(Default::default());
+        ui.set_has_selected_element(false);
+        return;
+    };
+
+    let properties = component_instance.map(|ci| element_properties(ci, e)).unwrap_or_default();
+    ui.set_selected_element_properties(property_info_model(properties));
+
+    ui.set_selected_element_info(ui::SelectedElementInfo {
+        x: position.origin.x,
+        y: position.origin.y,
+        width: position.size.width,
+        height: position.size.height,
+        managed_by_layout: component_instance
+            .and_then(|ci| find_parent_element(&root_element(ci), e))
+            .map_or(false, |parent| parent.borrow().layout.is_some()),
+    });
+    ui.set_has_selected_element(true);
+}
+
+fn primary_border_color(
+    colors: &crate::common::SelectionColors,
+    element: &ElementRc,
+) -> i_slint_core::Color {
+    i_slint_core::Color::from_argb_encoded(if element.borrow().layout.is_some() {
+        colors.primary_layout.unwrap_or(0xffff0000)
+    } else {
+        colors.primary.unwrap_or(0xff0000ff)
+    })
+}
+
+/// Scroll the preview so that `area` becomes visible, unless it already is
+pub fn reveal_area(ui: Option<&ui::PreviewUi>, area: LogicalRect) {
+    let Some(ui) = ui else {
+        return;
+    };
+    // The serial makes sure the UI reacts even if the same area is revealed again
+    let serial = ui.get_reveal_request().serial.wrapping_add(1).max(1);
+    ui.set_reveal_request(ui::RevealRequest {
+        x: area.origin.x,
+        y: area.origin.y,
+        width: area.size.width,
+        height: area.size.height,
+        serial,
+    });
+}
+
+/// Show the (lighter) hover overlay for the element under the mouse, or hide it
+pub fn set_hover(ui: Option<&ui::PreviewUi>, hovered: Option<&(ElementRc, LogicalRect)>) {
+    let Some(ui) = ui else {
+        return;
+    };
+    let Some((e, position)) = hovered else {
+        ui.set_has_hovered_element(false);
+        return;
+    };
+
+    let colors =
+        CONTENT_CACHE.get().map(|c| c.lock().unwrap().config.selection_colors).unwrap_or_default();
+    let border_color = primary_border_color(&colors, e);
+
+    ui.set_hovered_selection(ui::Selection {
+        width: position.size.width,
+        height: position.size.height,
+        x: position.origin.x,
+        y: position.origin.y,
+        border_color: border_color.transparentize(0.5),
+    });
+    ui.set_hovered_geometry(geometry_label(position).into());
+    ui.set_has_hovered_element(true);
+}
+
+/// Describes the geometry of an element, like `120×40 at 10, 20`
+fn geometry_label(rect: &LogicalRect) -> String {
+    let n = |v: f32| (v * 100.).round() / 100.;
+    format!(
+        "{}×{} at {}, {}",
+        n(rect.size.width),
+        n(rect.size.height),
+        n(rect.origin.x),
+        n(rect.origin.y)
+    )
+}
+
+pub fn set_selections(
+    ui: Option<&ui::PreviewUi>,
+    component_instance: Option<&ComponentInstance>,
+    element_positions: &[(ElementRc, LogicalRect)],
+    positions: ComponentPositions,
+) {
+    let Some(ui) = ui else {
+        return;
+    };
+
+    let colors =
+        CONTENT_CACHE.get().map(|c| c.lock().unwrap().config.selection_colors).unwrap_or_default();
+
+    let values = {
+        let mut tmp = Vec::with_capacity(positions.geometries.len() + element_positions.len());
+
+        for (e, primary_position) in element_positions {
+            let border_color = primary_border_color(&colors, e);
+
+            tmp.push(ui::Selection {
+                width: primary_position.size.width,
+                height: primary_position.size.height,
                 x: primary_position.origin.x,
                 y: primary_position.origin.y,
                 border_color,
             });
         }
-        let secondary_border_color = match positions.kind {
-            Some(ComponentKind::Layout) => i_slint_core::Color::from_argb_encoded(0x80ff0000),
-            _ => i_slint_core::Color::from_argb_encoded(0x800000ff),
-        };
+        let secondary_border_color = i_slint_core::Color::from_argb_encoded(match positions.kind {
+            Some(ComponentKind::Layout) => colors.secondary_layout.unwrap_or(0x80ff0000),
+            _ => colors.secondary.unwrap_or(0x800000ff),
+        });
 
         tmp.extend(positions.geometries.iter().map(|geometry| ui::Selection {
             width: geometry.size.width,
@@ -564,4 +4688,1076 @@ pub fn set_selections(
     };
     let model = Rc::new(slint::VecModel::from(values));
     ui.set_selections(slint::ModelRc::from(model));
+    set_selected_element_info(ui, component_instance, element_positions.last());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance_from_source(source: &str) -> ComponentInstance {
+        i_slint_backend_testing::init();
+
+        let mut compiler = slint_interpreter::ComponentCompiler::default();
+        let definition = spin_on::spin_on(
+            compiler.build_from_source(source.to_string(), PathBuf::from("/test.slint")),
+        );
+        definition.expect("test source compiles").create().unwrap()
+    }
+
+    #[test]
+    fn test_snippet_path() {
+        assert_eq!(snippet_path(Path::new("scratch.slint")), Path::new("<snippet>/scratch.slint"));
+        assert_eq!(
+            snippet_path(Path::new("/tmp/../a/b.slint")),
+            Path::new("<snippet>/tmp/a/b.slint")
+        );
+        assert_eq!(snippet_path(Path::new("")), Path::new("<snippet>/snippet.slint"));
+        for path in ["scratch.slint", "/tmp/a.slint", "untitled:Untitled-1"] {
+            assert!(!i_slint_compiler::pathutils::is_absolute(&snippet_path(Path::new(path))));
+        }
+    }
+
+    #[test]
+    fn test_component_size() {
+        let component_instance = instance_from_source(
+            r#"export component Test {
+                min-width: 100px;
+                preferred-width: 320px;
+                preferred-height: 240px;
+                max-height: 300px;
+            }"#,
+        );
+        let (width, height) = component_size(&component_instance);
+        assert_eq!((width.min, width.preferred), (100., 320.));
+        assert_eq!((height.preferred, height.max), (240., 300.));
+
+        let component_instance = instance_from_source(
+            r#"export component Test {
+                VerticalLayout {
+                    Rectangle { min-width: 50px; height: 20px; }
+                    Rectangle { height: 30px; }
+                }
+            }"#,
+        );
+        let (width, height) = component_size(&component_instance);
+        assert_eq!(width.min, 50.);
+        assert_eq!(height.preferred, 50.);
+    }
+
+    #[test]
+    fn test_diagnostics_report() {
+        let source = "export component Test {\n    width: 10px;\n    foo: 1;\n}\n";
+        let path = PathBuf::from("/test.slint");
+
+        let mut compiler = slint_interpreter::ComponentCompiler::default();
+        spin_on::spin_on(compiler.build_from_source(source.to_string(), path.clone()));
+        let report = diagnostics_report("Recompiled Test", compiler.diagnostics(), |p| {
+            (p == path).then(|| source.to_string())
+        });
+
+        let mut lines = report.lines();
+        assert_eq!(lines.next(), Some("Recompiled Test: 1 errors, 0 warnings"));
+        assert!(lines.next().unwrap().starts_with("/test.slint:3:5: error: "));
+        assert_eq!(lines.next(), Some("        foo: 1;"));
+        assert_eq!(lines.next(), Some("        ^"));
+        assert_eq!(lines.next(), None);
+
+        let report = diagnostics_report("Recompiled Test", compiler.diagnostics(), |_| None);
+        assert_eq!(report.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_wrapper_diagnostics() {
+        let source = "export component Button { in property <string> text; }\n";
+        let wrapper = preview_wrapper_source("Button", Some("text: \"Hi\";\nenabled: true;"));
+        let first_line = source.matches('\n').count() + 2;
+        let path = PathBuf::from("/test.slint");
+
+        let mut compiler = slint_interpreter::ComponentCompiler::default();
+        spin_on::spin_on(compiler.build_from_source(format!("{source}\n{wrapper}"), path.clone()));
+        let diagnostics =
+            move_wrapper_diagnostics(compiler.diagnostics().clone(), &path, first_line, wrapper);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].source_file(), Some(Path::new(WRAPPER_PATH)));
+        assert_eq!(diagnostics[0].line_column().0, 3);
+    }
+
+    #[test]
+    fn test_selectable_children_and_parent() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    Rectangle {
+        Rectangle { }
+    }
+    Rectangle { }
+}"#,
+        );
+        let root = root_element(&component_instance);
+        let children = selectable_children(&component_instance, &root);
+        assert_eq!(children.len(), 2);
+
+        let grand_children = selectable_children(&component_instance, &children[0].0);
+        assert_eq!(grand_children.len(), 1);
+        let parent = selectable_parent(&root, &grand_children[0].0).unwrap();
+        assert!(Rc::ptr_eq(&parent, &children[0].0));
+        assert!(selectable_children(&component_instance, &children[1].0).is_empty());
+    }
+
+    #[test]
+    fn test_root_element_with_several_children() {
+        let component_instance = instance_from_source(
+            r#"export component Test {
+    width: 100px;
+    height: 100px;
+    first := Rectangle { x: 0px; width: 50px; }
+    second := Rectangle { x: 50px; width: 50px; }
+}"#,
+        );
+        let root = root_element(&component_instance);
+        assert!(!is_injected_window(&root));
+        assert!(is_injected_window(&component_instance.definition().root_component().root_element));
+
+        let children =
+            root.borrow().children.iter().map(|c| c.borrow().id.clone()).collect::<Vec<_>>();
+        assert_eq!(children.len(), 2);
+        assert!(children[0].starts_with("first"));
+        assert!(children[1].starts_with("second"));
+
+        let (element, _) = element_at(75.0, 10.0, &component_instance, &root).unwrap();
+        assert!(element.borrow().id.starts_with("second"));
+    }
+
+    #[test]
+    fn test_elements_in_range() {
+        let source = r#"export component Test inherits Window {
+    a := Rectangle { }
+    b := Rectangle { }
+    c := Rectangle { width: 5px; }
+}"#;
+        let component_instance = instance_from_source(source);
+        let tree = element_tree(&root_element(&component_instance));
+        let ids_in_range = |start: &str, end: &str| {
+            let start = source.find(start).unwrap() as u32;
+            let end = source.find(end).unwrap() as u32;
+            let mut elements = Vec::new();
+            elements_in_range(
+                &tree,
+                Path::new("/test.slint"),
+                TextRange::new(start.into(), end.into()),
+                &mut elements,
+            );
+            let mut ids = elements
+                .iter()
+                .map(|e| element_id(e).unwrap_or_else(|| "root".into()))
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(ids_in_range("a :=", "b :="), vec!["a".to_string()]);
+        assert_eq!(ids_in_range("Rectangle { }", "c :="), vec!["a".to_string(), "b".to_string()]);
+        // A cursor highlights the element it is in
+        assert_eq!(ids_in_range("5px", "5px"), vec!["c".to_string()]);
+        // Without any child in the range, the parent is highlighted
+        assert_eq!(ids_in_range("b :=", "b :="), vec!["root".to_string()]);
+        assert!(ids_in_range("a :=", "b :=").iter().all(|id| id != "root"));
+    }
+
+    #[test]
+    fn test_isolated_component() {
+        let component_instance = instance_from_source(
+            r#"component Card inherits Rectangle { }
+export component Test inherits Window {
+    card := Card { }
+    plain := Rectangle { }
+}"#,
+        );
+        let children = root_element(&component_instance).borrow().children.clone();
+        assert_eq!(children.len(), 2);
+
+        let component = isolated_component(&children[0], "fluent").unwrap();
+        assert_eq!(component.path, PathBuf::from("/test.slint"));
+        assert_eq!(component.component.as_deref(), Some("Card"));
+        assert_eq!(component.style, "fluent");
+
+        assert!(isolated_component(&children[1], "fluent").is_none());
+    }
+
+    #[test]
+    fn test_error_placeholder() {
+        let component_instance = instance_from_source(ERROR_PLACEHOLDER_SOURCE);
+        assert!(component_instance
+            .set_property("message", slint_interpreter::Value::String("oops".into()))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_resolve_relative_paths() {
+        let config = PreviewConfig {
+            include_paths: vec![PathBuf::from("ui/../lib"), PathBuf::from("/abs/include")],
+            library_paths: HashMap::from([("mylib".to_string(), PathBuf::from("vendor/mylib"))]),
+            window_icon: Some(PathBuf::from("icon.png")),
+            ..Default::default()
+        };
+
+        let resolved = resolve_relative_paths(config.clone(), Path::new("/project/ui/main.slint"));
+        assert_eq!(resolved.window_icon, Some(PathBuf::from("/project/ui/icon.png")));
+        assert_eq!(
+            resolved.include_paths,
+            vec![PathBuf::from("/project/ui/lib"), PathBuf::from("/abs/include")]
+        );
+        assert_eq!(resolved.library_paths["mylib"], PathBuf::from("/project/ui/vendor/mylib"));
+
+        let config = PreviewConfig { workspace_root: Some("/project".into()), ..config };
+        let resolved = resolve_relative_paths(config, Path::new("/project/ui/main.slint"));
+        assert_eq!(resolved.include_paths[0], PathBuf::from("/project/lib"));
+        assert_eq!(resolved.library_paths["mylib"], PathBuf::from("/project/vendor/mylib"));
+    }
+
+    #[test]
+    fn test_window_title() {
+        let config = PreviewConfig::default();
+        assert_eq!(window_title(&config, "Main"), "Main - Slint Live-Preview");
+        let config = PreviewConfig { window_title: Some(String::new()), ..config };
+        assert_eq!(window_title(&config, "Main"), "Main - Slint Live-Preview");
+        let config = PreviewConfig { window_title: Some("Settings page".into()), ..config };
+        assert_eq!(window_title(&config, "Main"), "Settings page");
+    }
+
+    #[test]
+    fn test_effective_compiler_config() {
+        let component = PreviewComponent {
+            path: PathBuf::from("/project/main.slint"),
+            component: Some("Main".into()),
+            ..Default::default()
+        };
+        let config = PreviewConfig {
+            style: "material".into(),
+            include_paths: vec![PathBuf::from("/project/include")],
+            deny_warnings: true,
+            ..Default::default()
+        };
+        let effective = effective_compiler_config(&component, "cupertino", &config);
+        assert_eq!(effective.path, component.path);
+        assert_eq!(effective.component.as_deref(), Some("Main"));
+        // The style picked by `load_preview` wins over the configured one
+        assert_eq!(effective.style, "cupertino");
+        assert_eq!(effective.include_paths, config.include_paths);
+        assert!(effective.deny_warnings);
+    }
+
+    #[test]
+    fn test_removed_import() {
+        let lib_path = PathBuf::from("/test_removed_import/lib.slint");
+        let main_path = PathBuf::from("/test_removed_import/main.slint");
+        set_contents(&lib_path, "export component Lib inherits Rectangle {}".into());
+        set_contents(
+            &main_path,
+            "import { Lib } from \"lib.slint\";\nexport component Main { Lib {} }".into(),
+        );
+
+        let build = || {
+            let mut compiler = slint_interpreter::ComponentCompiler::default();
+            compiler.set_file_loader(|path| {
+                let path = path.to_owned();
+                Box::pin(async move { get_file_from_cache(path).map(Result::Ok) })
+            });
+            let source = get_file_from_cache(main_path.clone()).unwrap();
+            let compiled = spin_on::spin_on(compiler.build_from_source(source, main_path.clone()));
+            (compiled, compiler.diagnostics().clone())
+        };
+
+        let (compiled, diagnostics) = build();
+        assert!(compiled.is_some(), "{diagnostics:?}");
+
+        remove_contents(&lib_path);
+        let (compiled, diagnostics) = build();
+        assert!(compiled.is_none());
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.level() == DiagnosticLevel::Error && d.message().contains("lib.slint")));
+    }
+
+    #[test]
+    fn test_set_contents_hashes() {
+        let path = PathBuf::from("/test_set_contents_hashes.slint");
+        set_contents(&path, "export component A {}".into());
+        set_contents(&path, "export component A {}".into());
+        set_contents(&path, "export component B {}".into());
+
+        let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+        assert_eq!(cache.source_code[&path], "export component B {}");
+        assert_eq!(cache.source_hashes[&path], content_hash("export component B {}"));
+        drop(cache);
+
+        remove_contents(&path);
+        let cache = CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+        assert!(!cache.source_code.contains_key(&path));
+        assert!(!cache.source_hashes.contains_key(&path));
+    }
+
+    #[test]
+    fn test_file_hash() {
+        let path = std::env::temp_dir()
+            .join(format!("slint-lsp-test-file-hash-{}.slint", std::process::id()));
+        std::fs::write(&path, "export component A {}").unwrap();
+        let mut cache = ContentCache::default();
+        let hash = cache.file_hash(&path);
+        assert_eq!(hash, Some(content_hash("export component A {}")));
+
+        // Files loaded from disk are hashed too, so that changes to them are noticed
+        std::fs::write(&path, "export component B {}").unwrap();
+        assert_eq!(cache.file_hash(&path), Some(content_hash("export component B {}")));
+
+        // The contents of the cache are what the compiler uses
+        cache.source_hashes.insert(path.clone(), hash.unwrap());
+        assert_eq!(cache.file_hash(&path), hash);
+
+        cache.source_hashes.clear();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(cache.file_hash(&path), None);
+    }
+
+    #[test]
+    fn test_cached_files() {
+        let used = PathBuf::from("/test_cached_files/used.slint");
+        let unused = PathBuf::from("/test_cached_files/unused.slint");
+        set_contents(&used, "export component Used {}".into());
+        set_contents(&unused, "export component Unused {}".into());
+        get_file_from_cache(used.clone());
+
+        let files = cached_files();
+        assert!(files.contains(&(used, true)));
+        assert!(files.contains(&(unused, false)));
+    }
+
+    #[test]
+    fn test_presentation_mode() {
+        set_design_mode(true);
+        set_presentation_mode(true);
+        assert!(in_presentation_mode());
+        assert!(!CONTENT_CACHE.get().unwrap().lock().unwrap().design_mode);
+
+        // Changes during the presentation are applied afterwards
+        set_preview_ui_visible(false, true);
+        set_presentation_mode(false);
+        assert!(!in_presentation_mode());
+        let cache = CONTENT_CACHE.get().unwrap().lock().unwrap();
+        assert!(cache.design_mode);
+        assert_eq!(cache.preview_ui_visible, Some(false));
+        assert!(cache.selections_without_ui);
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("/a/*.slint", "/a/b.slint"));
+        assert!(!glob_matches("/a/*.slint", "/a/b/c.slint"));
+        assert!(glob_matches("/a/**/*.slint", "/a/b/c/d.slint"));
+        assert!(glob_matches("/a/**/*.slint", "/a/d.slint"));
+        assert!(glob_matches("/a/?.slint", "/a/b.slint"));
+        assert!(!glob_matches("/a/?.slint", "/a/bc.slint"));
+        assert!(glob_matches("**", "/anything/at/all"));
+        assert!(!glob_matches("/a/b", "/a/bc"));
+    }
+
+    #[test]
+    fn test_offset_at_line_column() {
+        let source = || "ab\ncd\n".to_string();
+        assert_eq!(offset_at_line_column(source(), 1, 1), 0);
+        assert_eq!(offset_at_line_column(source(), 2, 1), 3);
+        assert_eq!(offset_at_line_column(source(), 2, 2), 4);
+        // Clamped to the end of the document
+        assert_eq!(offset_at_line_column(source(), 9, 1), 6);
+        assert_eq!(offset_at_line_column(source(), 1, 10), 6);
+    }
+
+    #[test]
+    fn test_is_watch_excluded() {
+        let patterns = vec!["vendor/**".to_string(), "/project/generated/*.slint".to_string()];
+        assert!(is_watch_excluded(&patterns, Path::new("/project/vendor/lib/button.slint")));
+        assert!(is_watch_excluded(&patterns, Path::new("/project/generated/ui.slint")));
+        assert!(!is_watch_excluded(&patterns, Path::new("/project/generated/sub/ui.slint")));
+        assert!(!is_watch_excluded(&patterns, Path::new("/project/ui/main.slint")));
+        assert!(!is_watch_excluded(&[], Path::new("/project/vendor/lib/button.slint")));
+    }
+
+    #[test]
+    fn test_element_tree() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    title := Rectangle { background: red; }
+    Rectangle {
+        x: 10px;
+        inner := Rectangle { background: blue; }
+    }
+}"#,
+        );
+        let tree = element_tree(&root_element(&component_instance));
+
+        assert_eq!(tree.children.len(), 2);
+        assert!(tree.children[0].label.starts_with("title := "));
+        assert!(tree.children[0].children.is_empty());
+        assert_eq!(tree.children[1].children.len(), 1);
+        assert!(tree.children[1].children[0].label.starts_with("inner := "));
+        assert_eq!(tree.children[1].children[0].range.start.line, 4);
+
+        let mut flat = Vec::new();
+        flatten_element_tree(&tree, 0, &mut flat);
+        assert_eq!(flat.iter().map(|(depth, _)| *depth).collect::<Vec<_>>(), vec![0, 1, 1, 2]);
+    }
+
+    #[test]
+    fn test_unused_library_paths() {
+        let library_paths = HashMap::from([
+            ("used".to_string(), PathBuf::from("/libs/used")),
+            ("file".to_string(), PathBuf::from("/libs/file.slint")),
+            ("typo".to_string(), PathBuf::from("/lisb/other")),
+        ]);
+        let dependencies = vec![
+            PathBuf::from("/project/main.slint"),
+            PathBuf::from("/libs/used/widgets/button.slint"),
+            PathBuf::from("/libs/file.slint"),
+        ];
+        assert_eq!(unused_library_paths(&library_paths, &dependencies), vec!["typo".to_string()]);
+        assert!(unused_library_paths(&HashMap::new(), &dependencies).is_empty());
+    }
+
+    #[test]
+    fn test_json_to_value() {
+        use slint_interpreter::{Value, ValueType};
+
+        assert_eq!(
+            json_to_value(&serde_json::json!(42), Some(ValueType::Number)),
+            Ok(Value::Number(42.))
+        );
+        assert_eq!(
+            json_to_value(&serde_json::json!("hello"), Some(ValueType::String)),
+            Ok(Value::String("hello".into()))
+        );
+        assert_eq!(
+            json_to_value(&serde_json::json!("#ff0000"), Some(ValueType::Brush)),
+            Ok(Value::Brush(i_slint_core::Brush::SolidColor(i_slint_core::Color::from_rgb_u8(
+                255, 0, 0
+            ))))
+        );
+        assert!(json_to_value(&serde_json::json!("hello"), Some(ValueType::Bool)).is_err());
+        assert!(json_to_value(&serde_json::json!("not a color"), Some(ValueType::Brush)).is_err());
+
+        let Ok(Value::Struct(s)) =
+            json_to_value(&serde_json::json!({ "count": 3, "enabled": true }), None)
+        else {
+            panic!("expected a struct");
+        };
+        assert_eq!(s.get_field("count"), Some(&Value::Number(3.)));
+        assert_eq!(s.get_field("enabled"), Some(&Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_root_properties() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    in property <int> count: 3;
+    in-out property <string> label: "Hello";
+    in property <color> tint: #ff000080;
+    property <bool> private-property;
+}"#,
+        );
+        let properties = root_properties(&component_instance)
+            .into_iter()
+            .map(|(name, _, value)| (name, value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            properties,
+            vec![
+                ("count".to_string(), "3".to_string()),
+                ("label".to_string(), "\"Hello\"".to_string()),
+                ("tint".to_string(), "#ff000080".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_element_properties() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    width: 200px;
+    height: 100px;
+    Rectangle {
+        x: 10px;
+        y: 20px;
+        width: 30px;
+        height: 40px;
+        background: #ff0000;
+    }
+    Text {
+        text: "Hello";
+    }
+}"#,
+        );
+        let root = definition_root_element(&component_instance.definition());
+        let children = root.borrow().children.clone();
+        let properties = |element: &ElementRc| {
+            element_properties(&component_instance, element)
+                .into_iter()
+                .map(|(name, _, value)| (name, value))
+                .collect::<HashMap<_, _>>()
+        };
+
+        let rectangle = properties(&children[0]);
+        assert_eq!(rectangle.get("x").map(String::as_str), Some("10"));
+        assert_eq!(rectangle.get("y").map(String::as_str), Some("20"));
+        assert_eq!(rectangle.get("width").map(String::as_str), Some("30"));
+        assert_eq!(rectangle.get("height").map(String::as_str), Some("40"));
+        assert_eq!(rectangle.get("background").map(String::as_str), Some("#ff0000ff"));
+        // Rectangles have no text
+        assert!(!rectangle.contains_key("text"));
+
+        let text = properties(&children[1]);
+        assert_eq!(text.get("text").map(String::as_str), Some("\"Hello\""));
+    }
+
+    #[test]
+    fn test_insert_component_edit() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    Rectangle { background: red; }
+    GridLayout {
+        Row {
+            Text { text: "A"; }
+        }
+    }
+}"#,
+        );
+        let root = definition_root_element(&component_instance.definition());
+        let children = root.borrow().children.clone();
+
+        let (_, edit) = insert_component_edit(&children[0], "Button").unwrap();
+        assert_eq!(edit.new_text, "\n        Button { }\n    ");
+        assert!(insert_component_edit(&children[0], "not a name").is_err());
+        assert!(insert_component_edit(&children[1], "Button").is_err());
+    }
+
+    #[test]
+    fn test_snap_span() {
+        let lines = [0., 50., 100.];
+        // Too far away from any line
+        assert_eq!(snap_span(10., 30., true, true, &lines), (10., 30., None));
+        // Moving snaps the closest of start, end and center
+        assert_eq!(snap_span(3., 23., true, true, &lines), (0., 20., Some(0.)));
+        assert_eq!(snap_span(78., 98., true, true, &lines), (80., 100., Some(100.)));
+        assert_eq!(snap_span(38., 58., true, true, &lines), (40., 60., Some(50.)));
+        // Resizing only moves one edge
+        assert_eq!(snap_span(10., 47., false, true, &lines), (10., 50., Some(50.)));
+        assert_eq!(snap_span(2., 40., true, false, &lines), (0., 40., Some(0.)));
+        assert_eq!(snap_span(48., 60., false, true, &lines), (48., 60., None));
+    }
+
+    #[test]
+    fn test_matching_elements() {
+        let component_instance = instance_from_source(
+            r#"
+            export component Main {
+                Rectangle { background: red; }
+                title := Text { text: "Hello"; }
+                Rectangle {
+                    background: blue;
+                    Text { text: "World"; }
+                }
+            }
+            "#,
+        );
+        let root_element = root_element(&component_instance);
+        let ids = |query| {
+            matching_elements(&root_element, query).iter().map(element_label).collect::<Vec<_>>()
+        };
+        assert_eq!(ids("Text"), vec!["title := Text".to_string(), "Text".to_string()]);
+        assert_eq!(ids("title"), vec!["title := Text".to_string()]);
+        assert_eq!(ids("Rectangle").len(), 2);
+        assert!(ids("Button").is_empty());
+    }
+
+    #[test]
+    fn test_direct_imports() {
+        let dependencies = [
+            "/project/ui/main.slint",
+            "/project/ui/button.slint",
+            "/project/common/colors.slint",
+            "/project/common/fonts.slint",
+            "/include/widgets/slider.slint",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect::<HashSet<_>>();
+
+        let imports = direct_imports(
+            r#"
+            import { Button } from "button.slint";
+            import { Colors } from "../common/colors.slint";
+            import { Slider } from "widgets/slider.slint";
+            import { StandardButton } from "std-widgets.slint";
+            export * from "missing.slint";
+            export component Main { }
+            "#,
+            Path::new("/project/ui/main.slint"),
+            &dependencies,
+        );
+        assert_eq!(
+            imports,
+            [
+                "/project/ui/button.slint",
+                "/project/common/colors.slint",
+                "/include/widgets/slider.slint"
+            ]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn test_same_diagnostics() {
+        use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+        let diagnostic = |line, message: &str| Diagnostic {
+            range: Range::new(Position::new(line, 0), Position::new(line, 5)),
+            severity: Some(DiagnosticSeverity::ERROR),
+            message: message.into(),
+            ..Default::default()
+        };
+        let old = vec![diagnostic(1, "first"), diagnostic(2, "second")];
+
+        assert!(same_diagnostics(&old, &old.clone()));
+        let mut other_source = old.clone();
+        other_source[0].source = Some("other".into());
+        assert!(same_diagnostics(&old, &other_source));
+
+        assert!(!same_diagnostics(&old, &old[..1]));
+        assert!(!same_diagnostics(&old, &[diagnostic(1, "first"), diagnostic(3, "second")]));
+        assert!(!same_diagnostics(&old, &[diagnostic(1, "first"), diagnostic(2, "changed")]));
+        let mut warning = old.clone();
+        warning[1].severity = Some(DiagnosticSeverity::WARNING);
+        assert!(!same_diagnostics(&old, &warning));
+    }
+
+    #[test]
+    fn test_next_diagnostic_index() {
+        assert_eq!(next_diagnostic_index(None, 0, false), None);
+        assert_eq!(next_diagnostic_index(Some(2), 0, true), None);
+        assert_eq!(next_diagnostic_index(None, 3, false), Some(0));
+        assert_eq!(next_diagnostic_index(None, 3, true), Some(2));
+        assert_eq!(next_diagnostic_index(Some(1), 3, false), Some(2));
+        assert_eq!(next_diagnostic_index(Some(2), 3, false), Some(0));
+        assert_eq!(next_diagnostic_index(Some(0), 3, true), Some(2));
+        assert_eq!(next_diagnostic_index(Some(1), 3, true), Some(0));
+    }
+
+    #[test]
+    fn test_geometry_label() {
+        let rect = LogicalRect::new(
+            LogicalPoint::new(10., 20.5),
+            i_slint_core::lengths::LogicalSize::new(120., 40.333),
+        );
+        assert_eq!(geometry_label(&rect), "120×40.33 at 10, 20.5");
+    }
+
+    #[test]
+    fn test_measure() {
+        let rect = |x, y, w, h| {
+            LogicalRect::new(LogicalPoint::new(x, y), i_slint_core::lengths::LogicalSize::new(w, h))
+        };
+
+        // Side by side, overlapping vertically between 10 and 30
+        let m = measure(&rect(0., 0., 10., 30.), &rect(40., 10., 10., 40.));
+        assert_eq!((m.horizontal_x1, m.horizontal_x2, m.horizontal_y), (10., 40., 20.));
+        assert_eq!((m.vertical_y1, m.vertical_y2), (0., 0.));
+        assert_eq!(m.distance, 30.);
+
+        // Diagonal, the order of the rectangles does not matter for the gaps
+        let m = measure(&rect(40., 50., 10., 10.), &rect(0., 0., 10., 10.));
+        assert_eq!((m.horizontal_x1, m.horizontal_x2), (10., 40.));
+        assert_eq!((m.vertical_y1, m.vertical_y2), (10., 50.));
+        assert_eq!(m.distance, 50.);
+
+        // Nested
+        let m = measure(&rect(0., 0., 100., 100.), &rect(10., 10., 10., 10.));
+        assert_eq!(m.distance, 0.);
+    }
+
+    #[test]
+    fn test_fit_factor() {
+        assert_eq!(fit_factor(100., 50., 240., 240.), Some(2.));
+        assert_eq!(fit_factor(400., 100., 240., 440.), Some(0.5));
+        assert_eq!(fit_factor(0., 100., 240., 440.), None);
+        assert_eq!(fit_factor(100., 100., 20., 20.), None);
+    }
+
+    #[test]
+    fn test_clamped_scale_factor() {
+        assert_eq!(clamped_scale_factor(None), None);
+        assert_eq!(clamped_scale_factor(Some(2.0)), Some(2.0));
+        assert_eq!(clamped_scale_factor(Some(0.0)), Some(0.25));
+        assert_eq!(clamped_scale_factor(Some(100.0)), Some(8.0));
+        assert_eq!(clamped_scale_factor(Some(f32::NAN)), None);
+    }
+
+    #[test]
+    fn test_element_at_picks_front_most() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    width: 200px;
+    height: 200px;
+    back := Rectangle { x: 10px; y: 10px; width: 100px; height: 100px; background: red; }
+    front := Rectangle { x: 50px; y: 50px; width: 100px; height: 100px; background: blue; }
+}"#,
+        );
+        let root_element = root_element(&component_instance);
+
+        let (element, _) = element_at(75.0, 75.0, &component_instance, &root_element).unwrap();
+        assert!(element.borrow().id.starts_with("front"));
+
+        let (element, _) = element_at(20.0, 20.0, &component_instance, &root_element).unwrap();
+        assert!(element.borrow().id.starts_with("back"));
+
+        assert!(element_at(190.0, 20.0, &component_instance, &root_element).is_none());
+    }
+
+    #[test]
+    fn test_element_at_selecting_through() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    width: 200px;
+    height: 200px;
+    layout := VerticalLayout {
+        padding: 20px;
+        inner := Rectangle { background: red; }
+    }
+}"#,
+        );
+        let root_element = root_element(&component_instance);
+        let layouts = vec!["VerticalLayout".to_string()];
+
+        let (element, _) = element_at_selecting_through(
+            100.0,
+            100.0,
+            &component_instance,
+            &root_element,
+            &layouts,
+        )
+        .unwrap();
+        assert!(element.borrow().id.starts_with("inner"));
+
+        // Nothing in the layout at its padding
+        let (element, _) =
+            element_at_selecting_through(5.0, 5.0, &component_instance, &root_element, &layouts)
+                .unwrap();
+        assert!(element.borrow().id.starts_with("layout"));
+
+        // An empty filter selects the layout like before
+        let (element, _) =
+            element_at_selecting_through(100.0, 100.0, &component_instance, &root_element, &[])
+                .unwrap();
+        assert!(element.borrow().id.starts_with("layout"));
+    }
+
+    #[test]
+    fn test_cycle_candidates_in_layout() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    width: 100px;
+    height: 100px;
+    VerticalLayout {
+        outer := Rectangle {
+            bottom := Rectangle { background: red; }
+            top := Rectangle { background: blue; }
+        }
+    }
+}"#,
+        );
+        let root_element = root_element(&component_instance);
+
+        // Selecting goes through the layout
+        let (outer, _) = element_at(50.0, 50.0, &component_instance, &root_element).unwrap();
+        assert!(outer.borrow().id.starts_with("outer"));
+        let candidates = cycle_candidates(50.0, 50.0, &component_instance, &root_element, &outer);
+        assert_eq!(candidates.len(), 1);
+        assert!(Rc::ptr_eq(&candidates[0].0, &outer));
+
+        // The children of an element in the layout, like after selecting into it
+        let bottom = outer.borrow().children[0].clone();
+        assert!(bottom.borrow().id.starts_with("bottom"));
+        let candidates = cycle_candidates(50.0, 50.0, &component_instance, &root_element, &bottom);
+        assert_eq!(candidates.len(), 2);
+        assert!(candidates[0].0.borrow().id.starts_with("top"));
+        assert!(Rc::ptr_eq(&candidates[1].0, &bottom));
+    }
+
+    #[test]
+    fn test_element_at_in_popup() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    width: 200px;
+    height: 200px;
+    public function open() { popup.show(); }
+    popup := PopupWindow {
+        x: 50px;
+        y: 50px;
+        width: 100px;
+        height: 100px;
+        inside := Rectangle { x: 10px; y: 10px; width: 20px; height: 20px; background: red; }
+    }
+    below := Rectangle { background: blue; }
+}"#,
+        );
+        let root_element = root_element(&component_instance);
+
+        let (element, _) = element_at(65.0, 65.0, &component_instance, &root_element).unwrap();
+        assert!(element.borrow().id.starts_with("below"));
+        assert!(component_instance.open_popup_root_element().is_none());
+
+        component_instance.invoke("open", &[]).unwrap();
+        let popup = component_instance.open_popup_root_element().unwrap();
+
+        let (element, position) =
+            element_at(65.0, 65.0, &component_instance, &root_element).unwrap();
+        assert!(element.borrow().id.starts_with("inside"));
+        assert_eq!(
+            position,
+            LogicalRect::new(
+                LogicalPoint::new(60., 60.),
+                i_slint_core::lengths::LogicalSize::new(20., 20.)
+            )
+        );
+
+        // In the popup, but not in any of its children
+        let (element, _) = element_at(120.0, 120.0, &component_instance, &root_element).unwrap();
+        assert!(Rc::ptr_eq(&element, &popup));
+
+        // Outside of the popup
+        let (element, _) = element_at(10.0, 10.0, &component_instance, &root_element).unwrap();
+        assert!(element.borrow().id.starts_with("below"));
+    }
+
+    #[test]
+    fn test_apply_source_edits_with_preview_wrapper() {
+        i_slint_backend_testing::init();
+        let path = PathBuf::from("/test_apply_source_edits.slint");
+        let source =
+            "export component Test inherits Window {\n    r := Rectangle { }\n    Text { }\n}\n";
+        set_contents(&path, source.to_string());
+
+        // Compiled like `compile_preview` does when a component is named
+        let mut compiler = slint_interpreter::ComponentCompiler::default();
+        let compiled = format!("{source}\n{}", preview_wrapper_source("Test", None));
+        let instance = spin_on::spin_on(compiler.build_from_source(compiled, path.clone()))
+            .expect("test source compiles")
+            .create()
+            .unwrap();
+        let element = element_tree(&root_element(&instance)).children[0].element.upgrade().unwrap();
+        assert!(element.borrow().id.starts_with("r"));
+        let node = element.borrow().node.clone().unwrap();
+        let edit = element_edit::remove_element(&node).unwrap();
+
+        let after = apply_source_edits("Delete element", &node, vec![edit]).unwrap();
+        assert!(!after.contains("Rectangle"));
+        assert!(after.contains("Text { }"));
+        assert!(!after.contains("_Preview"));
+
+        assert!(is_compiled_from(source, source));
+        assert!(!is_compiled_from("export component Other { }", source));
+        remove_contents(&path);
+    }
+
+    #[test]
+    fn test_element_starting_at_in_new_instance() {
+        let source = r#"export component Test inherits Window {
+    width: 100px;
+    height: 100px;
+    first := Rectangle { background: red; }
+    second := Rectangle { background: blue; }
+}"#;
+        let old_instance = instance_from_source(source);
+        let old_element =
+            element_tree(&root_element(&old_instance)).children[1].element.upgrade().unwrap();
+        assert!(old_element.borrow().id.starts_with("second"));
+        let (path, offset) = element_offset(&old_element).unwrap();
+
+        // The same source code: the element is found again
+        let new_instance = instance_from_source(source);
+        let tree = element_tree(&root_element(&new_instance));
+        let new_element = element_starting_at(&tree, &path, offset).unwrap();
+        assert!(new_element.borrow().id.starts_with("second"));
+        assert!(!Rc::ptr_eq(&old_element, &new_element));
+
+        // The element is gone
+        let changed_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    width: 100px;
+    height: 100px;
+}"#,
+        );
+        let tree = element_tree(&root_element(&changed_instance));
+        assert!(element_starting_at(&tree, &path, offset).is_none());
+    }
+
+    #[test]
+    fn test_has_visible_content() {
+        let visible = |source: &str| has_visible_content(&instance_from_source(source));
+
+        assert!(visible(
+            r#"export component Test inherits Window {
+    width: 100px;
+    height: 100px;
+    Rectangle { x: 90px; y: 90px; width: 20px; height: 20px; background: red; }
+}"#
+        ));
+        assert!(!visible(
+            r#"export component Test inherits Window {
+    width: 0px;
+    height: 0px;
+}"#
+        ));
+        assert!(!visible(
+            r#"export component Test inherits Window {
+    width: 100px;
+    height: 100px;
+    Rectangle { x: 200px; y: 0px; width: 20px; height: 20px; background: red; }
+    Rectangle { x: 10px; y: 10px; width: 20px; height: 20px; visible: false; }
+}"#
+        ));
+        assert!(visible(
+            r#"export component Test inherits Window {
+    width: 100px;
+    height: 100px;
+    for i in 0 : Rectangle { x: 200px; width: 20px; height: 20px; }
+}"#
+        ));
+    }
+
+    #[test]
+    fn test_element_at_skips_collapsed_and_hidden() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    width: 200px;
+    height: 200px;
+    back := Rectangle { x: 10px; y: 10px; width: 100px; height: 100px; background: red; }
+    collapsed := Rectangle { x: 20px; y: 20px; width: 0px; height: 50px; background: blue; }
+    hidden := Rectangle { x: 10px; y: 10px; width: 100px; height: 100px; visible: false; }
+    shown := Rectangle { x: 150px; y: 150px; width: 40px; height: 40px; visible: true; }
+}"#,
+        );
+        let root_element = root_element(&component_instance);
+
+        // On the edge of the collapsed element
+        let (element, _) = element_at(20.0, 30.0, &component_instance, &root_element).unwrap();
+        assert!(element.borrow().id.starts_with("back"));
+        let collapsed = root_element
+            .borrow()
+            .children
+            .iter()
+            .find(|c| c.borrow().id.starts_with("collapsed"))
+            .cloned()
+            .unwrap();
+        assert!(!element_covers_point(20.0, 30.0, &component_instance, &collapsed));
+
+        // Below the hidden element
+        let (element, _) = element_at(50.0, 50.0, &component_instance, &root_element).unwrap();
+        assert!(element.borrow().id.starts_with("back"));
+
+        let (element, _) = element_at(160.0, 160.0, &component_instance, &root_element).unwrap();
+        assert!(element.borrow().id.starts_with("shown"));
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    width: 200px;
+    height: 100px;
+    background: white;
+    title := Text { x: 10px; y: 20px; text: "Hello"; color: red; }
+    group := Rectangle {
+        inner := Rectangle { background: blue; border-radius: 4px; }
+    }
+}"#,
+        );
+        let tree = render_tree(&component_instance, &root_element(&component_instance));
+
+        assert_eq!(
+            tree.geometry,
+            Some(RenderTreeGeometry { x: 0.0, y: 0.0, width: 200.0, height: 100.0 })
+        );
+        assert_eq!(tree.properties.get("background").map(String::as_str), Some("white"));
+        assert_eq!(tree.properties.get("width"), None);
+
+        let ids = tree.children.iter().map(|c| c.id.as_deref()).collect::<Vec<_>>();
+        assert_eq!(ids, [Some("title"), Some("group")]);
+
+        let text = &tree.children[0];
+        assert_eq!(text.properties.get("text").map(String::as_str), Some("\"Hello\""));
+        assert_eq!(text.properties.get("color").map(String::as_str), Some("red"));
+        assert_eq!(text.geometry.as_ref().map(|g| (g.x, g.y)), Some((10.0, 20.0)));
+        assert_eq!(text.range.start.line, 4);
+
+        let inner = &tree.children[1].children[0];
+        assert_eq!(inner.id.as_deref(), Some("inner"));
+        assert_eq!(inner.properties.len(), 2);
+    }
+
+    #[test]
+    fn test_callback_arguments() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    callback clicked();
+    callback moved(length, string);
+    in property <int> count;
+}"#,
+        );
+        let definition = component_instance.definition();
+
+        assert_eq!(callback_arguments(&definition, "clicked", &[]).map(|a| a.len()), Ok(0));
+        assert_eq!(
+            callback_arguments(
+                &definition,
+                "moved",
+                &[serde_json::json!(4), serde_json::json!("x")]
+            ),
+            Ok(vec![
+                slint_interpreter::Value::Number(4.),
+                slint_interpreter::Value::String("x".into())
+            ])
+        );
+        assert!(callback_arguments(&definition, "moved", &[serde_json::json!(4)]).is_err());
+        assert!(callback_arguments(
+            &definition,
+            "moved",
+            &[serde_json::json!("a"), serde_json::json!("x")]
+        )
+        .is_err());
+        assert!(callback_arguments(&definition, "count", &[]).is_err());
+        assert!(callback_arguments(&definition, "missing", &[]).is_err());
+    }
+
+    #[test]
+    fn test_accessibility_tree() {
+        let component_instance = instance_from_source(
+            r#"export component Test inherits Window {
+    Rectangle {
+        ok := Rectangle {
+            accessible-role: button;
+            accessible-label: "OK";
+        }
+    }
+}"#,
+        );
+        let tree = accessibility_tree(&component_instance, &root_element(&component_instance));
+
+        let button = tree.iter().find(|n| n.id.as_deref() == Some("ok")).unwrap();
+        assert_eq!(button.role, "button");
+        assert_eq!(button.properties.get("label").map(String::as_str), Some("OK"));
+        assert_eq!(button.file, "/test.slint");
+        assert!(button.offset.is_some());
+        assert_eq!(button.range.start.line, 2);
+    }
 }
@@ -0,0 +1,484 @@
+// Copyright © SixtyFPS GmbH <info@slint.dev>
+// SPDX-License-Identifier: GPL-3.0-only OR LicenseRef-Slint-Royalty-free-1.1 OR LicenseRef-Slint-commercial
+
+//! Changes to the source code of elements, for edits done in the preview
+
+use std::collections::{HashMap, HashSet};
+
+use i_slint_compiler::parser::{syntax_nodes, Language, SyntaxKind};
+
+use crate::util::{map_position, map_range};
+
+/// How a property of type length is set on an element
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LengthBinding {
+    /// The property is not set
+    NotSet,
+    /// The property is set to a literal length in logical pixels, like `10px`
+    Literal(f32),
+    /// The property is set to something the preview can not change
+    Other,
+}
+
+fn find_binding(element: &syntax_nodes::Element, property: &str) -> Option<syntax_nodes::Binding> {
+    element.Binding().find(|b| b.child_text(SyntaxKind::Identifier).as_deref() == Some(property))
+}
+
+pub fn length_binding(element: &syntax_nodes::Element, property: &str) -> LengthBinding {
+    let Some(binding) = find_binding(element, property) else {
+        return LengthBinding::NotSet;
+    };
+    let expression = binding.BindingExpression().text().to_string();
+    parse_logical_length(expression.trim().trim_end_matches(';').trim())
+        .map_or(LengthBinding::Other, LengthBinding::Literal)
+}
+
+fn parse_logical_length(text: &str) -> Option<f32> {
+    if text == "0" {
+        return Some(0.);
+    }
+    text.strip_suffix("px")?.parse::<f32>().ok().filter(|v| v.is_finite())
+}
+
+fn format_logical_length(value: f32) -> String {
+    if value.fract() == 0. {
+        format!("{}px", value as i64)
+    } else {
+        format!("{value}px")
+    }
+}
+
+// The indentation of the line the element starts on
+fn element_indent(element: &syntax_nodes::Element) -> String {
+    let mut token = element.first_token().and_then(|t| t.prev_token());
+    while let Some(t) = token {
+        if t.kind() == SyntaxKind::Whitespace && t.text().contains('\n') {
+            return t.text().split('\n').last().unwrap_or_default().to_owned();
+        }
+        token = t.prev_token();
+    }
+    String::new()
+}
+
+/// The edits setting the `values` properties of `element` to literal lengths in logical
+/// pixels. Existing bindings get their expression replaced, the others get added at the
+/// start of the element.
+pub fn set_length_bindings(
+    element: &syntax_nodes::Element,
+    values: &[(&str, f32)],
+) -> Option<Vec<lsp_types::TextEdit>> {
+    let source_file = element.source_file.clone();
+    let mut edits = Vec::new();
+    let mut new_bindings = String::new();
+    let indent = element_indent(element);
+
+    for (property, value) in values {
+        let value = format_logical_length(*value);
+        match find_binding(element, property) {
+            Some(binding) => {
+                let expression = binding.BindingExpression().Expression()?;
+                edits.push(lsp_types::TextEdit {
+                    range: map_range(&source_file, expression.text_range()),
+                    new_text: value,
+                });
+            }
+            None => new_bindings.push_str(&format!("\n{indent}    {property}: {value};")),
+        }
+    }
+
+    if !new_bindings.is_empty() {
+        let position =
+            map_position(&source_file, element.child_token(SyntaxKind::LBrace)?.text_range().end());
+        edits.push(lsp_types::TextEdit {
+            range: lsp_types::Range::new(position, position),
+            new_text: new_bindings,
+        });
+    }
+    Some(edits)
+}
+
+/// The edit adding an empty instance of `component` as the last child of `element`, indented
+/// one level deeper than the element
+pub fn insert_child(
+    element: &syntax_nodes::Element,
+    component: &str,
+) -> Option<lsp_types::TextEdit> {
+    let source_file = element.source_file.clone();
+    let indent = element_indent(element);
+    let r_brace = element.child_token(SyntaxKind::RBrace)?;
+    // Replace the whitespace in front of the closing brace, so that it ends up on its own line
+    let start = match r_brace.prev_token() {
+        Some(t) if t.kind() == SyntaxKind::Whitespace => t.text_range().start(),
+        _ => r_brace.text_range().start(),
+    };
+    Some(lsp_types::TextEdit {
+        range: map_range(&source_file, rowan::TextRange::new(start, r_brace.text_range().start())),
+        new_text: format!("\n{indent}    {component} {{ }}\n{indent}"),
+    })
+}
+
+// The node of `element` including its id and its `for` or `if`. None for the root of a component.
+fn outer_node(element: &syntax_nodes::Element) -> Option<rowan::SyntaxNode<Language>> {
+    let mut node = element.node.clone();
+    while let Some(parent) = node.parent().filter(|p| {
+        matches!(
+            p.kind(),
+            SyntaxKind::SubElement | SyntaxKind::RepeatedElement | SyntaxKind::ConditionalElement
+        )
+    }) {
+        node = parent;
+    }
+    (node.kind() != SyntaxKind::Element).then_some(node)
+}
+
+/// The edit inserting a copy of `element` right after it. The ids in the copy get a number
+/// appended so that they stay unique. Also returns the offset of the copied element in the
+/// changed source.
+pub fn duplicate_element(element: &syntax_nodes::Element) -> Option<(lsp_types::TextEdit, u32)> {
+    let source = element.source_file.source()?;
+    let node = outer_node(element)?;
+
+    let tokens = |n: &rowan::SyntaxNode<Language>| {
+        n.descendants_with_tokens().filter_map(|t| t.into_token()).collect::<Vec<_>>()
+    };
+    let mut used = node
+        .ancestors()
+        .last()
+        .map(|root| tokens(&root))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|t| t.kind() == SyntaxKind::Identifier)
+        .map(|t| t.text().to_owned())
+        .collect::<HashSet<_>>();
+    let mut renamed = HashMap::new();
+    for id in node
+        .descendants()
+        .filter(|n| n.kind() == SyntaxKind::SubElement)
+        .filter_map(|n| n.children_with_tokens().find(|t| t.kind() == SyntaxKind::Identifier))
+        .filter_map(|t| t.into_token())
+    {
+        let new_id = (2..)
+            .map(|n| format!("{}-{n}", id.text()))
+            .find(|candidate| !used.contains(candidate))
+            .unwrap();
+        used.insert(new_id.clone());
+        renamed.insert(id.text().to_owned(), new_id);
+    }
+
+    let mut copy = String::new();
+    let mut element_offset = 0;
+    for token in tokens(&node) {
+        if token.text_range().start() == element.text_range().start() {
+            element_offset = copy.len();
+        }
+        match renamed.get(token.text()).filter(|_| token.kind() == SyntaxKind::Identifier) {
+            Some(new_id) => copy.push_str(new_id),
+            None => copy.push_str(token.text()),
+        }
+    }
+
+    // Put the copy on its own line if the element is on its own line
+    let start = usize::from(node.text_range().start());
+    let end = usize::from(node.text_range().end());
+    let line_start = source[..start].trim_end_matches([' ', '\t']).len();
+    let separator = if line_start == 0 || source[..line_start].ends_with('\n') {
+        format!("\n{}", &source[line_start..start])
+    } else {
+        " ".to_owned()
+    };
+    let position = map_position(&element.source_file, node.text_range().end());
+    Some((
+        lsp_types::TextEdit {
+            range: lsp_types::Range::new(position, position),
+            new_text: format!("{separator}{copy}"),
+        },
+        (end + separator.len() + element_offset) as u32,
+    ))
+}
+
+/// The edit removing `element` from its parent, together with its id, its `for` or `if`, and
+/// the line it was on if nothing else is on it. Returns None for the root of a component.
+pub fn remove_element(element: &syntax_nodes::Element) -> Option<lsp_types::TextEdit> {
+    let source = element.source_file.source()?;
+    let node = outer_node(element)?;
+
+    let range = node.text_range();
+    let (mut start, mut end) = (usize::from(range.start()), usize::from(range.end()));
+    let line_start = source[..start].trim_end_matches([' ', '\t']).len();
+    let line_end =
+        end + (source[end..].len() - source[end..].trim_start_matches([' ', '\t']).len());
+    if (line_start == 0 || source[..line_start].ends_with('\n'))
+        && source[line_end..].starts_with('\n')
+    {
+        (start, end) = (line_start, line_end + 1);
+    }
+    Some(lsp_types::TextEdit {
+        range: map_range(
+            &element.source_file,
+            rowan::TextRange::new((start as u32).into(), (end as u32).into()),
+        ),
+        new_text: String::new(),
+    })
+}
+
+/// Wrap `edits` of the file containing `element` into a `WorkspaceEdit`
+pub fn workspace_edit(
+    element: &syntax_nodes::Element,
+    edits: Vec<lsp_types::TextEdit>,
+) -> Option<lsp_types::WorkspaceEdit> {
+    let url = lsp_types::Url::from_file_path(element.source_file.path()).ok()?;
+    Some(lsp_types::WorkspaceEdit {
+        changes: Some(HashMap::from([(url, edits)])),
+        ..Default::default()
+    })
+}
+
+// The byte offset of `position` in `source`. Columns are counted in bytes, like `map_position` does.
+fn offset_of(source: &str, position: lsp_types::Position) -> Option<usize> {
+    let line_start = match position.line {
+        0 => 0,
+        line => source.match_indices('\n').nth(line as usize - 1)?.0 + 1,
+    };
+    let offset = line_start + position.character as usize;
+    source.is_char_boundary(offset).then_some(offset)
+}
+
+fn position_of(source: &str, offset: usize) -> lsp_types::Position {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    lsp_types::Position::new(before.matches('\n').count() as u32, (offset - line_start) as u32)
+}
+
+/// Apply `edits` to `source`. Returns None if the edits overlap or do not fit `source`.
+pub fn apply_text_edits(source: &str, edits: &[lsp_types::TextEdit]) -> Option<String> {
+    let mut edits = edits
+        .iter()
+        .map(|e| {
+            Some((offset_of(source, e.range.start)?, offset_of(source, e.range.end)?, &e.new_text))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    edits.sort_by_key(|(start, end, _)| (*start, *end));
+
+    let mut result = String::with_capacity(source.len());
+    let mut done = 0;
+    for (start, end, new_text) in edits {
+        if start < done || end < start {
+            return None;
+        }
+        result.push_str(&source[done..start]);
+        result.push_str(new_text);
+        done = end;
+    }
+    result.push_str(&source[done..]);
+    Some(result)
+}
+
+/// The single edit turning `from` into `to`: it replaces everything between the start and
+/// the end both have in common.
+pub fn text_edit_between(from: &str, to: &str) -> lsp_types::TextEdit {
+    let prefix = from
+        .char_indices()
+        .zip(to.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(from.len().min(to.len()), |((i, _), _)| i);
+    let suffix: usize = from[prefix..]
+        .chars()
+        .rev()
+        .zip(to[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+
+    lsp_types::TextEdit {
+        range: lsp_types::Range::new(
+            position_of(from, prefix),
+            position_of(from, from.len() - suffix),
+        ),
+        new_text: to[prefix..to.len() - suffix].to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    // Returns the element with the given id in `source`
+    fn parse_element(source: &str, id: &str) -> syntax_nodes::Element {
+        let mut diag = i_slint_compiler::diagnostics::BuildDiagnostics::default();
+        let doc: syntax_nodes::Document = i_slint_compiler::parser::parse(
+            source.to_string(),
+            Some(Path::new("/test.slint")),
+            None,
+            &mut diag,
+        )
+        .into();
+        assert!(!diag.has_error());
+        doc.node
+            .descendants()
+            .filter_map(|node| {
+                syntax_nodes::SubElement::new(i_slint_compiler::parser::SyntaxNode {
+                    node,
+                    source_file: doc.source_file.clone(),
+                })
+            })
+            .find(|e| e.child_text(SyntaxKind::Identifier).as_deref() == Some(id))
+            .unwrap()
+            .Element()
+    }
+
+    #[test]
+    fn test_length_binding() {
+        let element = parse_element(
+            "component Main {\n    r := Rectangle {\n        x: 10px;\n        y: 0;\n        width: parent.width / 2;\n        height: 2.5px ;\n    }\n}\n",
+            "r",
+        );
+        assert_eq!(length_binding(&element, "x"), LengthBinding::Literal(10.));
+        assert_eq!(length_binding(&element, "y"), LengthBinding::Literal(0.));
+        assert_eq!(length_binding(&element, "width"), LengthBinding::Other);
+        assert_eq!(length_binding(&element, "height"), LengthBinding::Literal(2.5));
+        assert_eq!(length_binding(&element, "z"), LengthBinding::NotSet);
+    }
+
+    #[test]
+    fn test_set_length_bindings() {
+        use lsp_types::{Position, Range};
+
+        let element = parse_element(
+            "component Main {\n    r := Rectangle {\n        x: 10px;\n    }\n}\n",
+            "r",
+        );
+        let edits = set_length_bindings(&element, &[("x", 12.), ("y", -3.5)]).unwrap();
+        assert_eq!(
+            edits,
+            vec![
+                lsp_types::TextEdit {
+                    range: Range::new(Position::new(2, 11), Position::new(2, 15)),
+                    new_text: "12px".into(),
+                },
+                lsp_types::TextEdit {
+                    range: Range::new(Position::new(1, 20), Position::new(1, 20)),
+                    new_text: "\n        y: -3.5px;".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_child() {
+        for (source, expected) in [
+            (
+                "component Main {\n    r := Rectangle {\n        x: 10px;\n    }\n}\n",
+                "component Main {\n    r := Rectangle {\n        x: 10px;\n        Foo { }\n    }\n}\n",
+            ),
+            (
+                "component Main {\n    r := Rectangle { }\n}\n",
+                "component Main {\n    r := Rectangle {\n        Foo { }\n    }\n}\n",
+            ),
+            (
+                "component Main {\n    r := Rectangle {}\n}\n",
+                "component Main {\n    r := Rectangle {\n        Foo { }\n    }\n}\n",
+            ),
+        ] {
+            let element = parse_element(source, "r");
+            let edit = insert_child(&element, "Foo").unwrap();
+            assert_eq!(apply_text_edits(source, &[edit]).as_deref(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_remove_element() {
+        for (source, expected) in [
+            (
+                "component Main {\n    Rectangle {\n        r := Text { }\n        Image { }\n    }\n}\n",
+                "component Main {\n    Rectangle {\n        Image { }\n    }\n}\n",
+            ),
+            (
+                "component Main {\n    Rectangle {\n        for i in 3 : r := Text { }\n    }\n}\n",
+                "component Main {\n    Rectangle {\n    }\n}\n",
+            ),
+            (
+                "component Main {\n    Rectangle { r := Text { } Image { } }\n}\n",
+                "component Main {\n    Rectangle {  Image { } }\n}\n",
+            ),
+        ] {
+            let element = parse_element(source, "r");
+            let edit = remove_element(&element).unwrap();
+            let result = apply_text_edits(source, &[edit]).unwrap();
+            assert_eq!(result, expected);
+
+            let mut diag = i_slint_compiler::diagnostics::BuildDiagnostics::default();
+            i_slint_compiler::parser::parse(result, Some(Path::new("/test.slint")), None, &mut diag);
+            assert!(!diag.has_error());
+        }
+    }
+
+    #[test]
+    fn test_duplicate_element() {
+        for (source, expected, copy) in [
+            (
+                "component Main {\n    r := Rectangle {\n        t := Text { text: t.x; }\n    }\n}\n",
+                "component Main {\n    r := Rectangle {\n        t := Text { text: t.x; }\n    }\n    r-2 := Rectangle {\n        t-2 := Text { text: t-2.x; }\n    }\n}\n",
+                "Rectangle {\n        t-2",
+            ),
+            (
+                "component Main {\n    Rectangle { for i in 2 : r := Text { } }\n}\n",
+                "component Main {\n    Rectangle { for i in 2 : r := Text { } for i in 2 : r-2 := Text { } }\n}\n",
+                "Text { } }",
+            ),
+        ] {
+            let element = parse_element(source, "r");
+            let (edit, offset) = duplicate_element(&element).unwrap();
+            let result = apply_text_edits(source, &[edit]).unwrap();
+            assert_eq!(result, expected);
+            assert!(result[offset as usize..].starts_with(copy));
+        }
+    }
+
+    #[test]
+    fn test_apply_text_edits() {
+        use lsp_types::{Position, Range, TextEdit};
+
+        let source = "a: 1px;\nb: 2px;\n";
+        let edit = |line, start, end, text: &str| TextEdit {
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+            new_text: text.into(),
+        };
+        assert_eq!(
+            apply_text_edits(source, &[edit(1, 3, 6, "20px"), edit(0, 3, 6, "10px")]).as_deref(),
+            Some("a: 10px;\nb: 20px;\n")
+        );
+        assert_eq!(
+            apply_text_edits(source, &[edit(2, 0, 0, "c")]).as_deref(),
+            Some("a: 1px;\nb: 2px;\nc")
+        );
+        // overlapping
+        assert_eq!(apply_text_edits(source, &[edit(0, 0, 4, "x"), edit(0, 3, 6, "y")]), None);
+        // out of range
+        assert_eq!(apply_text_edits(source, &[edit(3, 0, 0, "c")]), None);
+    }
+
+    #[test]
+    fn test_text_edit_between() {
+        use lsp_types::{Position, Range, TextEdit};
+
+        for (from, to) in [
+            ("a: 1px;\nb: 2px;\n", "a: 1px;\nb: 24px;\n"),
+            ("x: 10px;\n", "x: 10px;\ny: 5px;\n"),
+            ("ä: ö;", "ä: ü;"),
+            ("same", "same"),
+            ("", "new"),
+        ] {
+            let edit = text_edit_between(from, to);
+            assert_eq!(apply_text_edits(from, &[edit]).as_deref(), Some(to));
+        }
+        assert_eq!(
+            text_edit_between("a: 1px;\nb: 2px;\n", "a: 1px;\nb: 24px;\n"),
+            TextEdit {
+                range: Range::new(Position::new(1, 4), Position::new(1, 4)),
+                new_text: "4".into(),
+            }
+        );
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright © SixtyFPS GmbH <info@slint.dev>
+// SPDX-License-Identifier: GPL-3.0-only OR LicenseRef-Slint-Royalty-free-1.1 OR LicenseRef-Slint-commercial
+
+//! Trace output of the preview, to find out why it reloads (or why it does not).
+//!
+//! The messages go to stderr, which editors show in the output of the language server.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+use crate::common::PreviewLogLevel;
+
+/// Environment variable overriding the configured level, set to `off`, `info` or `debug`
+const LOG_LEVEL_ENV: &str = "SLINT_LSP_PREVIEW_LOG";
+
+static CONFIGURED_LEVEL: AtomicU8 = AtomicU8::new(PreviewLogLevel::Off as u8);
+
+fn env_level() -> Option<PreviewLogLevel> {
+    static ENV_LEVEL: OnceLock<Option<PreviewLogLevel>> = OnceLock::new();
+    *ENV_LEVEL.get_or_init(|| {
+        let value = std::env::var(LOG_LEVEL_ENV).ok()?;
+        serde_json::from_value(serde_json::Value::String(value.trim().to_lowercase())).ok()
+    })
+}
+
+/// Sets the level from the configuration. The environment variable wins if it is set.
+pub fn set_level(level: PreviewLogLevel) {
+    CONFIGURED_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+fn level() -> PreviewLogLevel {
+    env_level().unwrap_or_else(|| match CONFIGURED_LEVEL.load(Ordering::Relaxed) {
+        x if x == PreviewLogLevel::Debug as u8 => PreviewLogLevel::Debug,
+        x if x == PreviewLogLevel::Info as u8 => PreviewLogLevel::Info,
+        _ => PreviewLogLevel::Off,
+    })
+}
+
+pub fn enabled(level: PreviewLogLevel) -> bool {
+    level != PreviewLogLevel::Off && level <= self::level()
+}
+
+pub fn write(level: PreviewLogLevel, message: std::fmt::Arguments) {
+    let level = match level {
+        PreviewLogLevel::Off => return,
+        PreviewLogLevel::Info => "info",
+        PreviewLogLevel::Debug => "debug",
+    };
+    eprintln!("[preview {level}] {message}");
+}
+
+/// Logs a message about the preview if the level (`Info` or `Debug`) is enabled.
+/// The arguments are only formatted in that case.
+macro_rules! preview_log {
+    ($level:ident, $($arg:tt)*) => {
+        if $crate::preview::logging::enabled($crate::common::PreviewLogLevel::$level) {
+            $crate::preview::logging::write(
+                $crate::common::PreviewLogLevel::$level,
+                format_args!($($arg)*),
+            );
+        }
+    };
+}
+
+pub(crate) use preview_log;
@@ -3,6 +3,7 @@
 
 // cSpell: ignore condvar
 
+use crate::common::PreviewRenderer;
 use crate::lsp_ext::Health;
 use crate::ServerNotifier;
 
@@ -37,6 +38,53 @@ enum RequestedGuiEventLoopState {
 
 thread_local! {static CLI_ARGS: std::cell::OnceCell<crate::Cli> = Default::default();}
 
+/// The renderer the preview picked when its window system got started, None if the backend
+/// was picked on the command line or in the environment
+static STARTED_RENDERER: std::sync::OnceLock<Option<PreviewRenderer>> = std::sync::OnceLock::new();
+
+// The `SLINT_BACKEND` selecting `renderer`, `None` if it is not built in
+fn renderer_backend(renderer: PreviewRenderer) -> Option<&'static str> {
+    match renderer {
+        PreviewRenderer::Default => Some(""),
+        PreviewRenderer::Femtovg => cfg!(feature = "renderer-femtovg").then_some("winit-femtovg"),
+        PreviewRenderer::Skia => cfg!(any(
+            feature = "renderer-skia",
+            feature = "renderer-skia-opengl",
+            feature = "renderer-skia-vulkan"
+        ))
+        .then_some("winit-skia"),
+        PreviewRenderer::Software => {
+            cfg!(feature = "renderer-software").then_some("winit-software")
+        }
+    }
+}
+
+// Why the preview does not use the configured `renderer`, if it does not
+fn renderer_warning(renderer: PreviewRenderer) -> Option<String> {
+    if renderer_backend(renderer).is_none() {
+        return Some(format!(
+            "The {renderer:?} renderer is not built into the language server, the preview uses the default one"
+        ));
+    }
+    match STARTED_RENDERER.get()? {
+        Some(started) if *started != renderer => Some(format!(
+            "The preview switches to the {renderer:?} renderer once the language server restarts"
+        )),
+        None if renderer != PreviewRenderer::Default => Some(format!(
+            "The preview uses the backend set with --backend or SLINT_BACKEND instead of the {renderer:?} renderer"
+        )),
+        _ => None,
+    }
+}
+
+/// The configured renderer changed. The window system is already running with the renderer it
+/// started with, so all that can be done is telling the user.
+pub fn renderer_changed(renderer: PreviewRenderer) {
+    if let Some(warning) = renderer_warning(renderer) {
+        send_status(&warning, Health::Warning);
+    }
+}
+
 pub fn run_in_ui_thread<F: Future<Output = ()> + 'static>(
     create_future: impl Send + FnOnce() -> F + 'static,
 ) {
@@ -73,8 +121,27 @@ pub fn start_ui_event_loop(cli_args: crate::Cli) {
         }
 
         if *state_requested == RequestedGuiEventLoopState::StartLoop {
+            // The backend picked on the command line or in the environment wins
+            let renderer =
+                super::CONTENT_CACHE.get_or_init(Default::default).lock().unwrap().config.renderer;
+            let started = if std::env::var_os("SLINT_BACKEND").is_some() {
+                None
+            } else if renderer_backend(renderer).is_some() {
+                Some(renderer)
+            } else {
+                Some(PreviewRenderer::Default)
+            };
+            STARTED_RENDERER.set(started).ok();
+            let backend = started.and_then(renderer_backend).filter(|b| !b.is_empty());
             // make sure the backend is initialized
-            i_slint_backend_selector::with_platform(|_| Ok(())).unwrap();
+            match backend {
+                Some(backend) => i_slint_core::with_platform(
+                    || i_slint_backend_selector::create_backend_by_name(backend),
+                    |_| Ok(()),
+                ),
+                None => i_slint_backend_selector::with_platform(|_| Ok(())),
+            }
+            .unwrap();
             // Send an event so that once the loop is started, we notify the LSP thread that it can send more events
             i_slint_core::api::invoke_from_event_loop(|| {
                 let mut state_request = GUI_EVENT_LOOP_STATE_REQUEST.lock().unwrap();
@@ -137,6 +204,11 @@ pub fn open_ui(sender: &ServerNotifier) {
 
         let mut s = SERVER_NOTIFIER.get_or_init(Default::default).lock().unwrap();
         *s = Some(sender.clone());
+        drop(s);
+
+        if let Some(warning) = renderer_warning(cache.config.renderer) {
+            crate::preview::send_status_notification(sender, &warning, Health::Warning);
+        }
     };
 
     i_slint_core::api::invoke_from_event_loop(move || {
@@ -147,10 +219,12 @@ pub fn open_ui(sender: &ServerNotifier) {
         });
     })
     .unwrap();
+
+    super::reload_last_component();
 }
 
 fn open_ui_impl(preview_state: &mut PreviewState) {
-    let (default_style, show_preview_ui, fullscreen) = {
+    let (default_style, show_preview_ui, show_selections, fullscreen, view_config, design_mode) = {
         let cache = super::CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
         let style = cache.config.style.clone();
         let style = if style.is_empty() {
@@ -164,12 +238,22 @@ fn open_ui_impl(preview_state: &mut PreviewState) {
             .or_else(|| CLI_ARGS.with(|args| args.get().map(|a| a.no_toolbar.clone())))
             .unwrap_or(false);
         let fullscreen = CLI_ARGS.with(|args| args.get().map(|a| a.fullscreen).unwrap_or_default());
-        (style, !hide_ui, fullscreen)
+        (
+            style,
+            cache.preview_ui_visible.unwrap_or(!hide_ui),
+            cache.selections_without_ui,
+            fullscreen,
+            cache.config.clone(),
+            cache.design_mode,
+        )
     };
 
     // TODO: Handle Error!
     let ui = preview_state.ui.get_or_insert_with(|| super::ui::create_ui(default_style).unwrap());
     ui.set_show_preview_ui(show_preview_ui);
+    ui.set_show_selections_without_ui(show_selections);
+    super::set_ui_view_config(ui, &view_config);
+    ui.set_design_mode(design_mode);
     ui.window().set_fullscreen(fullscreen);
     ui.window().on_close_requested(|| {
         let mut cache = super::CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
@@ -214,34 +298,53 @@ fn close_ui_impl(preview_state: &mut PreviewState) {
 struct PreviewState {
     ui: Option<super::ui::PreviewUi>,
     handle: Rc<RefCell<Option<ComponentInstance>>>,
-    selected_element: Option<ElementWeak>,
+    selected_elements: Vec<(ElementWeak, LogicalRect)>,
 }
 
 thread_local! {static PREVIEW_STATE: std::cell::RefCell<PreviewState> = Default::default();}
 
-pub fn set_selected_element(
-    element_position: Option<(&ElementRc, LogicalRect)>,
+pub fn set_selected_elements(
+    elements: &[(ElementRc, LogicalRect)],
     positions: slint_interpreter::highlight::ComponentPositions,
 ) {
     PREVIEW_STATE.with(move |preview_state| {
         let mut preview_state = preview_state.borrow_mut();
-        if let Some((e, _)) = element_position.as_ref() {
-            preview_state.selected_element = Some(Rc::downgrade(e));
-        } else {
-            preview_state.selected_element = None;
-        }
+        preview_state.selected_elements =
+            elements.iter().map(|(e, r)| (Rc::downgrade(e), *r)).collect();
+
+        let component_instance = preview_state.handle.borrow().as_ref().map(|ci| ci.clone_strong());
+        super::set_selections(
+            preview_state.ui.as_ref(),
+            component_instance.as_ref(),
+            elements,
+            positions,
+        );
+    })
+}
 
-        super::set_selections(preview_state.ui.as_ref(), element_position, positions);
+pub fn set_hovered_element(hovered: Option<(ElementRc, LogicalRect)>) {
+    PREVIEW_STATE.with(move |preview_state| {
+        let preview_state = preview_state.borrow();
+        super::set_hover(preview_state.ui.as_ref(), hovered.as_ref());
+    })
+}
+
+pub fn refresh_root_properties(component_instance: &ComponentInstance) {
+    PREVIEW_STATE.with(move |preview_state| {
+        if let Some(ui) = &preview_state.borrow().ui {
+            super::set_root_properties(ui, component_instance);
+        }
     })
 }
 
-pub fn selected_element() -> Option<ElementRc> {
+pub fn selected_elements() -> Vec<(ElementRc, LogicalRect)> {
     PREVIEW_STATE.with(move |preview_state| {
         let preview_state = preview_state.borrow();
-        let Some(weak) = &preview_state.selected_element else {
-            return None;
-        };
-        Weak::upgrade(&weak)
+        preview_state
+            .selected_elements
+            .iter()
+            .filter_map(|(weak, r)| Some((Weak::upgrade(weak)?, *r)))
+            .collect()
     })
 }
 
@@ -258,7 +361,7 @@ pub fn notify_diagnostics(diagnostics: &[slint_interpreter::Diagnostic]) -> Opti
         return Some(());
     };
 
-    let lsp_diags = crate::preview::convert_diagnostics(diagnostics);
+    let lsp_diags = crate::preview::diagnostics_to_publish(diagnostics);
 
     for (url, diagnostics) in lsp_diags {
         crate::preview::notify_lsp_diagnostics(&sender, url, diagnostics)?;
@@ -266,12 +369,79 @@ pub fn notify_diagnostics(diagnostics: &[slint_interpreter::Diagnostic]) -> Opti
     Some(())
 }
 
-pub fn set_show_preview_ui(show_preview_ui: bool) {
+pub fn set_show_preview_ui(show_preview_ui: bool, show_selections: bool) {
+    run_in_ui_thread(move || async move {
+        PREVIEW_STATE.with(|preview_state| {
+            let preview_state = preview_state.borrow();
+            if let Some(ui) = &preview_state.ui {
+                ui.set_show_preview_ui(show_preview_ui);
+                ui.set_show_selections_without_ui(show_selections);
+            }
+        })
+    });
+}
+
+pub fn set_view_config(config: crate::common::PreviewConfig) {
+    run_in_ui_thread(move || async move {
+        PREVIEW_STATE.with(|preview_state| {
+            let preview_state = preview_state.borrow();
+            if let Some(ui) = &preview_state.ui {
+                super::set_ui_view_config(ui, &config)
+            }
+        })
+    });
+}
+
+/// Show the component of `factory` in the preview area, returning the factory shown so far.
+/// Returns None if the preview is not open.
+///
+/// This must be run in the UI thread.
+pub fn replace_preview_area(factory: slint::ComponentFactory) -> Option<slint::ComponentFactory> {
+    PREVIEW_STATE.with(|preview_state| {
+        let preview_state = preview_state.borrow();
+        let ui = preview_state.ui.as_ref()?;
+        let previous = ui.get_preview_area();
+        ui.set_preview_area(factory);
+        Some(previous)
+    })
+}
+
+/// Show `factory` instead of the previewed component, which could not be built
+pub fn show_placeholder(factory: slint::ComponentFactory) {
+    PREVIEW_STATE.with(|preview_state| {
+        let preview_state = preview_state.borrow();
+        if let Some(ui) = &preview_state.ui {
+            preview_state.handle.replace(None);
+            super::set_placeholder_factory(ui, factory);
+        }
+    })
+}
+
+pub fn update_last_good_preview(available: bool, shown: bool) {
+    PREVIEW_STATE.with(|preview_state| {
+        let preview_state = preview_state.borrow();
+        if let Some(ui) = &preview_state.ui {
+            ui.set_has_last_good_preview(available);
+            ui.set_showing_last_good_preview(shown);
+        }
+    })
+}
+
+pub fn update_isolation(isolated: bool, mask: Option<LogicalRect>) {
+    PREVIEW_STATE.with(|preview_state| {
+        let preview_state = preview_state.borrow();
+        if let Some(ui) = &preview_state.ui {
+            super::set_ui_isolation(ui, isolated, mask)
+        }
+    })
+}
+
+pub fn update_design_mode(enabled: bool) {
     run_in_ui_thread(move || async move {
         PREVIEW_STATE.with(|preview_state| {
             let preview_state = preview_state.borrow();
             if let Some(ui) = &preview_state.ui {
-                ui.set_show_preview_ui(show_preview_ui)
+                ui.set_design_mode(enabled)
             }
         })
     });
@@ -297,6 +467,17 @@ pub fn get_current_style() -> String {
     })
 }
 
+pub fn set_known_components(components: Vec<String>, current: String) {
+    PREVIEW_STATE.with(move |preview_state| {
+        let preview_state = preview_state.borrow_mut();
+        if let Some(ui) = &preview_state.ui {
+            let model = VecModel::from(components.into_iter().map(Into::into).collect::<Vec<_>>());
+            ui.set_known_components(Rc::new(model).into());
+            ui.set_current_component(current.into());
+        }
+    });
+}
+
 pub fn set_status_text(text: &str) {
     let text = text.to_string();
 
@@ -334,6 +515,148 @@ pub fn send_status(message: &str, health: Health) {
     crate::preview::send_status_notification(&sender, message, health)
 }
 
+pub fn send_diagnostics_report(report: String) {
+    let Some(sender) = SERVER_NOTIFIER.get_or_init(Default::default).lock().unwrap().clone() else {
+        return;
+    };
+
+    crate::preview::send_log_message_notification(&sender, &report)
+}
+
+pub fn notify_element_selected(file: String, selection: lsp_types::Range) {
+    let Some(sender) = SERVER_NOTIFIER.get_or_init(Default::default).lock().unwrap().clone() else {
+        return;
+    };
+
+    crate::preview::send_element_selected_notification(&sender, &file, selection)
+}
+
+pub fn notify_dependencies(files: Vec<PathBuf>) {
+    let Some(sender) = SERVER_NOTIFIER.get_or_init(Default::default).lock().unwrap().clone() else {
+        return;
+    };
+
+    crate::preview::send_dependencies_notification(&sender, &files)
+}
+
+/// How often the images and fonts used by the preview are checked for changes
+const RESOURCE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+#[derive(Default)]
+struct ResourceWatcher {
+    timer: slint::Timer,
+    /// The watched files with their modification time when last checked
+    modified: std::collections::HashMap<PathBuf, Option<std::time::SystemTime>>,
+}
+
+thread_local! {static RESOURCE_WATCHER: RefCell<ResourceWatcher> = Default::default();}
+
+fn modification_time(path: &std::path::Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Watch `paths` for changes instead of the files watched so far
+///
+/// Editors do not necessarily tell about changes of files that are not open, so poll.
+pub fn watch_resources(paths: Vec<PathBuf>) {
+    RESOURCE_WATCHER.with(|watcher| {
+        let mut watcher = watcher.borrow_mut();
+        watcher.modified = paths
+            .into_iter()
+            .map(|path| {
+                let time = modification_time(&path);
+                (path, time)
+            })
+            .collect();
+
+        if watcher.modified.is_empty() {
+            watcher.timer.stop();
+        } else if !watcher.timer.running() {
+            watcher.timer.start(
+                slint::TimerMode::Repeated,
+                RESOURCE_POLL_INTERVAL,
+                check_watched_resources,
+            );
+        }
+    })
+}
+
+fn check_watched_resources() {
+    let changed = RESOURCE_WATCHER.with(|watcher| {
+        watcher
+            .borrow_mut()
+            .modified
+            .iter_mut()
+            .filter_map(|(path, time)| {
+                let current = modification_time(path);
+                (current != *time).then(|| {
+                    *time = current;
+                    path.clone()
+                })
+            })
+            .collect::<Vec<_>>()
+    });
+    if !changed.is_empty() {
+        super::resources_changed(changed);
+    }
+}
+
+/// Decode the images at `paths` again and put them into the image cache of Slint
+pub fn refresh_cached_images(paths: &[PathBuf]) {
+    use i_slint_core::graphics::{ImageCacheKey, ImageInner, SharedImageBuffer, SharedPixelBuffer};
+
+    for path in paths {
+        // SVGs and fonts end up here as well, those are left alone
+        let Ok(image) = image::open(path) else {
+            continue;
+        };
+        let image = image.to_rgba8();
+        let buffer = SharedImageBuffer::RGBA8(SharedPixelBuffer::clone_from_slice(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+        ));
+        let cache_key = ImageCacheKey::Path(path.to_string_lossy().as_ref().into());
+        i_slint_core::graphics::cache::replace_cached_image(
+            cache_key.clone(),
+            ImageInner::EmbeddedImage { cache_key, buffer },
+        );
+    }
+}
+
+pub fn notify_style(style: String) {
+    let Some(sender) = SERVER_NOTIFIER.get_or_init(Default::default).lock().unwrap().clone() else {
+        return;
+    };
+
+    let available_styles = super::ui::known_styles().iter().map(|s| s.to_string()).collect();
+    crate::preview::send_style_notification(&sender, style, available_styles)
+}
+
+pub fn notify_component_size(size: crate::lsp_ext::PreviewComponentSizeParams) {
+    let Some(sender) = SERVER_NOTIFIER.get_or_init(Default::default).lock().unwrap().clone() else {
+        return;
+    };
+
+    crate::preview::send_component_size_notification(&sender, size)
+}
+
+pub fn notify_loading(loading: bool) {
+    let Some(sender) = SERVER_NOTIFIER.get_or_init(Default::default).lock().unwrap().clone() else {
+        return;
+    };
+
+    crate::preview::send_loading_notification(&sender, loading)
+}
+
+pub fn notify_resource_urls(resources: Vec<crate::lsp_ext::ResourceUrl>) {
+    let Some(sender) = SERVER_NOTIFIER.get_or_init(Default::default).lock().unwrap().clone() else {
+        return;
+    };
+
+    crate::preview::send_resource_urls_notification(&sender, resources)
+}
+
 pub fn ask_editor_to_show_document(file: String, selection: lsp_types::Range) {
     let Some(sender) = SERVER_NOTIFIER.get_or_init(Default::default).lock().unwrap().clone() else {
         return;
@@ -344,8 +667,51 @@ pub fn ask_editor_to_show_document(file: String, selection: lsp_types::Range) {
     slint_interpreter::spawn_local(fut).unwrap(); // Fire and forget.
 }
 
+/// Send `edit` to the editor, `done` gets called with whether it was applied
+pub fn send_workspace_edit(
+    label: String,
+    edit: lsp_types::WorkspaceEdit,
+    confirm: bool,
+    done: impl FnOnce(bool) + 'static,
+) {
+    let Some(sender) = SERVER_NOTIFIER.get_or_init(Default::default).lock().unwrap().clone() else {
+        return;
+    };
+
+    let fut = crate::send_workspace_edit(sender, Some(label), edit, confirm);
+
+    slint_interpreter::spawn_local(async move {
+        let applied = fut.await;
+        if !applied {
+            send_status("The change was not applied to the source code", Health::Warning);
+        }
+        done(applied);
+    })
+    .unwrap(); // Fire and forget.
+}
+
+/// Set the title and the icon of the preview window, the default icon when `icon` is None
+pub fn set_window_title_and_icon(title: String, icon: Option<PathBuf>) {
+    let icon = icon.and_then(|path| match slint::Image::load_from_path(&path) {
+        Ok(image) => Some(image),
+        Err(_) => {
+            send_status(
+                &format!("Cannot load the window icon {}", path.display()),
+                Health::Warning,
+            );
+            None
+        }
+    });
+    PREVIEW_STATE.with(|preview_state| {
+        if let Some(ui) = &preview_state.borrow().ui {
+            ui.set_window_title(title.into());
+            ui.set_window_icon(icon.unwrap_or_default());
+        }
+    });
+}
+
 /// This runs `set_preview_factory` in the UI thread
-pub fn update_preview_area(compiled: ComponentDefinition) {
+pub fn update_preview_area(compiled: ComponentDefinition, keep_viewport: bool) {
     PREVIEW_STATE.with(|preview_state| {
         let mut preview_state = preview_state.borrow_mut();
 
@@ -357,6 +723,7 @@ pub fn update_preview_area(compiled: ComponentDefinition) {
         super::set_preview_factory(
             ui,
             compiled,
+            keep_viewport,
             Box::new(move |instance| {
                 shared_handle.replace(Some(instance));
             }),
@@ -379,7 +746,13 @@ pub fn update_highlight(path: PathBuf, offset: u32) {
 
         if let Some(handle) = handle {
             let element_positions = handle.component_positions(path, offset);
-            set_selected_element(None, element_positions);
+            let reveal = element_positions.geometries.first().cloned();
+            super::set_selected_element(None, element_positions);
+            if let Some(area) = reveal {
+                PREVIEW_STATE.with(|preview_state| {
+                    super::reveal_area(preview_state.borrow().ui.as_ref(), area)
+                });
+            }
         }
     })
 }
@@ -15,11 +15,7 @@ pub fn create_ui(style: String) -> Result<PreviewUi, PlatformError> {
     let ui = PreviewUi::new()?;
 
     // styles:
-    let known_styles = once(&"native")
-        .chain(i_slint_compiler::fileaccess::styles().iter())
-        .filter(|s| s != &&"qt" || i_slint_backend_selector::HAS_NATIVE_STYLE)
-        .cloned()
-        .collect::<Vec<_>>();
+    let known_styles = known_styles();
     let style = if known_styles.contains(&style.as_str()) {
         style
     } else {
@@ -36,6 +32,9 @@ pub fn create_ui(style: String) -> Result<PreviewUi, PlatformError> {
     ui.on_style_changed(super::change_style);
     ui.set_known_styles(style_model.into());
     ui.set_current_style(style.clone().into());
+    ui.on_component_changed(super::change_component);
+    ui.on_pinned_changed(super::set_preview_pinned);
+    ui.on_design_mode_changed(super::design_mode_changed);
 
     ui.on_show_document(|url, line, column| {
         use lsp_types::{Position, Range};
@@ -45,10 +44,57 @@ pub fn create_ui(style: String) -> Result<PreviewUi, PlatformError> {
     });
     ui.on_select_at(super::select_element_at);
     ui.on_select_into(super::select_element_into);
+    ui.on_select_parent(super::select_parent_element);
+    ui.on_select_first_child(super::select_first_child_element);
+    ui.on_select_sibling(super::select_sibling_element);
+    ui.on_add_to_selection_at(super::add_element_to_selection_at);
+    ui.on_copy_selected_element_location(super::copy_selected_element_location);
+    ui.on_outline_item_selected(super::select_outline_item);
+    ui.on_hover_at(super::hover_element_at);
+    ui.on_move_selected_element(super::move_selected_element);
+    ui.on_resize_selected_element(super::resize_selected_element);
+    ui.on_nudge_selected_element(super::nudge_selected_element);
+    ui.on_insert_component(super::insert_component);
+    ui.on_delete_selected_element(super::delete_selected_element);
+    ui.on_duplicate_selected_element(super::duplicate_selected_element);
+    ui.on_snap_geometry(super::snap_geometry);
+    ui.on_measure_at(super::measure_at);
+    ui.on_zoom_in(super::zoom_in);
+    ui.on_zoom_out(super::zoom_out);
+    ui.on_zoom_reset(super::zoom_reset);
+    ui.on_zoom_to_fit(super::zoom_to_fit);
+    ui.on_multi_viewport_changed(super::multi_viewport_changed);
+    ui.on_activate_viewport(super::activate_viewport);
+    ui.on_isolate_changed(super::isolate_changed);
+    ui.on_last_good_preview_changed(super::show_last_good_preview);
+    ui.on_undo_preview_edit(super::undo_last_preview_edit);
+    ui.on_redo_preview_edit(super::redo_preview_edit);
+    ui.set_checkerboard(checkerboard());
 
     Ok(ui)
 }
 
+/// The styles the preview can use
+pub fn known_styles() -> Vec<&'static str> {
+    once(&"native")
+        .chain(i_slint_compiler::fileaccess::styles().iter())
+        .filter(|s| s != &&"qt" || i_slint_backend_selector::HAS_NATIVE_STYLE)
+        .cloned()
+        .collect()
+}
+
+/// An image with one pixel per checkerboard tile, meant to be scaled up without smoothing.
+fn checkerboard() -> slint::Image {
+    const SIZE: u32 = 512;
+    let mut buffer = slint::SharedPixelBuffer::<slint::Rgb8Pixel>::new(SIZE, SIZE);
+    for (i, pixel) in buffer.make_mut_slice().iter_mut().enumerate() {
+        let (x, y) = (i as u32 % SIZE, i as u32 / SIZE);
+        let v = if (x + y) % 2 == 0 { 0xff } else { 0xcc };
+        *pixel = slint::Rgb8Pixel { r: v, g: v, b: v };
+    }
+    slint::Image::from_rgb8(buffer)
+}
+
 pub fn convert_diagnostics(diagnostics: &[slint_interpreter::Diagnostic]) -> Vec<Diagnostics> {
     diagnostics
         .iter()
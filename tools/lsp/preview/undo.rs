@@ -0,0 +1,137 @@
+// Copyright © SixtyFPS GmbH <info@slint.dev>
+// SPDX-License-Identifier: GPL-3.0-only OR LicenseRef-Slint-Royalty-free-1.1 OR LicenseRef-Slint-commercial
+
+//! The history of the source code changes done in the preview, to undo and redo them
+
+use std::path::PathBuf;
+
+/// How many changes can be undone
+const MAX_UNDO_STEPS: usize = 100;
+
+/// Changes with the same label to the same file done within this many milliseconds of each
+/// other are undone together, e.g. when dragging an element several times in a row.
+const COALESCE_MS: u64 = 1000;
+
+/// A change to the source code of one file, done by the preview
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceChange {
+    pub label: String,
+    pub path: PathBuf,
+    pub before: String,
+    pub after: String,
+    /// When the change was done, in milliseconds
+    pub time: u64,
+}
+
+#[derive(Default)]
+pub struct EditHistory {
+    undo: Vec<SourceChange>,
+    redo: Vec<SourceChange>,
+}
+
+impl EditHistory {
+    /// Remember a change that was just done. This drops the changes that could be redone.
+    pub fn push(&mut self, change: SourceChange) {
+        self.redo.clear();
+        if let Some(last) = self.undo.last_mut() {
+            if last.label == change.label
+                && last.path == change.path
+                && last.after == change.before
+                && change.time.saturating_sub(last.time) < COALESCE_MS
+            {
+                last.after = change.after;
+                last.time = change.time;
+                return;
+            }
+        }
+        self.undo.push(change);
+        if self.undo.len() > MAX_UNDO_STEPS {
+            self.undo.remove(0);
+        }
+    }
+
+    /// The change `undo` returns next, without undoing it
+    pub fn next_undo(&self) -> Option<&SourceChange> {
+        self.undo.last()
+    }
+
+    /// The change `redo` returns next, without redoing it
+    pub fn next_redo(&self) -> Option<&SourceChange> {
+        self.redo.last()
+    }
+
+    /// The change to undo, which can then be redone
+    pub fn undo(&mut self) -> Option<SourceChange> {
+        let change = self.undo.pop()?;
+        self.redo.push(change.clone());
+        Some(change)
+    }
+
+    /// The change to redo, which can then be undone again
+    pub fn redo(&mut self) -> Option<SourceChange> {
+        let change = self.redo.pop()?;
+        self.undo.push(change.clone());
+        Some(change)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(label: &str, before: &str, after: &str, time: u64) -> SourceChange {
+        SourceChange {
+            label: label.into(),
+            path: PathBuf::from("/test.slint"),
+            before: before.into(),
+            after: after.into(),
+            time,
+        }
+    }
+
+    #[test]
+    fn test_edit_history() {
+        let mut history = EditHistory::default();
+        history.push(change("Move element", "a", "b", 0));
+        // Coalesced with the previous change
+        history.push(change("Move element", "b", "c", 500));
+        // Too late to be coalesced
+        history.push(change("Move element", "c", "d", 2000));
+        // Different label
+        history.push(change("Resize element", "d", "e", 2100));
+
+        assert_eq!(history.undo(), Some(change("Resize element", "d", "e", 2100)));
+        assert_eq!(history.undo(), Some(change("Move element", "c", "d", 2000)));
+        assert_eq!(history.redo(), Some(change("Move element", "c", "d", 2000)));
+        assert_eq!(history.undo(), Some(change("Move element", "c", "d", 2000)));
+        assert_eq!(history.undo(), Some(change("Move element", "a", "c", 500)));
+        assert_eq!(history.undo(), None);
+
+        // A new change drops what could be redone
+        history.push(change("Move element", "a", "x", 3000));
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo(), Some(change("Move element", "a", "x", 3000)));
+    }
+
+    #[test]
+    fn test_edit_history_next() {
+        let mut history = EditHistory::default();
+        assert_eq!(history.next_undo(), None);
+        history.push(change("Move element", "a", "b", 0));
+        history.push(change("Resize element", "b", "c", 100));
+
+        // Looking at the next step does not take it
+        assert_eq!(history.next_undo(), Some(&change("Resize element", "b", "c", 100)));
+        assert_eq!(history.next_undo(), Some(&change("Resize element", "b", "c", 100)));
+        assert_eq!(history.next_redo(), None);
+
+        history.undo();
+        assert_eq!(history.next_undo(), Some(&change("Move element", "a", "b", 0)));
+        assert_eq!(history.next_redo(), Some(&change("Resize element", "b", "c", 100)));
+    }
+}
@@ -46,17 +46,33 @@ struct PreviewState {
     handle: Rc<RefCell<Option<slint_interpreter::ComponentInstance>>>,
     lsp_notifier: Option<SignalLspFunction>,
     resource_url_mapper: Option<ResourceUrlMapperFunction>,
-    selected_element: Option<ElementWeak>,
+    selected_elements: Vec<(ElementWeak, LogicalRect)>,
 }
 thread_local! {static PREVIEW_STATE: std::cell::RefCell<PreviewState> = Default::default();}
 
-pub fn selected_element() -> Option<ElementRc> {
+pub fn set_hovered_element(hovered: Option<(ElementRc, LogicalRect)>) {
     PREVIEW_STATE.with(move |preview_state| {
         let preview_state = preview_state.borrow();
-        let Some(weak) = &preview_state.selected_element else {
-            return None;
-        };
-        Weak::upgrade(&weak)
+        super::set_hover(preview_state.ui.as_ref(), hovered.as_ref());
+    })
+}
+
+pub fn refresh_root_properties(component_instance: &ComponentInstance) {
+    PREVIEW_STATE.with(move |preview_state| {
+        if let Some(ui) = &preview_state.borrow().ui {
+            super::set_root_properties(ui, component_instance);
+        }
+    })
+}
+
+pub fn selected_elements() -> Vec<(ElementRc, LogicalRect)> {
+    PREVIEW_STATE.with(move |preview_state| {
+        let preview_state = preview_state.borrow();
+        preview_state
+            .selected_elements
+            .iter()
+            .filter_map(|(weak, r)| Some((Weak::upgrade(weak)?, *r)))
+            .collect()
     })
 }
 
@@ -66,19 +82,19 @@ pub fn component_instance() -> Option<ComponentInstance> {
     })
 }
 
-pub fn set_selected_element(
-    element_position: Option<(&ElementRc, LogicalRect)>,
-    positions: ComponentPositions,
-) {
+pub fn set_selected_elements(elements: &[(ElementRc, LogicalRect)], positions: ComponentPositions) {
     PREVIEW_STATE.with(move |preview_state| {
         let mut preview_state = preview_state.borrow_mut();
-        if let Some((e, _)) = element_position.as_ref() {
-            preview_state.selected_element = Some(Rc::downgrade(e));
-        } else {
-            preview_state.selected_element = None;
-        }
-
-        super::set_selections(preview_state.ui.as_ref(), element_position, positions);
+        preview_state.selected_elements =
+            elements.iter().map(|(e, r)| (Rc::downgrade(e), *r)).collect();
+
+        let component_instance = preview_state.handle.borrow().as_ref().map(|ci| ci.clone_strong());
+        super::set_selections(
+            preview_state.ui.as_ref(),
+            component_instance.as_ref(),
+            elements,
+            positions,
+        );
     })
 }
 
@@ -112,6 +128,12 @@ pub fn create(
                     } else {
                         match super::ui::create_ui(style) {
                             Ok(ui) => {
+                                let (view_config, design_mode) = {
+                                    let cache = super::CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
+                                    (cache.config.clone(), cache.design_mode)
+                                };
+                                super::set_ui_view_config(&ui, &view_config);
+                                ui.set_design_mode(design_mode);
                                 preview_state.borrow_mut().ui = Some(ui);
                                 resolve.take().call1(&JsValue::UNDEFINED,
                                     &JsValue::from(Self { })).unwrap_throw()
@@ -145,6 +167,7 @@ pub fn show_ui(&self) -> Result<js_sys::Promise, JsValue> {
             let mut cache = super::CONTENT_CACHE.get_or_init(Default::default).lock().unwrap();
             cache.ui_is_visible = true;
         }
+        super::reload_last_component();
         invoke_from_event_loop_wrapped_in_promise(|instance| instance.show())
     }
 
@@ -159,19 +182,110 @@ pub fn process_lsp_to_preview_message(&self, value: JsValue) -> Result<(), JsVal
                 super::set_contents(&PathBuf::from(&path), contents);
                 Ok(())
             }
+            M::RemoveContents { path } => {
+                super::remove_contents(&PathBuf::from(&path));
+                Ok(())
+            }
+            M::CursorMoved { path, line, column } => {
+                super::cursor_moved(PathBuf::from(path), line, column);
+                Ok(())
+            }
             M::SetConfiguration { config } => {
                 super::config_changed(config);
                 Ok(())
             }
-            M::ShowPreview { path, component, style } => {
-                let pc = PreviewComponent { path: PathBuf::from(path), component, style };
+            M::ShowPreview { path, component, style, wrapper_body } => {
+                let pc =
+                    PreviewComponent { path: PathBuf::from(path), component, style, wrapper_body };
                 super::load_preview(pc);
                 Ok(())
             }
+            M::ShowPreviewFromSource { path, component, style, contents } => {
+                let pc = PreviewComponent {
+                    path: PathBuf::from(path),
+                    component,
+                    style,
+                    wrapper_body: None,
+                };
+                super::load_preview_from_source(pc, contents);
+                Ok(())
+            }
             M::HighlightFromEditor { path, offset } => {
                 super::highlight(&path.map(PathBuf::from), offset);
                 Ok(())
             }
+            M::HighlightAtLineColumn { path, line, column } => {
+                super::highlight_at_line_column(&PathBuf::from(path), line, column);
+                Ok(())
+            }
+            M::HighlightRange { path, start_offset, end_offset } => {
+                super::highlight_range(PathBuf::from(path), start_offset, end_offset);
+                Ok(())
+            }
+            M::ForceReload => {
+                super::force_reload();
+                Ok(())
+            }
+            M::SetProperty { name, value } => {
+                super::set_preview_property(name, value);
+                Ok(())
+            }
+            M::InvokeCallback { name, args } => {
+                super::invoke_preview_callback(name, args);
+                Ok(())
+            }
+            M::HighlightMatching { query } => {
+                super::highlight_matching(query);
+                Ok(())
+            }
+            M::ShowNextDiagnostic { backwards } => {
+                super::show_next_diagnostic(backwards);
+                Ok(())
+            }
+            M::SetDesignMode { enabled } => {
+                super::set_design_mode(enabled);
+                Ok(())
+            }
+            M::SetPresentationMode { enabled } => {
+                super::set_presentation_mode(enabled);
+                Ok(())
+            }
+            M::SetReferenceImage { image } => {
+                super::set_reference_image(image);
+                Ok(())
+            }
+            M::SetPreviewUiVisible { visible, show_selections } => {
+                super::set_preview_ui_visible(visible, show_selections);
+                Ok(())
+            }
+            M::UndoPreviewEdit => {
+                super::undo_last_preview_edit();
+                Ok(())
+            }
+            M::RedoPreviewEdit => {
+                super::redo_preview_edit();
+                Ok(())
+            }
+            M::ResourcesChanged { paths } => {
+                super::resources_changed(paths.into_iter().map(PathBuf::from).collect());
+                Ok(())
+            }
+            M::SelfTest => {
+                super::self_test();
+                Ok(())
+            }
+            M::ToggleLastGoodPreview => {
+                super::toggle_last_good_preview();
+                Ok(())
+            }
+            M::RecompileVerbose => {
+                super::recompile_verbose();
+                Ok(())
+            }
+            M::WorkspaceEditApplied { id, applied } => {
+                workspace_edit_applied(id, applied);
+                Ok(())
+            }
         }
     }
 }
@@ -268,11 +382,82 @@ pub fn send_message_to_lsp(message: crate::common::PreviewToLspMessage) {
     })
 }
 
-pub fn set_show_preview_ui(show_preview_ui: bool) {
+/// The web preview has no window of its own to set the title and icon of
+pub fn set_window_title_and_icon(_title: String, _icon: Option<PathBuf>) {}
+
+/// The web preview renders with what the browser offers, whatever is configured
+pub fn renderer_changed(renderer: crate::common::PreviewRenderer) {
+    if renderer != crate::common::PreviewRenderer::Default {
+        send_status("The web preview can not change its renderer", Health::Warning);
+    }
+}
+
+pub fn set_show_preview_ui(show_preview_ui: bool, show_selections: bool) {
+    PREVIEW_STATE.with(move |preview_state| {
+        let preview_state = preview_state.borrow_mut();
+        if let Some(ui) = &preview_state.ui {
+            ui.set_show_preview_ui(show_preview_ui);
+            ui.set_show_selections_without_ui(show_selections);
+        }
+    });
+}
+
+pub fn set_view_config(config: crate::common::PreviewConfig) {
     PREVIEW_STATE.with(move |preview_state| {
         let preview_state = preview_state.borrow_mut();
         if let Some(ui) = &preview_state.ui {
-            ui.set_show_preview_ui(show_preview_ui)
+            super::set_ui_view_config(ui, &config)
+        }
+    });
+}
+
+/// Show the component of `factory` in the preview area, returning the factory shown so far.
+/// Returns None if the preview is not open.
+pub fn replace_preview_area(factory: slint::ComponentFactory) -> Option<slint::ComponentFactory> {
+    PREVIEW_STATE.with(|preview_state| {
+        let preview_state = preview_state.borrow();
+        let ui = preview_state.ui.as_ref()?;
+        let previous = ui.get_preview_area();
+        ui.set_preview_area(factory);
+        Some(previous)
+    })
+}
+
+/// Show `factory` instead of the previewed component, which could not be built
+pub fn show_placeholder(factory: slint::ComponentFactory) {
+    PREVIEW_STATE.with(|preview_state| {
+        let preview_state = preview_state.borrow();
+        if let Some(ui) = &preview_state.ui {
+            preview_state.handle.replace(None);
+            super::set_placeholder_factory(ui, factory);
+        }
+    })
+}
+
+pub fn update_last_good_preview(available: bool, shown: bool) {
+    PREVIEW_STATE.with(|preview_state| {
+        let preview_state = preview_state.borrow();
+        if let Some(ui) = &preview_state.ui {
+            ui.set_has_last_good_preview(available);
+            ui.set_showing_last_good_preview(shown);
+        }
+    })
+}
+
+pub fn update_isolation(isolated: bool, mask: Option<LogicalRect>) {
+    PREVIEW_STATE.with(|preview_state| {
+        let preview_state = preview_state.borrow();
+        if let Some(ui) = &preview_state.ui {
+            super::set_ui_isolation(ui, isolated, mask)
+        }
+    })
+}
+
+pub fn update_design_mode(enabled: bool) {
+    PREVIEW_STATE.with(move |preview_state| {
+        let preview_state = preview_state.borrow_mut();
+        if let Some(ui) = &preview_state.ui {
+            ui.set_design_mode(enabled)
         }
     });
 }
@@ -297,6 +482,17 @@ pub fn get_current_style() -> String {
     })
 }
 
+pub fn set_known_components(components: Vec<String>, current: String) {
+    PREVIEW_STATE.with(move |preview_state| {
+        let preview_state = preview_state.borrow_mut();
+        if let Some(ui) = &preview_state.ui {
+            let model = VecModel::from(components.into_iter().map(Into::into).collect::<Vec<_>>());
+            ui.set_known_components(Rc::new(model).into());
+            ui.set_current_component(current.into());
+        }
+    });
+}
+
 pub fn set_status_text(text: &str) {
     let text = text.to_string();
 
@@ -335,7 +531,7 @@ pub fn send_status(message: &str, health: Health) {
 
 pub fn notify_diagnostics(diagnostics: &[slint_interpreter::Diagnostic]) -> Option<()> {
     set_diagnostics(diagnostics);
-    let diags = crate::preview::convert_diagnostics(diagnostics);
+    let diags = crate::preview::diagnostics_to_publish(diagnostics);
 
     for (uri, diagnostics) in diags {
         send_message_to_lsp(crate::common::PreviewToLspMessage::Diagnostics { uri, diagnostics });
@@ -343,11 +539,88 @@ pub fn notify_diagnostics(diagnostics: &[slint_interpreter::Diagnostic]) -> Opti
     Some(())
 }
 
+pub fn send_diagnostics_report(report: String) {
+    send_message_to_lsp(crate::common::PreviewToLspMessage::DiagnosticsReport { report });
+}
+
+pub fn notify_element_selected(file: String, selection: lsp_types::Range) {
+    send_message_to_lsp(crate::common::PreviewToLspMessage::ElementSelected { file, selection })
+}
+
+pub fn notify_dependencies(files: Vec<PathBuf>) {
+    send_message_to_lsp(crate::common::PreviewToLspMessage::Dependencies { files })
+}
+
+thread_local! {
+    /// The last id given to a workspace edit, and what to call once the LSP reports whether
+    /// the workspace edits with the other ids got applied
+    static PENDING_WORKSPACE_EDITS: RefCell<(u64, std::collections::HashMap<u64, Box<dyn FnOnce(bool)>>)> =
+        Default::default();
+}
+
+/// Send `edit` to the editor, `done` gets called with whether it was applied
+pub fn send_workspace_edit(
+    label: String,
+    edit: lsp_types::WorkspaceEdit,
+    confirm: bool,
+    done: impl FnOnce(bool) + 'static,
+) {
+    let id = PENDING_WORKSPACE_EDITS.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        pending.0 += 1;
+        let id = pending.0;
+        pending.1.insert(id, Box::new(done));
+        id
+    });
+    send_message_to_lsp(crate::common::PreviewToLspMessage::SendWorkspaceEdit {
+        id,
+        label: Some(label),
+        edit,
+        confirm,
+    })
+}
+
+fn workspace_edit_applied(id: u64, applied: bool) {
+    let Some(done) = PENDING_WORKSPACE_EDITS.with(|pending| pending.borrow_mut().1.remove(&id))
+    else {
+        return;
+    };
+    if !applied {
+        send_status("The change was not applied to the source code", Health::Warning);
+    }
+    done(applied);
+}
+
+pub fn watch_resources(_paths: Vec<PathBuf>) {
+    // There is no file system to watch, the editor reports changed resources
+}
+
+pub fn refresh_cached_images(_paths: &[PathBuf]) {
+    // Images are loaded by the browser from the URLs of the resource URL mapper
+}
+
+pub fn notify_style(style: String) {
+    let available_styles = super::ui::known_styles().iter().map(|s| s.to_string()).collect();
+    send_message_to_lsp(crate::common::PreviewToLspMessage::Style { style, available_styles })
+}
+
+pub fn notify_component_size(size: crate::lsp_ext::PreviewComponentSizeParams) {
+    send_message_to_lsp(crate::common::PreviewToLspMessage::ComponentSize { size })
+}
+
+pub fn notify_loading(loading: bool) {
+    send_message_to_lsp(crate::common::PreviewToLspMessage::Loading { loading })
+}
+
+pub fn notify_resource_urls(resources: Vec<crate::lsp_ext::ResourceUrl>) {
+    send_message_to_lsp(crate::common::PreviewToLspMessage::ResourceUrls { resources })
+}
+
 pub fn ask_editor_to_show_document(file: String, selection: lsp_types::Range) {
     send_message_to_lsp(crate::common::PreviewToLspMessage::ShowDocument { file, selection })
 }
 
-pub fn update_preview_area(compiled: slint_interpreter::ComponentDefinition) {
+pub fn update_preview_area(compiled: slint_interpreter::ComponentDefinition, keep_viewport: bool) {
     PREVIEW_STATE.with(|preview_state| {
         let preview_state = preview_state.borrow_mut();
 
@@ -357,6 +630,7 @@ pub fn update_preview_area(compiled: slint_interpreter::ComponentDefinition) {
         super::set_preview_factory(
             ui,
             compiled,
+            keep_viewport,
             Box::new(move |instance| {
                 shared_handle.replace(Some(instance));
             }),
@@ -375,7 +649,13 @@ pub fn update_highlight(path: PathBuf, offset: u32) {
 
         if let Some(handle) = handle {
             let element_positions = handle.component_positions(path, offset);
-            set_selected_element(None, element_positions);
+            let reveal = element_positions.geometries.first().cloned();
+            super::set_selected_element(None, element_positions);
+            if let Some(area) = reveal {
+                PREVIEW_STATE.with(|preview_state| {
+                    super::reveal_area(preview_state.borrow().ui.as_ref(), area)
+                });
+            }
         }
     })
     .unwrap();
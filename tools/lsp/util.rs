@@ -276,3 +276,85 @@ fn to_lsp_diag_level(level: DiagnosticLevel) -> lsp_types::DiagnosticSeverity {
         _ => lsp_types::DiagnosticSeverity::INFORMATION,
     }
 }
+
+/// Returns the names of the components exported from `source_code`, in declaration order
+///
+/// This only looks at the syntax, so it also works for documents that fail to compile.
+#[cfg(any(
+    feature = "preview-builtin",
+    feature = "preview-external",
+    feature = "preview-engine",
+    test
+))]
+pub fn exported_component_names(source_code: &str, path: &std::path::Path) -> Vec<String> {
+    let mut diag = i_slint_compiler::diagnostics::BuildDiagnostics::default();
+    let doc: syntax_nodes::Document =
+        i_slint_compiler::parser::parse(source_code.to_string(), Some(path), None, &mut diag)
+            .into();
+
+    let is_global = |c: &syntax_nodes::Component| {
+        c.child_text(SyntaxKind::Identifier).map_or(false, |t| t == "global")
+    };
+    let local_components = doc
+        .Component()
+        .chain(doc.ExportsList().flat_map(|e| e.Component()))
+        .filter(|c| !is_global(c))
+        .filter_map(|c| i_slint_compiler::parser::identifier_text(&c.DeclaredIdentifier()))
+        .collect::<std::collections::HashSet<_>>();
+
+    let mut result = Vec::new();
+    for exports in doc.ExportsList() {
+        let names = exports
+            .Component()
+            .filter(|c| !is_global(c))
+            .filter_map(|c| i_slint_compiler::parser::identifier_text(&c.DeclaredIdentifier()))
+            .chain(exports.ExportSpecifier().filter_map(|s| {
+                i_slint_compiler::parser::identifier_text(&s.ExportIdentifier())
+                    .filter(|n| local_components.contains(n))
+            }));
+        for name in names {
+            if !result.contains(&name) {
+                result.push(name);
+            }
+        }
+    }
+
+    if doc.ExportsList().next().is_none() {
+        // The last component is exported implicitly
+        result.extend(
+            doc.Component()
+                .last()
+                .filter(|c| !is_global(c))
+                .and_then(|c| i_slint_compiler::parser::identifier_text(&c.DeclaredIdentifier())),
+        );
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exported_component_names() {
+        let path = std::path::Path::new("/test.slint");
+        assert_eq!(
+            exported_component_names(
+                r#"
+                component Helper { }
+                export global Settings { }
+                export component Main { }
+                component Other { }
+                export { Other, Settings as Config }
+                "#,
+                path
+            ),
+            vec!["Main".to_string(), "Other".to_string()]
+        );
+        assert_eq!(
+            exported_component_names("component First { }\ncomponent Last { }\n", path),
+            vec!["Last".to_string()]
+        );
+        assert!(exported_component_names("", path).is_empty());
+    }
+}
@@ -63,6 +63,28 @@ fn set_contents(&self, path: &std::path::Path, contents: &str) {
         );
     }
 
+    fn remove_contents(&self, path: &std::path::Path) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::RemoveContents {
+                path: path.to_string_lossy().to_string(),
+            },
+        );
+    }
+
+    fn cursor_moved(&self, path: std::path::PathBuf, line: u32, column: u32) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::CursorMoved {
+                path: path.to_string_lossy().to_string(),
+                line,
+                column,
+            },
+        );
+    }
+
     fn load_preview(&self, component: common::PreviewComponent) {
         if component.path.as_os_str().is_empty() {
             return;
@@ -77,6 +99,20 @@ fn load_preview(&self, component: common::PreviewComponent) {
                 path: component.path.to_string_lossy().to_string(),
                 component: component.component,
                 style: component.style.to_string(),
+                wrapper_body: component.wrapper_body,
+            },
+        );
+    }
+
+    fn load_preview_from_source(&self, component: common::PreviewComponent, source: String) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::ShowPreviewFromSource {
+                path: component.path.to_string_lossy().to_string(),
+                component: component.component,
+                style: component.style.to_string(),
+                contents: source,
             },
         );
     }
@@ -100,6 +136,186 @@ fn highlight(&self, path: Option<std::path::PathBuf>, offset: u32) -> Result<()>
         )
     }
 
+    fn highlight_at_line_column(
+        &self,
+        path: std::path::PathBuf,
+        line: u32,
+        column: u32,
+    ) -> Result<()> {
+        #[cfg(feature = "preview-external")]
+        self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::HighlightAtLineColumn {
+                path: path.to_string_lossy().to_string(),
+                line,
+                column,
+            },
+        )
+    }
+
+    fn highlight_range(
+        &self,
+        path: std::path::PathBuf,
+        start_offset: u32,
+        end_offset: u32,
+    ) -> Result<()> {
+        #[cfg(feature = "preview-external")]
+        self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::HighlightRange {
+                path: path.to_string_lossy().to_string(),
+                start_offset,
+                end_offset,
+            },
+        )
+    }
+
+    fn force_reload(&self) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::ForceReload,
+        );
+    }
+
+    fn self_test(&self) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::SelfTest,
+        );
+    }
+
+    fn recompile_verbose(&self) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::RecompileVerbose,
+        );
+    }
+
+    fn toggle_last_good_preview(&self) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::ToggleLastGoodPreview,
+        );
+    }
+
+    fn save_screenshot(&self, _path: std::path::PathBuf) -> Result<()> {
+        Err("Screenshots are not supported by the web preview".into())
+    }
+
+    fn save_render_tree(&self, _path: std::path::PathBuf) -> Result<()> {
+        Err("Saving the render tree is not supported by the web preview".into())
+    }
+
+    fn save_accessibility_tree(&self, _path: std::path::PathBuf) -> Result<()> {
+        Err("Saving the accessibility tree is not supported by the web preview".into())
+    }
+
+    fn compiler_config(&self) -> Result<Option<crate::common::PreviewCompilerConfig>> {
+        Err("Querying the compiler configuration is not supported by the web preview".into())
+    }
+
+    fn is_loading(&self) -> Result<bool> {
+        Err("Querying the loading state is not supported by the web preview".into())
+    }
+
+    fn cached_files(&self) -> Result<Vec<(std::path::PathBuf, bool)>> {
+        Err("Listing the cached files is not supported by the web preview".into())
+    }
+
+    fn set_preview_property(&self, name: String, value: serde_json::Value) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::SetProperty { name, value },
+        );
+    }
+
+    fn invoke_preview_callback(&self, name: String, args: Vec<serde_json::Value>) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::InvokeCallback { name, args },
+        );
+    }
+
+    fn highlight_matching(&self, query: String) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::HighlightMatching { query },
+        );
+    }
+
+    fn show_next_diagnostic(&self, backwards: bool) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::ShowNextDiagnostic { backwards },
+        );
+    }
+
+    fn set_design_mode(&self, enabled: bool) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::SetDesignMode { enabled },
+        );
+    }
+
+    fn set_presentation_mode(&self, enabled: bool) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::SetPresentationMode { enabled },
+        );
+    }
+
+    fn set_reference_image(&self, image: Option<crate::common::ReferenceImage>) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::SetReferenceImage { image },
+        );
+    }
+
+    fn set_preview_ui_visible(&self, visible: bool, show_selections: bool) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::SetPreviewUiVisible { visible, show_selections },
+        );
+    }
+
+    fn undo_last_preview_edit(&self) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::UndoPreviewEdit,
+        );
+    }
+
+    fn redo_preview_edit(&self) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::RedoPreviewEdit,
+        );
+    }
+
+    fn resources_changed(&self, paths: Vec<std::path::PathBuf>) {
+        #[cfg(feature = "preview-external")]
+        let _ = self.server_notifier.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::ResourcesChanged {
+                paths: paths.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+            },
+        );
+    }
+
     fn current_component(&self) -> Option<crate::common::PreviewComponent> {
         self.to_show.borrow().clone()
     }
@@ -269,7 +485,14 @@ pub fn create(
     language::register_request_handlers(&mut rh);
 
     Ok(SlintServer {
-        ctx: Rc::new(Context { document_cache, init_param, server_notifier, preview }),
+        ctx: Rc::new(Context {
+            document_cache,
+            init_param,
+            server_notifier,
+            preview,
+            #[cfg(any(feature = "preview-builtin", feature = "preview-external"))]
+            slint_files: Default::default(),
+        }),
         reentry_guard,
         rh: Rc::new(rh),
     })
@@ -306,12 +529,47 @@ pub async fn process_preview_to_lsp_message(
             M::ShowDocument { file, selection } => {
                 send_show_document_to_editor(self.ctx.server_notifier.clone(), file, selection)
             }
+            M::ElementSelected { file, selection } => {
+                crate::preview::send_element_selected_notification(
+                    &self.ctx.server_notifier,
+                    &file,
+                    selection,
+                );
+            }
+            M::Dependencies { files } => {
+                crate::preview::send_dependencies_notification(&self.ctx.server_notifier, &files);
+            }
+            M::Style { style, available_styles } => {
+                crate::preview::send_style_notification(
+                    &self.ctx.server_notifier,
+                    style,
+                    available_styles,
+                );
+            }
+            M::ResourceUrls { resources } => {
+                crate::preview::send_resource_urls_notification(
+                    &self.ctx.server_notifier,
+                    resources,
+                );
+            }
+            M::Loading { loading } => {
+                crate::preview::send_loading_notification(&self.ctx.server_notifier, loading);
+            }
             M::PreviewTypeChanged { is_external: _ } => {
                 // Nothing to do!
             }
             M::RequestState { .. } => {
                 crate::language::request_state(&self.ctx);
             }
+            M::SendWorkspaceEdit { id, label, edit, confirm } => {
+                send_workspace_edit(self.ctx.server_notifier.clone(), id, label, edit, confirm);
+            }
+            M::DiagnosticsReport { report } => {
+                crate::preview::send_log_message_notification(&self.ctx.server_notifier, &report);
+            }
+            M::ComponentSize { size } => {
+                crate::preview::send_component_size_notification(&self.ctx.server_notifier, size);
+            }
         }
         Ok(())
     }
@@ -341,6 +599,27 @@ pub fn reload_document(&self, content: String, uri: JsValue, version: i32) -> js
         })
     }
 
+    #[wasm_bindgen]
+    pub fn cursor_moved(&self, params: JsValue) -> JsResult<()> {
+        let params: lsp_ext::CursorMovedParams = serde_wasm_bindgen::from_value(params)?;
+        if let Some(path) = language::uri_to_file(&params.uri) {
+            // LSP positions start at 0
+            self.ctx.preview.cursor_moved(
+                path,
+                params.position.line + 1,
+                params.position.character + 1,
+            );
+        }
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn active_document_changed(&self, params: JsValue) -> JsResult<()> {
+        let params: lsp_ext::ActiveDocumentChangedParams = serde_wasm_bindgen::from_value(params)?;
+        language::auto_open_preview(&self.ctx, &params.uri);
+        Ok(())
+    }
+
     #[wasm_bindgen]
     pub fn handle_request(&self, _id: JsValue, method: String, params: JsValue) -> js_sys::Promise {
         let guard = self.reentry_guard.clone();
@@ -395,3 +674,30 @@ pub fn send_show_document_to_editor(
         fut.await.unwrap();
     });
 }
+
+// Send `edit` to the editor and report to the preview whether it got applied
+pub fn send_workspace_edit(
+    sender: ServerNotifier,
+    id: u64,
+    label: Option<String>,
+    edit: lsp_types::WorkspaceEdit,
+    confirm: bool,
+) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let applied = if confirm && !language::confirm_preview_edit(&sender, label.as_deref()).await
+        {
+            false
+        } else if let Ok(fut) = sender.send_request::<lsp_types::request::ApplyWorkspaceEdit>(
+            lsp_types::ApplyWorkspaceEditParams { label, edit },
+        ) {
+            fut.await.map_or(false, |response| response.applied)
+        } else {
+            false
+        };
+        #[cfg(feature = "preview-external")]
+        let _ = sender.send_notification(
+            "slint/lsp_to_preview".to_string(),
+            crate::common::LspToPreviewMessage::WorkspaceEditApplied { id, applied },
+        );
+    });
+}